@@ -0,0 +1,31 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// Decodes arbitrary bytes as base64, as the values of LDIF records are.
+// Decoding must not panic, and what is decoded must encode and decode to the
+// same bytes.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate slapi_r_plugin;
+
+use slapi_r_plugin::ldif::base64_decode;
+use slapi_r_plugin::ldif::base64_encode;
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(decoded) = ::std::str::from_utf8(data).ok().and_then(base64_decode) {
+        assert_eq!(base64_decode(&base64_encode(&decoded)), Some(decoded));
+    }
+    assert_eq!(base64_decode(&base64_encode(data)).as_ref().map(|d| &d[..]), Some(data));
+});
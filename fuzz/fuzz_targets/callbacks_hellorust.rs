@@ -0,0 +1,28 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// Calls the callbacks of hellorust with arbitrary mock pblocks. See
+// slapi_r_plugin::replay::fuzz_callbacks.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate hellorust;
+extern crate slapi_r_plugin;
+
+use hellorust::HellorustPlugin;
+use slapi_r_plugin::replay::fuzz_callbacks;
+
+fuzz_target!(|data: &[u8]| {
+    fuzz_callbacks(&HellorustPlugin::plugin_functions(), data);
+});
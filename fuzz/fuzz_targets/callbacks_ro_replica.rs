@@ -0,0 +1,28 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// Calls the callbacks of ro_replica with arbitrary mock pblocks. See
+// slapi_r_plugin::replay::fuzz_callbacks.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate ro_replica;
+extern crate slapi_r_plugin;
+
+use ro_replica::RoReplicaPlugin;
+use slapi_r_plugin::replay::fuzz_callbacks;
+
+fuzz_target!(|data: &[u8]| {
+    fuzz_callbacks(&RoReplicaPlugin::plugin_functions(), data);
+});
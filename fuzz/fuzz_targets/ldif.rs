@@ -0,0 +1,34 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// Reads arbitrary bytes as LDIF. Reading must fail with an error rather than
+// panic, and the records that are read must write LDIF that reads back as
+// the same records.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate slapi_r_plugin;
+
+use slapi_r_plugin::ldif;
+use slapi_r_plugin::ldif::LdifReader;
+use slapi_r_plugin::ldif::LdifRecord;
+
+fuzz_target!(|data: &[u8]| {
+    let records: Vec<LdifRecord> = LdifReader::new(data).take_while(Result::is_ok).filter_map(Result::ok).collect();
+    let written = ldif::to_string(&records);
+    match ldif::parse_str(&written) {
+        Ok(read) => assert_eq!(read, records, "{}", written),
+        Err(e) => panic!("{:?} reading back\n{}", e, written),
+    }
+});
@@ -0,0 +1,31 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// Reads arbitrary bytes as a record file of the replay module. Reading must
+// fail with an error rather than panic, and the records that are read must be
+// replayable, which parses their entries and modifications again.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate slapi_r_plugin;
+
+use slapi_r_plugin::plugin::Slapi_R_Plugin_FN;
+use slapi_r_plugin::replay;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(records) = replay::read_records(data) {
+        // No callbacks are registered, so only the mock pblocks are built.
+        replay::replay(&Slapi_R_Plugin_FN::new(), &records);
+    }
+});
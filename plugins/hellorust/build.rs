@@ -0,0 +1,20 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// Passes the plugin metadata in Cargo.toml to the compiler. enable.ldif and
+// the rs389 class are written by cargo xtask generate.
+
+extern crate slapi_r_plugin_build;
+
+fn main() {
+    slapi_r_plugin_build::configure();
+}
@@ -19,37 +19,53 @@
 
 #[macro_use]
 extern crate slapi_r_plugin;
+extern crate slapi_r_plugin_derive;
 
 /// Definition of the plugin subsystem for logging
-const SUBSYSTEM: &'static str = "plugins::ro_replica";
+const SUBSYSTEM: &str = "plugins::ro_replica";
 
-use slapi_r_plugin::constants;
 use slapi_r_plugin::constants::LogLevel;
 use slapi_r_plugin::error::PluginOperationError;
 use slapi_r_plugin::error::PluginRegistrationError;
 use slapi_r_plugin::log::slapi_r_log_error;
-use slapi_r_plugin::plugin::Slapi_R_Plugin_Manager;
-use slapi_r_plugin::plugin::Slapi_Plugin_V3;
+use slapi_r_plugin::log::Slapi_R_Log_Record;
+use slapi_r_plugin::log::Slapi_R_Access_Note;
+use slapi_r_plugin::log::slapi_r_log_access_note;
+use slapi_r_plugin::metrics;
 use slapi_r_plugin::pblock::Slapi_PBlock_V3;
 use slapi_r_plugin::pblock::Slapi_PBlock_Init_V3;
+use slapi_r_plugin_derive::slapi_plugin;
 
 
-struct RoReplicaPlugin {}
+/// The ro_replica plugin. Its callbacks are public through plugin_functions,
+/// so that the fuzz targets can call them.
+pub struct RoReplicaPlugin {}
 
+#[slapi_plugin]
 impl RoReplicaPlugin {
     /// This is the callback intercepting modifications. Because of the design of
     /// the plugin interface, one callback can handle all types. If the operation
     /// is replicated, or from internal, we allow it. If it's from external, we
     /// return an unwilling to perform.
+    #[pre_modify]
+    #[pre_modrdn]
+    #[pre_add]
+    #[pre_delete]
     fn intercept_operation<T: Slapi_PBlock_V3>( pb: &T ) -> Result<(), PluginOperationError> {
         let operation = pb.get_operation();
         match operation {
             Some(op) => {
                 if op.is_replicated() || op.is_internal() {
                     slapi_r_log_error_plugin!(LogLevel::INFO, SUBSYSTEM, format!("Operation is replicated or internal, allowing\n"));
+                    metrics::registry().counter("allowed_operations").inc();
                     Ok(())
                 } else {
-                    slapi_r_log_error_plugin!(LogLevel::INFO, SUBSYSTEM, format!("Operation is external, rejecting!\n"));
+                    slapi_r_log_structured_plugin!(LogLevel::INFO, SUBSYSTEM,
+                        Slapi_R_Log_Record::new(pb, "Operation is external, rejecting").outcome("reject"));
+                    if slapi_r_log_access_note(&op, &Slapi_R_Access_Note::new().action("reject")).is_err() {
+                        return Err(PluginOperationError::LoggingError);
+                    }
+                    metrics::registry().counter("rejected_operations").inc();
                     pb.send_ldap_result(PluginOperationError::UnwillingToPerform, "Can not modify readonly database.");
                     Err(PluginOperationError::UnwillingToPerform)
                 }
@@ -61,41 +77,19 @@ impl RoReplicaPlugin {
             }
         }
     } // intercept_operation()
-}
-
-
-impl Slapi_Plugin_V3 for RoReplicaPlugin {
-    ///
-    /// This is the initialisation function for the Ro Replica Rust plugin.
-    ///
-    /// This function will assign all the callbacks from this function into
-    /// Directory Server.
-    ///
-    fn init<T: Slapi_PBlock_Init_V3>( pb: T ) -> Result<(), PluginRegistrationError> {
-
-        match slapi_r_log_error(LogLevel::INFO, SUBSYSTEM, format!("ro_replica started\n")) {
-            Ok(_) => {},
-            Err(_) => return Err(PluginRegistrationError::LoggingError),
-        };
 
-        let mut p_manager: Slapi_R_Plugin_Manager = Slapi_R_Plugin_Manager::new();
-
-        p_manager.functions.start = Some(RoReplicaPlugin::start);
-        p_manager.functions.close = Some(RoReplicaPlugin::close);
-        p_manager.functions.pre_modify = Some(RoReplicaPlugin::intercept_operation);
-        p_manager.functions.pre_modrdn = Some(RoReplicaPlugin::intercept_operation);
-        p_manager.functions.pre_add = Some(RoReplicaPlugin::intercept_operation);
-        p_manager.functions.pre_delete = Some(RoReplicaPlugin::intercept_operation);
-
-        match p_manager.register(pb) {
+    /// Log that the plugin is being registered.
+    #[init]
+    fn hello<T: Slapi_PBlock_Init_V3>( _: &T ) -> Result<(), PluginRegistrationError> {
+        match slapi_r_log_error(LogLevel::INFO, SUBSYSTEM, "ro_replica started\n".to_string()) {
             Ok(_) => Ok(()),
-            Err(err) => Err(err),
+            Err(_) => Err(PluginRegistrationError::LoggingError),
         }
-
     }
 
     /// A start callback, that allows the plugin to initialise and start any required
     /// datastructures, etc.
+    #[start]
     fn start<T: Slapi_PBlock_V3>( _: &T ) -> Result<(), PluginOperationError> {
         slapi_r_log_error_plugin!(LogLevel::INFO, SUBSYSTEM, format!("Calling the ro_replica start callback \n") );
         Ok(())
@@ -103,22 +97,27 @@ impl Slapi_Plugin_V3 for RoReplicaPlugin {
 
     /// A close callback, that allows the plugin to destroy any structuse made in
     /// the start callback
+    #[close]
     fn close<T: Slapi_PBlock_V3>( _: &T ) -> Result<(), PluginOperationError> {
         slapi_r_log_error_plugin!(LogLevel::INFO, SUBSYSTEM, format!("Calling the ro_replica close callback \n") );
         Ok(())
     }
-
 }
 
-
-// This is the magic that links a static no_mangle fn into the .so, and the rust
-// init types
-slapi_r_plugin_init!(RoReplicaPlugin);
-
-
 #[cfg(test)]
 mod tests {
+    #[cfg(slapi_r_mock)]
+    use super::RoReplicaPlugin;
+    #[cfg(slapi_r_mock)]
+    use slapi_r_plugin::replay::assert_replay;
+
     #[test]
     fn it_works() {
     }
+
+    #[cfg(slapi_r_mock)]
+    #[test]
+    fn test_replay_external_writes() {
+        assert_replay(&RoReplicaPlugin::plugin_functions(), include_str!("../replay/external_writes.jsonl"));
+    }
 }
@@ -6,9 +6,6 @@ Release:          1%{?dist}
 License:          GPLv3+
 URL:              http://github.com/Firstyear/ds-rust-plugins
 Group:            Development/Libraries
-BuildRequires:    autoconf
-BuildRequires:    automake
-BuildRequires:    libtool
 BuildRequires:    rust
 BuildRequires:    cargo
 BuildRequires:    389-ds-base-devel
@@ -28,25 +25,18 @@ helloworld example and read only database plugin.
 %setup -q
 
 %build
-# Is there a macro that does this?
-autoreconf -i
-%configure
-make
+cargo xtask build --release
 
 %install
-%{__make} install DESTDIR=$RPM_BUILD_ROOT INSTALL="%{__install} -p"
-# %{__rm} -f $RPM_BUILD_ROOT%{_libdir}/lib*.a
-#%{__rm} -f $RPM_BUILD_ROOT%{_libdir}/lib*.la
+cargo xtask stage --release --destdir $RPM_BUILD_ROOT --libdir %{_libdir} --datadir %{_datadir}
 
 %clean
-make distclean
+cargo clean
 
 %files
 %defattr(-,root,root,-)
 %doc %{_datadir}/dirsrv/
-%{_libdir}/dirsrv/plugins/*.so*
-%exclude %{_libdir}/dirsrv/plugins/*.la
-%exclude %{_libdir}/dirsrv/plugins/*.a
+%{_libdir}/dirsrv/plugins/*.so
 
 %changelog
 * Sat Sep 24 2016 William Brown <wibrown@redhat.com> - 0.1.0-1
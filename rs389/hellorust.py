@@ -0,0 +1,31 @@
+# --- BEGIN COPYRIGHT BLOCK ---
+# Copyright (C) 2016 Red Hat, Inc.
+# All rights reserved.
+#
+# License: GPL (version 3 or any later version).
+# See LICENSE for details.
+# --- END COPYRIGHT BLOCK ---
+#
+# Generated from Cargo.toml by slapi_r_plugin_build. Do not edit.
+#
+
+from lib389.plugins import Plugin
+
+class HellorustPlugin(Plugin):
+    def __init__(self, instance, dn="cn=hellorust,cn=plugins,cn=config", batch=False):
+        super(HellorustPlugin, self).__init__(instance, dn, batch)
+        self._default_properties = {
+            'nsslapd-pluginPath': 'libhellorust',
+            'nsslapd-pluginInitfunc': 'slapi_r_plugin_init_fn',
+            'nsslapd-pluginType': 'preoperation',
+            'nsslapd-pluginEnabled': 'on',
+            'nsslapd-plugin-depends-on-type': 'database',
+            'nsslapd-pluginId': 'Hello Rust',
+            'nsslapd-pluginVersion': '0.1.0',
+            'nsslapd-pluginVendor': '389 Project',
+            'nsslapd-pluginDescription': 'Hello Rust!',
+        }
+
+    def create(self, rdn="hellorust", properties={}, basedn="cn=plugins,cn=config"):
+        self._default_properties.update(properties)
+        super(HellorustPlugin, self).create(rdn, self._default_properties, basedn)
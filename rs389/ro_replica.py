@@ -0,0 +1,31 @@
+# --- BEGIN COPYRIGHT BLOCK ---
+# Copyright (C) 2016 Red Hat, Inc.
+# All rights reserved.
+#
+# License: GPL (version 3 or any later version).
+# See LICENSE for details.
+# --- END COPYRIGHT BLOCK ---
+#
+# Generated from Cargo.toml by slapi_r_plugin_build. Do not edit.
+#
+
+from lib389.plugins import Plugin
+
+class RoreplicaPlugin(Plugin):
+    def __init__(self, instance, dn="cn=ro_replica,cn=plugins,cn=config", batch=False):
+        super(RoreplicaPlugin, self).__init__(instance, dn, batch)
+        self._default_properties = {
+            'nsslapd-pluginPath': 'libro_replica',
+            'nsslapd-pluginInitfunc': 'slapi_r_plugin_init_fn',
+            'nsslapd-pluginType': 'preoperation',
+            'nsslapd-pluginEnabled': 'on',
+            'nsslapd-plugin-depends-on-type': 'database',
+            'nsslapd-pluginId': 'ro_replica',
+            'nsslapd-pluginVersion': '0.1.0',
+            'nsslapd-pluginVendor': '389 Project',
+            'nsslapd-pluginDescription': 'Prevents writes to this instance from external sources.',
+        }
+
+    def create(self, rdn="ro_replica", properties={}, basedn="cn=plugins,cn=config"):
+        self._default_properties.update(properties)
+        super(RoreplicaPlugin, self).create(rdn, self._default_properties, basedn)
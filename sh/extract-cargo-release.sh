@@ -1,2 +0,0 @@
-#!/bin/sh
-cat $1 | grep -E '^version.*' | awk '{print $3}'
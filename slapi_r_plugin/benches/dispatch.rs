@@ -0,0 +1,86 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// The cost slapi_r_plugin adds to every plugin callback. The callbacks are
+// registered and called through the mock backend as Directory Server would,
+// so compare callback/pre_search with callback/direct for the overhead of
+// dispatch. Run with:
+//
+// cargo bench -p slapi_r_plugin
+
+#[cfg(slapi_r_mock)]
+#[macro_use]
+extern crate criterion;
+#[cfg(slapi_r_mock)]
+#[macro_use]
+extern crate slapi_r_plugin;
+
+#[cfg(slapi_r_mock)]
+mod benches {
+    use criterion::black_box;
+    use criterion::Criterion;
+    use slapi_r_plugin::constants::*;
+    use slapi_r_plugin::error::PluginOperationError;
+    use slapi_r_plugin::metrics;
+    use slapi_r_plugin::mock;
+    use slapi_r_plugin::pblock::Slapi_R_PBlock;
+    use slapi_r_plugin::plugin::Slapi_R_Plugin_Manager;
+
+    const SUBSYSTEM: &str = "benches::dispatch";
+
+    fn allow(_: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        Ok(())
+    }
+
+    fn reject(_: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        Err(PluginOperationError::UnwillingToPerform)
+    }
+
+    // As most per-search plugins, which log at a level that is not enabled.
+    fn log_plugin(_: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        slapi_r_log_error_plugin!(LogLevel::PLUGIN, SUBSYSTEM, format!("Entry returned\n"));
+        Ok(())
+    }
+
+    pub fn callbacks(c: &mut Criterion) {
+        let mut manager = Slapi_R_Plugin_Manager::new();
+        manager.functions.pre_search = Some(allow);
+        manager.functions.pre_add = Some(reject);
+        manager.functions.pre_entry = Some(log_plugin);
+        let pb = mock::register(manager).unwrap();
+        mock::set_log_enabled(false);
+
+        c.bench_function("callback/direct", |b| b.iter(|| black_box(allow)(&pb)));
+        c.bench_function("callback/pre_search", |b| b.iter(|| mock::call_plugin_fn(&pb, SLAPI_PLUGIN_PRE_SEARCH_FN)));
+        c.bench_function("callback/pre_add_error", |b| b.iter(|| mock::call_plugin_fn(&pb, SLAPI_PLUGIN_PRE_ADD_FN)));
+        c.bench_function("callback/pre_entry_log_disabled", |b| b.iter(|| mock::call_plugin_fn(&pb, SLAPI_PLUGIN_PRE_ENTRY_FN)));
+
+        mock::set_log_enabled(true);
+        pb.destroy();
+    }
+
+    pub fn counters(c: &mut Criterion) {
+        let counter = metrics::registry().counter("bench_calls");
+        c.bench_function("metrics/counter_lookup", |b| b.iter(|| metrics::registry().counter(black_box("bench_calls")).inc()));
+        c.bench_function("metrics/counter_kept", |b| b.iter(|| counter.inc()));
+    }
+}
+
+#[cfg(slapi_r_mock)]
+criterion_group!(benches, benches::callbacks, benches::counters);
+#[cfg(slapi_r_mock)]
+criterion_main!(benches);
+
+// Without the mock backend there is no Directory Server to call the
+// callbacks, so there is nothing to measure.
+#[cfg(not(slapi_r_mock))]
+fn main() {}
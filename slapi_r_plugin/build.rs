@@ -10,7 +10,112 @@
 // Author: William Brown <wibrown@redhat.com>
 //
 
-// Do nothing, and do it well.
-fn main() {
+// Locate libslapd, so that the crate links against it. In order, this uses
+// DIRSRV_LIB_DIR, DIRSRV_PREFIX, and then pkg-config with dirsrv.pc. When
+// none of these find libslapd, the mock backend in src/mock is built instead,
+// so that the crate can be built and tested without Directory Server.
+//
+// A [target.<triple>.slapd] section in .cargo/config still overrides all of
+// this, as this crate sets links = "slapd".
+
+extern crate cc;
+extern crate pkg_config;
+
+use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+
+struct Slapd {
+    lib_dir: PathBuf,
+    include_dir: Option<PathBuf>,
+}
+
+fn has_libslapd(dir: &Path) -> bool {
+    dir.join("libslapd.so").exists()
+}
+
+// DS installs libslapd in <libdir>/dirsrv, and some distributions use lib64.
+fn from_prefix(prefix: PathBuf) -> Option<Slapd> {
+    let include_dir = prefix.join("include/dirsrv");
+    ["lib64/dirsrv", "lib/dirsrv"].iter()
+        .map(|d| prefix.join(d))
+        .find(|d| has_libslapd(d))
+        .map(|lib_dir| Slapd {
+            lib_dir,
+            include_dir: if include_dir.exists() { Some(include_dir) } else { None },
+        })
+}
+
+fn from_pkg_config() -> Option<Slapd> {
+    let lib = match pkg_config::Config::new().cargo_metadata(false).probe("dirsrv") {
+        Ok(lib) => lib,
+        Err(_) => return None,
+    };
+    // dirsrv.pc may be installed without the library, IE by a devel package
+    // for another architecture, so check that libslapd is really there.
+    let lib_dir = match lib.link_paths.iter().find(|d| has_libslapd(d)) {
+        Some(d) => d.clone(),
+        None => return None,
+    };
+    Some(Slapd {
+        lib_dir,
+        include_dir: lib.include_paths.into_iter().find(|d| d.join("slapi-plugin.h").exists()),
+    })
+}
+
+fn find_slapd() -> Option<Slapd> {
+    // An override that does not work is an error, rather than a reason to
+    // quietly build the mock backend.
+    if let Ok(dir) = env::var("DIRSRV_LIB_DIR") {
+        let lib_dir = PathBuf::from(dir);
+        if !has_libslapd(&lib_dir) {
+            panic!("DIRSRV_LIB_DIR is set, but {} does not contain libslapd.so", lib_dir.display());
+        }
+        let include_dir = env::var("DIRSRV_PREFIX").ok().map(|p| PathBuf::from(p).join("include/dirsrv"));
+        return Some(Slapd { lib_dir, include_dir });
+    }
+    if let Ok(prefix) = env::var("DIRSRV_PREFIX") {
+        match from_prefix(PathBuf::from(&prefix)) {
+            Some(slapd) => return Some(slapd),
+            None => panic!("DIRSRV_PREFIX is set, but {}/lib/dirsrv does not contain libslapd.so", prefix),
+        }
+    }
+    from_pkg_config()
 }
 
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/mock/log.c");
+    println!("cargo:rerun-if-env-changed=DIRSRV_LIB_DIR");
+    println!("cargo:rerun-if-env-changed=DIRSRV_PREFIX");
+    println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+    println!("cargo:rustc-check-cfg=cfg(slapi_r_mock)");
+
+    match find_slapd() {
+        Some(slapd) => {
+            println!("cargo:rustc-link-search=native={}", slapd.lib_dir.display());
+            println!("cargo:rustc-link-lib=dylib=slapd");
+            // Available to dependent build scripts as DEP_SLAPD_ROOT and
+            // DEP_SLAPD_INCLUDE.
+            println!("cargo:root={}", slapd.lib_dir.display());
+            if let Some(include_dir) = slapd.include_dir {
+                println!("cargo:include={}", include_dir.display());
+            }
+        }
+        None => {
+            println!("cargo:warning=libslapd not found, set DIRSRV_PREFIX or DIRSRV_LIB_DIR. Building the mock backend, for testing only.");
+            // The variadic functions can not be written in stable Rust.
+            cc::Build::new()
+                .file("src/mock/log.c")
+                .compile("slapi_r_mock");
+            println!("cargo:rustc-cfg=slapi_r_mock");
+            // libslapd exports its functions to the plugins it loads, so
+            // export those of the mock from the test and bench binaries, where
+            // capability detection can find them.
+            println!("cargo:rustc-link-arg=-rdynamic");
+            // Available to the build scripts of plugins as DEP_SLAPD_MOCK,
+            // so that their tests can use the mock backend.
+            println!("cargo:mock=1");
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use super::constants::*;
+
+/// The access right that a plugin wishes to check on behalf of the identity
+/// of the current operation. These map to the SLAPI_ACL_* values that
+/// slapi_access_allowed accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// May the identity compare values of the attribute.
+    Compare,
+    /// May the identity use the attribute in a search filter.
+    Search,
+    /// May the identity read values of the attribute.
+    Read,
+    /// May the identity modify values of the attribute.
+    Write,
+    /// May the identity delete the entry.
+    Delete,
+    /// May the identity add the entry.
+    Add,
+    /// May the identity add or remove its own DN as a value of the attribute.
+    SelfWrite,
+    /// May the identity proxy as another identity.
+    Proxy,
+}
+
+impl Access {
+    /// Convert the access right to the SLAPI_ACL_* value that Directory Server
+    /// can interpret.
+    pub fn as_ds_isize(&self) -> isize {
+        match *self {
+            Access::Compare => SLAPI_ACL_COMPARE,
+            Access::Search => SLAPI_ACL_SEARCH,
+            Access::Read => SLAPI_ACL_READ,
+            Access::Write => SLAPI_ACL_WRITE,
+            Access::Delete => SLAPI_ACL_DELETE,
+            Access::Add => SLAPI_ACL_ADD,
+            Access::SelfWrite => SLAPI_ACL_SELF,
+            Access::Proxy => SLAPI_ACL_PROXY,
+        }
+    }
+}
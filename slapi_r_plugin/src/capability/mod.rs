@@ -0,0 +1,183 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use libc;
+use slapi_r_plugin_sys as sys;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::mem;
+use std::os::raw::c_char;
+use std::sync::Mutex;
+use super::constants::*;
+
+/// A feature of Directory Server that not every version provides. Each is
+/// detected by looking up a function that the feature requires in the
+/// running server, so that a plugin can disable the feature rather than
+/// fail on a missing symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    /// Backend transaction plugin types, IE betxnpreoperation. Callbacks of
+    /// these types are refused at registration without it.
+    BeTxn,
+    /// Virtual attribute service providers. These are not registered as
+    /// callbacks, so a plugin should check this before it calls the
+    /// slapi_vattrspi_* functions.
+    Vattr,
+    /// Registering further plugins from one shared object.
+    SubPlugins,
+    /// Registering further plugins with a precedence.
+    PluginPrecedence,
+    /// Administrative tasks under cn=tasks,cn=config.
+    Tasks,
+    /// Checking if a log level is enabled before formatting a message.
+    LogLevelCheck,
+}
+
+/// Every capability, in the order they are reported.
+pub const CAPABILITIES: [Capability; 6] = [
+    Capability::BeTxn,
+    Capability::Vattr,
+    Capability::SubPlugins,
+    Capability::PluginPrecedence,
+    Capability::Tasks,
+    Capability::LogLevelCheck,
+];
+
+// The detected capabilities. The server can not change while it runs, so
+// the lookups are only done once.
+static DETECTED: Mutex<Option<Vec<Capability>>> = Mutex::new(None);
+
+impl Capability {
+    /// The function whose presence shows the server has this capability.
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Capability::BeTxn => "slapi_back_transaction_begin",
+            Capability::Vattr => "slapi_vattrspi_register",
+            Capability::SubPlugins => "slapi_register_plugin",
+            Capability::PluginPrecedence => "slapi_register_plugin_ext",
+            Capability::Tasks => "slapi_task_register_handler",
+            Capability::LogLevelCheck => "slapi_is_loglevel_set",
+        }
+    }
+
+    /// The name of the capability, as it is logged.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Capability::BeTxn => "betxn",
+            Capability::Vattr => "vattr",
+            Capability::SubPlugins => "sub_plugins",
+            Capability::PluginPrecedence => "plugin_precedence",
+            Capability::Tasks => "tasks",
+            Capability::LogLevelCheck => "log_level_check",
+        }
+    }
+
+    /// Returns true if the running server provides this capability.
+    pub fn is_available(&self) -> bool {
+        slapi_r_capabilities().contains(self)
+    }
+}
+
+/// Returns true if the named function can be found in the running process.
+/// Plugins are loaded with lazy binding, so a function of libslapd that does
+/// not exist only fails when it is first called. Checking first allows the
+/// plugin to avoid the call.
+pub fn slapi_r_symbol_available(name: &str) -> bool {
+    let c_name = match CString::new(name) {
+        Ok(n) => n,
+        Err(_) => return false,
+    };
+    unsafe { !libc::dlsym(libc::RTLD_DEFAULT, c_name.as_ptr()).is_null() }
+}
+
+/// Returns the capability that a pblock slot needs, if the slot is not known
+/// to every server.
+pub fn slot_capability(pblock_type: isize) -> Option<Capability> {
+    match pblock_type {
+        SLAPI_PLUGIN_BE_TXN_PRE_ADD_FN |
+        SLAPI_PLUGIN_BE_TXN_PRE_MODIFY_FN |
+        SLAPI_PLUGIN_BE_TXN_PRE_MODRDN_FN |
+        SLAPI_PLUGIN_BE_TXN_PRE_DELETE_FN |
+        SLAPI_PLUGIN_BE_TXN_PRE_DELETE_TOMBSTONE_FN |
+        SLAPI_PLUGIN_BE_TXN_POST_ADD_FN |
+        SLAPI_PLUGIN_BE_TXN_POST_MODIFY_FN |
+        SLAPI_PLUGIN_BE_TXN_POST_MODRDN_FN |
+        SLAPI_PLUGIN_BE_TXN_POST_DELETE_FN |
+        SLAPI_TXN => Some(Capability::BeTxn),
+        _ => None,
+    }
+}
+
+/// Returns true if the running server knows this SLAPI_* pblock slot. A slot
+/// that belongs to a capability is known if the capability is available, and
+/// every other slot is known to all servers.
+///
+/// The server can not be asked directly: slapi_pblock_get writes a value of
+/// the size of that slot, IE a whole PRNetAddr for SLAPI_CONN_CLIENTNETADDR,
+/// so probing a slot of unknown size could overrun any buffer we give it.
+pub fn slapi_r_slot_available(pblock_type: isize) -> bool {
+    match slot_capability(pblock_type) {
+        Some(c) => c.is_available(),
+        None => true,
+    }
+}
+
+/// Returns the capabilities that the running server provides.
+pub fn slapi_r_capabilities() -> Vec<Capability> {
+    let mut detected = DETECTED.lock().unwrap();
+    if detected.is_none() {
+        *detected = Some(CAPABILITIES.iter().cloned().filter(|c| slapi_r_symbol_available(c.symbol())).collect());
+    }
+    detected.as_ref().unwrap().clone()
+}
+
+/// Returns the version of the running server, IE 1.3.6.1, if the server
+/// reports it.
+pub fn slapi_r_server_version() -> Option<String> {
+    let c_name = CString::new("slapd_get_version_value").unwrap();
+    unsafe {
+        let f = libc::dlsym(libc::RTLD_DEFAULT, c_name.as_ptr());
+        if f.is_null() {
+            return None;
+        }
+        let get_version: extern "C" fn() -> *mut c_char = mem::transmute(f);
+        let mut version = get_version();
+        if version.is_null() {
+            return None;
+        }
+        let v = CStr::from_ptr(version).to_string_lossy().into_owned();
+        sys::slapi_ch_free_string(&mut version);
+        Some(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_symbol_available() {
+        assert!(slapi_r_symbol_available("malloc"));
+        assert!(!slapi_r_symbol_available("slapi_r_no_such_function"));
+        assert!(!slapi_r_symbol_available("bad\0name"));
+    }
+
+    #[test]
+    fn test_capability_slots() {
+        assert_eq!(slot_capability(SLAPI_PLUGIN_BE_TXN_POST_ADD_FN), Some(Capability::BeTxn));
+        assert_eq!(slot_capability(SLAPI_TXN), Some(Capability::BeTxn));
+        assert_eq!(slot_capability(SLAPI_PLUGIN_PRE_ADD_FN), None);
+        assert!(slapi_r_slot_available(SLAPI_PLUGIN_PRE_ADD_FN));
+    }
+}
@@ -12,95 +12,293 @@
 
 #![warn(missing_docs)]
 
+// The values come from slapi_r_plugin-sys, so that with its bindgen feature they
+// are checked against slapi-plugin.h.
+use slapi_r_plugin_sys as sys;
+
 /// A successful operation. Most Directory Server fuctions expect this.
-pub const LDAP_SUCCESS: isize = 0;
+pub const LDAP_SUCCESS: isize = sys::LDAP_SUCCESS as isize;
+/// An internal error occured processing the operation.
+pub const LDAP_OPERATIONS_ERROR: isize = sys::LDAP_OPERATIONS_ERROR as isize;
+/// The server is unwilling to perform the operation.
+pub const LDAP_UNWILLING_TO_PERFORM: isize = sys::LDAP_UNWILLING_TO_PERFORM as isize;
+/// A required attribute is missing, or an attribute is not allowed.
+pub const LDAP_OBJECT_CLASS_VIOLATION: isize = sys::LDAP_OBJECT_CLASS_VIOLATION as isize;
+/// The entry being added already exists.
+pub const LDAP_ALREADY_EXISTS: isize = sys::LDAP_ALREADY_EXISTS as isize;
+/// The client does not have sufficient access to perform the operation.
+pub const LDAP_INSUFFICIENT_ACCESS: isize = sys::LDAP_INSUFFICIENT_ACCESS as isize;
 
 
-// Constants used for plugin types! These should be an enum one day. This is not complete!
+// Constants used for plugin types! These should be an enum one day. More are
+// available from slapi_r_plugin-sys.
 
+/// PBlock constant to retrieve the entry of an add operation.
+pub const SLAPI_ADD_ENTRY: isize = sys::SLAPI_ADD_ENTRY as isize;
+/// PBlock constant to retrieve the modifications of a modify operation.
+pub const SLAPI_MODIFY_MODS: isize = sys::SLAPI_MODIFY_MODS as isize;
+/// PBlock constant to retrieve the filter of a search operation, as a string.
+pub const SLAPI_SEARCH_STRFILTER: isize = sys::SLAPI_SEARCH_STRFILTER as isize;
 /// PBlock constant to retrieve the current operation
-pub const SLAPI_OPERATION: isize = 132;
+pub const SLAPI_OPERATION: isize = sys::SLAPI_OPERATION as isize;
+/// PBlock constant to retrieve the id of the connection the operation is on.
+pub const SLAPI_CONN_ID: isize = sys::SLAPI_CONN_ID as isize;
+/// PBlock constant to retrieve the time the current operation was started.
+pub const SLAPI_OPINITIATED_TIME: isize = sys::SLAPI_OPINITIATED_TIME as isize;
+/// PBlock constant to retrieve the DN the current operation is authorised as.
+pub const SLAPI_REQUESTOR_DN: isize = sys::SLAPI_REQUESTOR_DN as isize;
 // PBlock constant for requesting if the current operation is Replicated
 // pub const SLAPI_IS_REPLICATED_OPERATION: isize = 142;
+/// PBlock constant to retrieve the id of the current operation.
+pub const SLAPI_OPERATION_ID: isize = sys::SLAPI_OPERATION_ID as isize;
+/// PBlock constant to retrieve the backend transaction, if one is open.
+pub const SLAPI_TXN: isize = sys::SLAPI_TXN as isize;
 
 /// PBlock constant for retrieving the next entry of an entry result list.
-pub const SLAPI_SEARCH_RESULT_ENTRY: isize = 194;
+pub const SLAPI_SEARCH_RESULT_ENTRY: isize = sys::SLAPI_SEARCH_RESULT_ENTRY as isize;
+/// PBlock constant for a copy of the search result entry, made by a pre_entry
+/// plugin so that it can change the entry that is sent. Directory Server
+/// sends the copy in place of the result entry, and frees it.
+pub const SLAPI_SEARCH_ENTRY_COPY: isize = sys::SLAPI_SEARCH_ENTRY_COPY as isize;
 /// PBlock constant for registering the close function for a plugin.
-pub const SLAPI_PLUGIN_CLOSE_FN: isize = 210;
+pub const SLAPI_PLUGIN_CLOSE_FN: isize = sys::SLAPI_PLUGIN_CLOSE_FN as isize;
 /// PBlock constant for registering the start function for a plugin.
-pub const SLAPI_PLUGIN_START_FN: isize = 212;
+pub const SLAPI_PLUGIN_START_FN: isize = sys::SLAPI_PLUGIN_START_FN as isize;
 
 /// PBlock constant for registering a pre BIND operation.
-pub const SLAPI_PLUGIN_PRE_BIND_FN: isize = 401;
+pub const SLAPI_PLUGIN_PRE_BIND_FN: isize = sys::SLAPI_PLUGIN_PRE_BIND_FN as isize;
 /// PBlock constant for registering a pre UNBIND operation.
-pub const SLAPI_PLUGIN_PRE_UNBIND_FN: isize = 402;
+pub const SLAPI_PLUGIN_PRE_UNBIND_FN: isize = sys::SLAPI_PLUGIN_PRE_UNBIND_FN as isize;
 /// PBlock constant for registering a pre SEARCH operation.
-pub const SLAPI_PLUGIN_PRE_SEARCH_FN: isize = 403;
+pub const SLAPI_PLUGIN_PRE_SEARCH_FN: isize = sys::SLAPI_PLUGIN_PRE_SEARCH_FN as isize;
 /// PBlock constant for registering a pre COMPARE operation.
-pub const SLAPI_PLUGIN_PRE_COMPARE_FN: isize = 404;
+pub const SLAPI_PLUGIN_PRE_COMPARE_FN: isize = sys::SLAPI_PLUGIN_PRE_COMPARE_FN as isize;
 /// PBlock constant for registering a pre MODIFY operation.
-pub const SLAPI_PLUGIN_PRE_MODIFY_FN: isize = 405;
+pub const SLAPI_PLUGIN_PRE_MODIFY_FN: isize = sys::SLAPI_PLUGIN_PRE_MODIFY_FN as isize;
 /// PBlock constant for registering a pre MODRDN operation.
-pub const SLAPI_PLUGIN_PRE_MODRDN_FN: isize = 406;
+pub const SLAPI_PLUGIN_PRE_MODRDN_FN: isize = sys::SLAPI_PLUGIN_PRE_MODRDN_FN as isize;
 /// PBlock constant for registering a pre ADD operation.
-pub const SLAPI_PLUGIN_PRE_ADD_FN: isize = 407;
+pub const SLAPI_PLUGIN_PRE_ADD_FN: isize = sys::SLAPI_PLUGIN_PRE_ADD_FN as isize;
 /// PBlock constant for registering a pre DELETE operation.
-pub const SLAPI_PLUGIN_PRE_DELETE_FN: isize = 408;
+pub const SLAPI_PLUGIN_PRE_DELETE_FN: isize = sys::SLAPI_PLUGIN_PRE_DELETE_FN as isize;
 /// PBlock constant for registering a pre ABANDON operation.
-pub const SLAPI_PLUGIN_PRE_ABANDON_FN: isize = 409;
+pub const SLAPI_PLUGIN_PRE_ABANDON_FN: isize = sys::SLAPI_PLUGIN_PRE_ABANDON_FN as isize;
 /// PBlock constant for registering a pre ENTRY operation.
-pub const SLAPI_PLUGIN_PRE_ENTRY_FN: isize = 410;
+pub const SLAPI_PLUGIN_PRE_ENTRY_FN: isize = sys::SLAPI_PLUGIN_PRE_ENTRY_FN as isize;
 /// PBlock constant for registering a pre REFERAL operation.
-pub const SLAPI_PLUGIN_PRE_REFERAL_FN: isize = 411;
+pub const SLAPI_PLUGIN_PRE_REFERAL_FN: isize = sys::SLAPI_PLUGIN_PRE_REFERAL_FN as isize;
 /// PBlock constant for registering a pre RESULT operation.
-pub const SLAPI_PLUGIN_PRE_RESULT_FN: isize = 412;
+pub const SLAPI_PLUGIN_PRE_RESULT_FN: isize = sys::SLAPI_PLUGIN_PRE_RESULT_FN as isize;
 /// PBlock constant for registering a pre EXTOP operation.
-pub const SLAPI_PLUGIN_PRE_EXTOP_FN: isize = 413;
+pub const SLAPI_PLUGIN_PRE_EXTOP_FN: isize = sys::SLAPI_PLUGIN_PRE_EXTOP_FN as isize;
+/// PBlock constant for registering a pre internal add operation.
+pub const SLAPI_PLUGIN_INTERNAL_PRE_ADD_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_PRE_ADD_FN as isize;
+/// PBlock constant for registering a pre internal modify operation.
+pub const SLAPI_PLUGIN_INTERNAL_PRE_MODIFY_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_PRE_MODIFY_FN as isize;
+/// PBlock constant for registering a pre internal modrdn operation.
+pub const SLAPI_PLUGIN_INTERNAL_PRE_MODRDN_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_PRE_MODRDN_FN as isize;
+/// PBlock constant for registering a pre internal delete operation.
+pub const SLAPI_PLUGIN_INTERNAL_PRE_DELETE_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_PRE_DELETE_FN as isize;
+/// PBlock constant for registering a pre backend add operation.
+pub const SLAPI_PLUGIN_BE_PRE_ADD_FN: isize = sys::SLAPI_PLUGIN_BE_PRE_ADD_FN as isize;
+/// PBlock constant for registering a pre backend modify operation.
+pub const SLAPI_PLUGIN_BE_PRE_MODIFY_FN: isize = sys::SLAPI_PLUGIN_BE_PRE_MODIFY_FN as isize;
+/// PBlock constant for registering a pre backend modrdn operation.
+pub const SLAPI_PLUGIN_BE_PRE_MODRDN_FN: isize = sys::SLAPI_PLUGIN_BE_PRE_MODRDN_FN as isize;
+/// PBlock constant for registering a pre backend delete operation.
+pub const SLAPI_PLUGIN_BE_PRE_DELETE_FN: isize = sys::SLAPI_PLUGIN_BE_PRE_DELETE_FN as isize;
 /// PBlock constant for registering a pre BE_TXN add operation.
-pub const SLAPI_PLUGIN_BE_TXN_PRE_ADD_FN: isize = 460;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_ADD_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_PRE_ADD_FN as isize;
 /// PBlock constant for registering a pre BE_TXN modify operation.
-pub const SLAPI_PLUGIN_BE_TXN_PRE_MODIFY_FN: isize = 461;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_MODIFY_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_PRE_MODIFY_FN as isize;
 /// PBlock constant for registering a pre BE_TXN modrdn operation.
-pub const SLAPI_PLUGIN_BE_TXN_PRE_MODRDN_FN: isize = 462;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_MODRDN_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_PRE_MODRDN_FN as isize;
 /// PBlock constant for registering a pre BE_TXN delete operation.
-pub const SLAPI_PLUGIN_BE_TXN_PRE_DELETE_FN: isize = 463;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_DELETE_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_PRE_DELETE_FN as isize;
 /// PBlock constant for registering a pre BE_TXN delete tombstone operatation.
-pub const SLAPI_PLUGIN_BE_TXN_PRE_DELETE_TOMBSTONE_FN: isize = 464;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_DELETE_TOMBSTONE_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_PRE_DELETE_TOMBSTONE_FN as isize;
+/// PBlock constant for registering a post BIND operation.
+pub const SLAPI_PLUGIN_POST_BIND_FN: isize = sys::SLAPI_PLUGIN_POST_BIND_FN as isize;
+/// PBlock constant for registering a post UNBIND operation.
+pub const SLAPI_PLUGIN_POST_UNBIND_FN: isize = sys::SLAPI_PLUGIN_POST_UNBIND_FN as isize;
 /// PBlock constant for registering a post search operation.
-pub const SLAPI_PLUGIN_POST_SEARCH_FN: isize = 503;
-/// PBlock constant for registering a post add operation.
-pub const SLAPI_PLUGIN_BE_TXN_POST_ADD_FN: isize = 560;
-/// PBlock constant for registering a post modify operation.
-pub const SLAPI_PLUGIN_BE_TXN_POST_MODIFY_FN: isize = 561;
-/// PBlock constant for registering a post modrdn operation.
-pub const SLAPI_PLUGIN_BE_TXN_POST_MODRDN_FN: isize = 562;
-/// PBlock constant for registering a post delete operation.
-pub const SLAPI_PLUGIN_BE_TXN_POST_DELETE_FN: isize = 563;
+pub const SLAPI_PLUGIN_POST_SEARCH_FN: isize = sys::SLAPI_PLUGIN_POST_SEARCH_FN as isize;
+/// PBlock constant for registering a post COMPARE operation.
+pub const SLAPI_PLUGIN_POST_COMPARE_FN: isize = sys::SLAPI_PLUGIN_POST_COMPARE_FN as isize;
+/// PBlock constant for registering a post MODIFY operation.
+pub const SLAPI_PLUGIN_POST_MODIFY_FN: isize = sys::SLAPI_PLUGIN_POST_MODIFY_FN as isize;
+/// PBlock constant for registering a post MODRDN operation.
+pub const SLAPI_PLUGIN_POST_MODRDN_FN: isize = sys::SLAPI_PLUGIN_POST_MODRDN_FN as isize;
+/// PBlock constant for registering a post ADD operation.
+pub const SLAPI_PLUGIN_POST_ADD_FN: isize = sys::SLAPI_PLUGIN_POST_ADD_FN as isize;
+/// PBlock constant for registering a post DELETE operation.
+pub const SLAPI_PLUGIN_POST_DELETE_FN: isize = sys::SLAPI_PLUGIN_POST_DELETE_FN as isize;
+/// PBlock constant for registering a post ABANDON operation.
+pub const SLAPI_PLUGIN_POST_ABANDON_FN: isize = sys::SLAPI_PLUGIN_POST_ABANDON_FN as isize;
+/// PBlock constant for registering a post ENTRY operation.
+pub const SLAPI_PLUGIN_POST_ENTRY_FN: isize = sys::SLAPI_PLUGIN_POST_ENTRY_FN as isize;
+/// PBlock constant for registering a post REFERAL operation.
+pub const SLAPI_PLUGIN_POST_REFERAL_FN: isize = sys::SLAPI_PLUGIN_POST_REFERAL_FN as isize;
+/// PBlock constant for registering a post RESULT operation.
+pub const SLAPI_PLUGIN_POST_RESULT_FN: isize = sys::SLAPI_PLUGIN_POST_RESULT_FN as isize;
+/// PBlock constant for registering a post internal add operation.
+pub const SLAPI_PLUGIN_INTERNAL_POST_ADD_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_POST_ADD_FN as isize;
+/// PBlock constant for registering a post internal modify operation.
+pub const SLAPI_PLUGIN_INTERNAL_POST_MODIFY_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_POST_MODIFY_FN as isize;
+/// PBlock constant for registering a post internal modrdn operation.
+pub const SLAPI_PLUGIN_INTERNAL_POST_MODRDN_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_POST_MODRDN_FN as isize;
+/// PBlock constant for registering a post internal delete operation.
+pub const SLAPI_PLUGIN_INTERNAL_POST_DELETE_FN: isize = sys::SLAPI_PLUGIN_INTERNAL_POST_DELETE_FN as isize;
+/// PBlock constant for registering a post backend add operation.
+pub const SLAPI_PLUGIN_BE_POST_ADD_FN: isize = sys::SLAPI_PLUGIN_BE_POST_ADD_FN as isize;
+/// PBlock constant for registering a post backend modify operation.
+pub const SLAPI_PLUGIN_BE_POST_MODIFY_FN: isize = sys::SLAPI_PLUGIN_BE_POST_MODIFY_FN as isize;
+/// PBlock constant for registering a post backend modrdn operation.
+pub const SLAPI_PLUGIN_BE_POST_MODRDN_FN: isize = sys::SLAPI_PLUGIN_BE_POST_MODRDN_FN as isize;
+/// PBlock constant for registering a post backend delete operation.
+pub const SLAPI_PLUGIN_BE_POST_DELETE_FN: isize = sys::SLAPI_PLUGIN_BE_POST_DELETE_FN as isize;
+/// PBlock constant for registering a post BE_TXN add operation.
+pub const SLAPI_PLUGIN_BE_TXN_POST_ADD_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_POST_ADD_FN as isize;
+/// PBlock constant for registering a post BE_TXN modify operation.
+pub const SLAPI_PLUGIN_BE_TXN_POST_MODIFY_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_POST_MODIFY_FN as isize;
+/// PBlock constant for registering a post BE_TXN modrdn operation.
+pub const SLAPI_PLUGIN_BE_TXN_POST_MODRDN_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_POST_MODRDN_FN as isize;
+/// PBlock constant for registering a post BE_TXN delete operation.
+pub const SLAPI_PLUGIN_BE_TXN_POST_DELETE_FN: isize = sys::SLAPI_PLUGIN_BE_TXN_POST_DELETE_FN as isize;
 
 // Constants that pblock uses for get / set. This is not complete!
 /// PBlock constant for accessing private plugin data.
-pub const SLAPI_PLUGIN_PRIVATE: isize = 4;
+pub const SLAPI_PLUGIN_PRIVATE: isize = sys::SLAPI_PLUGIN_PRIVATE as isize;
 /// PBlock constant for accessing plugin type.
-pub const SLAPI_PLUGIN_TYPE: isize = 5;
+pub const SLAPI_PLUGIN_TYPE: isize = sys::SLAPI_PLUGIN_TYPE as isize;
 /// PBlock constant for accessing plugin API version.
-pub const SLAPI_PLUGIN_VERSION: isize = 8;
+pub const SLAPI_PLUGIN_VERSION: isize = sys::SLAPI_PLUGIN_VERSION as isize;
 /// PBlock constant for accessing plugin operation return codes.
-pub const SLAPI_PLUGIN_OPRETURN: isize = 9;
+pub const SLAPI_PLUGIN_OPRETURN: isize = sys::SLAPI_PLUGIN_OPRETURN as isize;
+/// PBlock constant for accessing the LDAP result of an internal operation.
+pub const SLAPI_PLUGIN_INTOP_RESULT: isize = sys::SLAPI_PLUGIN_INTOP_RESULT as isize;
 /// PBlock constant for accessing plugin description information.
-pub const SLAPI_PLUGIN_DESCRIPTION: isize = 12;
+pub const SLAPI_PLUGIN_DESCRIPTION: isize = sys::SLAPI_PLUGIN_DESCRIPTION as isize;
+/// PBlock constant for accessing the plugin identity, used for internal operations.
+pub const SLAPI_PLUGIN_IDENTITY: isize = sys::SLAPI_PLUGIN_IDENTITY as isize;
+/// PBlock constant for accessing the plugin configuration entry during init.
+pub const SLAPI_PLUGIN_CONFIG_ENTRY: isize = sys::SLAPI_PLUGIN_CONFIG_ENTRY as isize;
+
+/// Access right to compare the value of an attribute.
+pub const SLAPI_ACL_COMPARE: isize = sys::SLAPI_ACL_COMPARE as isize;
+/// Access right to search on an attribute.
+pub const SLAPI_ACL_SEARCH: isize = sys::SLAPI_ACL_SEARCH as isize;
+/// Access right to read the value of an attribute.
+pub const SLAPI_ACL_READ: isize = sys::SLAPI_ACL_READ as isize;
+/// Access right to modify the value of an attribute.
+pub const SLAPI_ACL_WRITE: isize = sys::SLAPI_ACL_WRITE as isize;
+/// Access right to delete an entry.
+pub const SLAPI_ACL_DELETE: isize = sys::SLAPI_ACL_DELETE as isize;
+/// Access right to add an entry.
+pub const SLAPI_ACL_ADD: isize = sys::SLAPI_ACL_ADD as isize;
+/// Access right to add or remove your own DN as a value of an attribute.
+pub const SLAPI_ACL_SELF: isize = sys::SLAPI_ACL_SELF as isize;
+/// Access right to proxy as another identity.
+pub const SLAPI_ACL_PROXY: isize = sys::SLAPI_ACL_PROXY as isize;
+
 
+/// Search scope of only the base entry.
+pub const LDAP_SCOPE_BASE: isize = sys::LDAP_SCOPE_BASE as isize;
 
+/// Add values to an attribute in a modification.
+pub const LDAP_MOD_ADD: isize = sys::LDAP_MOD_ADD as isize;
+/// Delete values, or the whole attribute, in a modification.
+pub const LDAP_MOD_DELETE: isize = sys::LDAP_MOD_DELETE as isize;
+/// Replace all values of an attribute in a modification.
+pub const LDAP_MOD_REPLACE: isize = sys::LDAP_MOD_REPLACE as isize;
+/// Flag of a modification whose values are bervals rather than strings.
+pub const LDAP_MOD_BVALUES: isize = sys::LDAP_MOD_BVALUES as isize;
+/// DSE callback flag, the callback runs before the operation is applied.
+pub const DSE_FLAG_PREOP: isize = sys::DSE_FLAG_PREOP as isize;
+/// DSE callback result, the operation should proceed.
+pub const SLAPI_DSE_CALLBACK_OK: isize = sys::SLAPI_DSE_CALLBACK_OK as isize;
+/// DSE callback result, the operation failed and should be rejected.
+pub const SLAPI_DSE_CALLBACK_ERROR: isize = sys::SLAPI_DSE_CALLBACK_ERROR as isize;
+/// DSE callback result, the operation succeeded but should not change the DSE.
+pub const SLAPI_DSE_CALLBACK_DO_NOT_APPLY: isize = sys::SLAPI_DSE_CALLBACK_DO_NOT_APPLY as isize;
+
+/// Operation flag for a persistent search.
+pub const OP_FLAG_PS: isize = sys::OP_FLAG_PS as isize;
+/// Operation flag for a persistent search that only returns changes.
+pub const OP_FLAG_PS_CHANGESONLY: isize = sys::OP_FLAG_PS_CHANGESONLY as isize;
+/// Operation flag for a search requesting the get effective rights control.
+pub const OP_FLAG_GET_EFFECTIVE_RIGHTS: isize = sys::OP_FLAG_GET_EFFECTIVE_RIGHTS as isize;
 /// Operation flag for an operation that is generated by a replication event.
-pub const OP_FLAG_REPLICATED: isize = 0x000008;
+pub const OP_FLAG_REPLICATED: isize = sys::OP_FLAG_REPLICATED as isize;
+/// Operation flag for a replication fixup operation, such as URP conflict resolution.
+pub const OP_FLAG_REPL_FIXUP: isize = sys::OP_FLAG_REPL_FIXUP as isize;
 /// Operation flag for an operation that is generated by an internal event.
-pub const OP_FLAG_INTERNAL: isize = 0x000020;
+pub const OP_FLAG_INTERNAL: isize = sys::OP_FLAG_INTERNAL as isize;
+/// Operation flag requesting the operation is written to the access log.
+pub const OP_FLAG_ACTION_LOG_ACCESS: isize = sys::OP_FLAG_ACTION_LOG_ACCESS as isize;
+/// Operation flag requesting the operation is written to the audit log.
+pub const OP_FLAG_ACTION_LOG_AUDIT: isize = sys::OP_FLAG_ACTION_LOG_AUDIT as isize;
+/// Operation flag requesting schema checking of the operation.
+pub const OP_FLAG_ACTION_SCHEMA_CHECK: isize = sys::OP_FLAG_ACTION_SCHEMA_CHECK as isize;
+/// Operation flag requesting the operation is written to the changelog.
+pub const OP_FLAG_ACTION_LOG_CHANGES: isize = sys::OP_FLAG_ACTION_LOG_CHANGES as isize;
+/// Operation flag requesting plugins are invoked for a replicated operation.
+pub const OP_FLAG_ACTION_INVOKE_FOR_REPLOP: isize = sys::OP_FLAG_ACTION_INVOKE_FOR_REPLOP as isize;
+/// Operation flag preventing the operation being chained to a remote server.
+pub const OP_FLAG_NEVER_CHAIN: isize = sys::OP_FLAG_NEVER_CHAIN as isize;
+/// Operation flag for an operation targeting a tombstone entry.
+pub const OP_FLAG_TOMBSTONE_ENTRY: isize = sys::OP_FLAG_TOMBSTONE_ENTRY as isize;
+/// Operation flag for an operation resurrecting a tombstone entry.
+pub const OP_FLAG_RESURECT_ENTRY: isize = sys::OP_FLAG_RESURECT_ENTRY as isize;
 /// Operation flag for an operation that is generated by the legacy replication system.
-pub const OP_FLAG_LEGACY_REPLICATION_DN: isize = 0x004000;
+pub const OP_FLAG_LEGACY_REPLICATION_DN: isize = sys::OP_FLAG_LEGACY_REPLICATION_DN as isize;
+/// Operation flag preventing the operation being written to the audit log or changelog.
+pub const OP_FLAG_ACTION_NOLOG: isize = sys::OP_FLAG_ACTION_NOLOG as isize;
+/// Operation flag preventing the update of modifiersName and modifyTimestamp.
+pub const OP_FLAG_SKIP_MODIFIED_ATTRS: isize = sys::OP_FLAG_SKIP_MODIFIED_ATTRS as isize;
+/// Operation flag for an operation that updates the replication RUV entry.
+pub const OP_FLAG_REPL_RUV: isize = sys::OP_FLAG_REPL_RUV as isize;
+/// Operation flag for a search using the simple paged results control.
+pub const OP_FLAG_PAGED_RESULTS: isize = sys::OP_FLAG_PAGED_RESULTS as isize;
+/// Operation flag for a search using the server side sorting control.
+pub const OP_FLAG_SERVER_SIDE_SORTING: isize = sys::OP_FLAG_SERVER_SIDE_SORTING as isize;
+/// Operation flag for a search that returns its candidates in reverse order.
+pub const OP_FLAG_REVERSE_CANDIDATE_ORDER: isize = sys::OP_FLAG_REVERSE_CANDIDATE_ORDER as isize;
+/// Operation flag preventing the entries of the operation being added to the entry cache.
+pub const OP_FLAG_NEVER_CACHE: isize = sys::OP_FLAG_NEVER_CACHE as isize;
+/// Operation flag for an operation that repairs a tombstone entry.
+pub const OP_FLAG_TOMBSTONE_FIXUP: isize = sys::OP_FLAG_TOMBSTONE_FIXUP as isize;
+/// Operation flag for an operation that is part of a bulk import.
+pub const OP_FLAG_BULK_IMPORT: isize = sys::OP_FLAG_BULK_IMPORT as isize;
+/// Operation flag for an operation that is processed but not applied, such as
+/// one carrying the LDAP no-op control.
+pub const OP_FLAG_NOOP: isize = sys::OP_FLAG_NOOP as isize;
+
+/// Operation type value for a bind. See slapi_op_get_type.
+pub const SLAPI_OPERATION_BIND: usize = sys::SLAPI_OPERATION_BIND as usize;
+/// Operation type value for an unbind.
+pub const SLAPI_OPERATION_UNBIND: usize = sys::SLAPI_OPERATION_UNBIND as usize;
+/// Operation type value for a search.
+pub const SLAPI_OPERATION_SEARCH: usize = sys::SLAPI_OPERATION_SEARCH as usize;
+/// Operation type value for a modify.
+pub const SLAPI_OPERATION_MODIFY: usize = sys::SLAPI_OPERATION_MODIFY as usize;
+/// Operation type value for an add.
+pub const SLAPI_OPERATION_ADD: usize = sys::SLAPI_OPERATION_ADD as usize;
+/// Operation type value for a delete.
+pub const SLAPI_OPERATION_DELETE: usize = sys::SLAPI_OPERATION_DELETE as usize;
+/// Operation type value for a modrdn.
+pub const SLAPI_OPERATION_MODRDN: usize = sys::SLAPI_OPERATION_MODRDN as usize;
+/// Operation type value for a compare.
+pub const SLAPI_OPERATION_COMPARE: usize = sys::SLAPI_OPERATION_COMPARE as usize;
+/// Operation type value for an abandon.
+pub const SLAPI_OPERATION_ABANDON: usize = sys::SLAPI_OPERATION_ABANDON as usize;
+/// Operation type value for an extended operation.
+pub const SLAPI_OPERATION_EXTENDED: usize = sys::SLAPI_OPERATION_EXTENDED as usize;
 
 
 /// Plugin API versions that can be used for a plugin.
 /// You should in most cases use version _03
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PluginVersion {
     /// Version 00. Do not use.
     _00,
@@ -112,61 +310,97 @@ pub enum PluginVersion {
     _03,
 }
 
+impl PluginVersion {
+    /// The version as Directory Server stores it in SLAPI_PLUGIN_VERSION, a
+    /// null terminated string such as SLAPI_PLUGIN_VERSION_03, "03".
+    pub fn as_ds_version(&self) -> &'static [u8] {
+        match *self {
+            PluginVersion::_00 => b"00\0",
+            PluginVersion::_01 => sys::SLAPI_PLUGIN_VERSION_01,
+            PluginVersion::_02 => sys::SLAPI_PLUGIN_VERSION_02,
+            PluginVersion::_03 => sys::SLAPI_PLUGIN_VERSION_03,
+        }
+    }
+
+    /// Convert the null terminated version string of SLAPI_PLUGIN_VERSION.
+    /// None is returned if it is not a version we know about.
+    pub fn from_ds_version(version: &[u8]) -> Option<PluginVersion> {
+        [PluginVersion::_00, PluginVersion::_01, PluginVersion::_02, PluginVersion::_03]
+            .iter()
+            .cloned()
+            .find(|v| v.as_ds_version() == version)
+    }
+}
+
 /// Error logging levels that may be used. This is controlled by -d on the
-/// ns-slapd commandline.
-#[derive(Debug)]
+/// ns-slapd commandline. The values are those of SLAPI_LOG_* in slapi-plugin.h.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogLevel {
     /// Always log messages at this level. Soon to go away, see EMERG, ALERT, CRIT, ERR, WARNING, NOTICE, INFO, DEBUG
-    FATAL,
+    FATAL = sys::SLAPI_LOG_FATAL as isize,
     /// Log detailed messages.
-    TRACE,
+    TRACE = sys::SLAPI_LOG_TRACE as isize,
     /// Log packet tracing.
-    PACKETS,
+    PACKETS = sys::SLAPI_LOG_PACKETS as isize,
     /// Log argument tracing.
-    ARGS,
+    ARGS = sys::SLAPI_LOG_ARGS as isize,
     /// Log connection tracking.
-    CONNS,
+    CONNS = sys::SLAPI_LOG_CONNS as isize,
     /// Log BER parsing.
-    BER,
+    BER = sys::SLAPI_LOG_BER as isize,
     /// Log filter processing.
-    FILTER,
+    FILTER = sys::SLAPI_LOG_FILTER as isize,
     /// Log configuration processing.
-    CONFIG,
+    CONFIG = sys::SLAPI_LOG_CONFIG as isize,
+    /// Log access control processing.
+    ACL = sys::SLAPI_LOG_ACL as isize,
     /// Log .... ???
-    SHELL,
+    SHELL = sys::SLAPI_LOG_SHELL as isize,
     /// Log .... ???
-    PARSE,
+    PARSE = sys::SLAPI_LOG_PARSE as isize,
     /// Log .... ???
-    HOUSE,
+    HOUSE = sys::SLAPI_LOG_HOUSE as isize,
     /// Log detailed replication information.
-    REPL,
+    REPL = sys::SLAPI_LOG_REPL as isize,
     /// Log cache management.
-    CACHE,
+    CACHE = sys::SLAPI_LOG_CACHE as isize,
     /// Log detailed plugin operations.
-    PLUGIN,
+    PLUGIN = sys::SLAPI_LOG_PLUGIN as isize,
     /// Log .... ???
-    TIMING,
+    TIMING = sys::SLAPI_LOG_TIMING as isize,
     /// Log backend infomation.
-    BACKLDBM,
+    BACKLDBM = sys::SLAPI_LOG_BACKLDBM as isize,
     /// Log ACL processing.
-    ACLSUMMARY,
+    ACLSUMMARY = sys::SLAPI_LOG_ACLSUMMARY as isize,
     /// Log nuncstans processing.
-    NUNCSTANS,
+    NUNCSTANS = sys::SLAPI_LOG_NUNCSTANS as isize,
     /// Emergency messages. Server is bursting into flame.
-    EMERG,
+    EMERG = sys::SLAPI_LOG_EMERG as isize,
     /// Important alerts, server may explode soon.
-    ALERT,
+    ALERT = sys::SLAPI_LOG_ALERT as isize,
     /// Critical messages, but the server isn't going to explode. Admin should intervene.
-    CRIT,
+    CRIT = sys::SLAPI_LOG_CRIT as isize,
     /// Error has occured, but we can keep going. Could indicate misconfiguration.
-    ERR,
+    ERR = sys::SLAPI_LOG_ERR as isize,
     /// Warning about an issue that isn't very important. Good to resolve though.
-    WARNING,
+    WARNING = sys::SLAPI_LOG_WARNING as isize,
     /// Inform the admin of something that they should know about, IE server is running now.
-    NOTICE,
+    NOTICE = sys::SLAPI_LOG_NOTICE as isize,
     /// Informational messages that are nice to know.
-    INFO,
+    INFO = sys::SLAPI_LOG_INFO as isize,
     /// Debugging information from the server.
-    DEBUG,
+    DEBUG = sys::SLAPI_LOG_DEBUG as isize,
 }
 
+/// The access log level that operation statistics are written at.
+pub const LDAP_DEBUG_STATS: isize = sys::LDAP_DEBUG_STATS as isize;
+
+/// Operation note for a search with an unindexed component, notes=U.
+pub const SLAPI_OP_NOTE_UNINDEXED: isize = sys::SLAPI_OP_NOTE_UNINDEXED as isize;
+/// Operation note for a simple paged results search, notes=P.
+pub const SLAPI_OP_NOTE_SIMPLEPAGED: isize = sys::SLAPI_OP_NOTE_SIMPLEPAGED as isize;
+/// Operation note for a search that is fully unindexed, notes=A.
+pub const SLAPI_OP_NOTE_FULL_UNINDEXED: isize = sys::SLAPI_OP_NOTE_FULL_UNINDEXED as isize;
+/// Operation note for a search with a filter that is invalid, notes=F.
+pub const SLAPI_OP_NOTE_FILTER_INVALID: isize = sys::SLAPI_OP_NOTE_FILTER_INVALID as isize;
+
@@ -13,6 +13,20 @@
 #![warn(missing_docs)]
 
 use libc;
+use slapi_r_plugin_sys as sys;
+use std::mem;
+use std::ptr;
+use std::marker::PhantomData;
+use std::slice;
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use super::ldif;
+use super::ldif::LdifRecord;
+use super::mods::ModType;
+use super::mods::Modification;
 
 #[derive(Debug)]
 #[allow(non_camel_case_types)]
@@ -20,9 +34,25 @@ use libc;
 /// This represents an entry that has been retrieved from the revelant
 /// backend as part of an operation.
 /// By making this opaque, we create rewrite and improvement possibilities
-pub struct Slapi_R_Entry {
+///
+/// The entry is owned by Directory Server, and is only valid while the pblock
+/// it came from is, so it borrows the pblock for the lifetime 'a. Keeping the
+/// entry after the callback returns is a compile error. Copy it to an owned
+/// Entry with to_entry if you need it for longer.
+///
+/// ```compile_fail
+/// use slapi_r_plugin::entry::Slapi_R_Entry;
+/// use slapi_r_plugin::pblock::{Slapi_PBlock_V3, Slapi_R_PBlock};
+///
+/// fn keep(pb: Slapi_R_PBlock) -> Option<Slapi_R_Entry<'static>> {
+///     // pb is dropped here, but the entry would outlive it.
+///     pb.get_search_result_entry()
+/// }
+/// ```
+pub struct Slapi_R_Entry<'a> {
     // Will need to contain a pointer to the real Slapi_Entry
-    slapi_entry: *const libc::c_void,
+    slapi_entry: *mut sys::Slapi_Entry,
+    _owner: PhantomData<&'a ()>,
 }
 
 // #[derive(Debug)]
@@ -32,11 +62,379 @@ pub struct Slapi_R_Entry {
     // get search_result_entry?
 // }
 
-impl Slapi_R_Entry {
+impl<'a> Slapi_R_Entry<'a> {
     /// Build a new Slapi_R_Entry from a pointer to the slapi_entry
     /// The slapi_entry is created in a number of paths through DS,
     /// so you should not need to do this yourself.
-    pub fn new(slapi_entry: *const libc::c_void) -> Slapi_R_Entry {
-        Slapi_R_Entry { slapi_entry: slapi_entry }
+    ///
+    /// # Safety
+    /// The pointer must be a valid, non null slapi_entry for all of 'a.
+    pub unsafe fn new(slapi_entry: *const libc::c_void) -> Slapi_R_Entry<'a> {
+        Slapi_R_Entry {
+            slapi_entry: slapi_entry as *mut sys::Slapi_Entry,
+            _owner: PhantomData,
+        }
+    }
+
+    /// Returns the pointer to the slapi_entry for use by other slapi_r_plugin
+    /// modules that need to pass the entry back to Directory Server.
+    pub(crate) fn as_ptr(&self) -> *mut sys::Slapi_Entry {
+        self.slapi_entry
+    }
+
+    /// Returns the DN of this entry.
+    pub fn get_dn(&self) -> String {
+        unsafe {
+            let dn = sys::slapi_entry_get_dn_const(self.slapi_entry);
+            if dn.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(dn).to_string_lossy().into_owned()
+            }
+        }
+    }
+
+    /// Returns the first value of the named attribute as a string, if the
+    /// attribute is present on this entry. A name containing a nul byte is
+    /// never present.
+    pub fn get_attribute_value(&self, name: &str) -> Option<String> {
+        let c_name = CString::new(name).ok()?;
+        unsafe {
+            let mut value = sys::slapi_entry_attr_get_charptr(self.slapi_entry, c_name.as_ptr());
+            if value.is_null() {
+                None
+            } else {
+                let v = CStr::from_ptr(value).to_string_lossy().into_owned();
+                sys::slapi_ch_free_string(&mut value);
+                Some(v)
+            }
+        }
+    }
+
+    /// Returns the names of the attributes present on this entry, in the
+    /// order that Directory Server stores them.
+    pub fn get_attribute_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut attr: *mut sys::Slapi_Attr = ptr::null_mut();
+        unsafe {
+            let mut rc = sys::slapi_entry_first_attr(self.slapi_entry, &mut attr);
+            while rc == 0 && !attr.is_null() {
+                let mut attr_type: *mut c_char = ptr::null_mut();
+                sys::slapi_attr_get_type(attr, &mut attr_type);
+                if !attr_type.is_null() {
+                    names.push(CStr::from_ptr(attr_type).to_string_lossy().into_owned());
+                }
+                let prev = attr;
+                rc = sys::slapi_entry_next_attr(self.slapi_entry, prev, &mut attr);
+            }
+        }
+        names
+    }
+
+    /// Removes an attribute and all of its values from this entry. This
+    /// modifies the entry in place, so must only be used on entries that the
+    /// plugin owns, such as a copy made with slapi_entry_dup. Entries that
+    /// Directory Server gives to a callback may be shared with the entry
+    /// cache. A name containing a nul byte is never present, so nothing is
+    /// removed.
+    pub(crate) fn remove_attribute(&mut self, name: &str) {
+        let c_name = match CString::new(name) {
+            Ok(n) => n,
+            Err(_) => return,
+        };
+        unsafe {
+            sys::slapi_entry_attr_delete(self.slapi_entry, c_name.as_ptr());
+        }
+    }
+
+    /// Copy this entry, with all attributes and values, into an owned Entry
+    /// that remains valid after the callback returns.
+    pub fn to_entry(&self) -> Entry {
+        let mut entry = Entry::new(&self.get_dn());
+        let mut attr: *mut sys::Slapi_Attr = ptr::null_mut();
+        unsafe {
+            let mut rc = sys::slapi_entry_first_attr(self.slapi_entry, &mut attr);
+            while rc == 0 && !attr.is_null() {
+                let mut attr_type: *mut c_char = ptr::null_mut();
+                sys::slapi_attr_get_type(attr, &mut attr_type);
+                if !attr_type.is_null() {
+                    let name = CStr::from_ptr(attr_type).to_string_lossy().into_owned();
+                    let mut value: *mut sys::Slapi_Value = ptr::null_mut();
+                    let mut hint = sys::slapi_attr_first_value(attr, &mut value);
+                    while hint != -1 && !value.is_null() {
+                        let bv = sys::slapi_value_get_berval(value);
+                        if !bv.is_null() {
+                            let bytes = if (*bv).bv_val.is_null() {
+                                Vec::new()
+                            } else {
+                                slice::from_raw_parts((*bv).bv_val as *const u8, (*bv).bv_len as usize).to_vec()
+                            };
+                            entry.add_value(&name, bytes);
+                        }
+                        hint = sys::slapi_attr_next_value(attr, hint, &mut value);
+                    }
+                }
+                let prev = attr;
+                rc = sys::slapi_entry_next_attr(self.slapi_entry, prev, &mut attr);
+            }
+        }
+        entry
+    }
+}
+
+#[derive(Debug)]
+#[allow(non_camel_case_types)]
+/// Slapi_R_Owned_Entry is a slapi_entry that the plugin created with
+/// Entry::to_slapi_entry, and so owns. It is freed when dropped, unless it
+/// is given to Directory Server with into_raw, as an internal add does.
+pub struct Slapi_R_Owned_Entry {
+    slapi_entry: *mut sys::Slapi_Entry,
+}
+
+impl Slapi_R_Owned_Entry {
+    /// Borrow the entry, to read it as an entry from Directory Server.
+    pub fn as_entry<'a>(&'a self) -> Slapi_R_Entry<'a> {
+        unsafe { Slapi_R_Entry::new(self.slapi_entry as *const libc::c_void) }
+    }
+
+    /// Give the entry to Directory Server, which then frees it, IE with
+    /// slapi_add_entry_internal_set_pb.
+    pub(crate) fn into_raw(self) -> *mut sys::Slapi_Entry {
+        let e = self.slapi_entry;
+        mem::forget(self);
+        e
+    }
+}
+
+impl Drop for Slapi_R_Owned_Entry {
+    fn drop(&mut self) {
+        unsafe {
+            sys::slapi_entry_free(self.slapi_entry);
+        }
+    }
+}
+
+/// Entry is an owned copy of an entry, holding the DN and the values of each
+/// attribute. Unlike Slapi_R_Entry it does not depend on Directory Server,
+/// so it remains valid after a callback returns, and logic that works on it
+/// can be unit tested without libslapd.
+///
+/// Attribute names are case insensitive, as they are in LDAP, but the name
+/// as first given is kept for display. Values are bytes, and are compared
+/// exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    dn: String,
+    // Keyed by the lower case attribute name.
+    attrs: BTreeMap<String, (String, Vec<Vec<u8>>)>,
+}
+
+impl Entry {
+    /// Create an entry with no attributes.
+    pub fn new(dn: &str) -> Entry {
+        Entry {
+            dn: dn.to_string(),
+            attrs: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the DN of this entry.
+    pub fn get_dn(&self) -> &str {
+        &self.dn
+    }
+
+    /// Change the DN of this entry.
+    pub fn set_dn(&mut self, dn: &str) {
+        self.dn = dn.to_string();
+    }
+
+    /// Add a value to an attribute, creating the attribute if needed. A value
+    /// that is already present is not added again.
+    pub fn add_value<V: Into<Vec<u8>>>(&mut self, name: &str, value: V) {
+        let value = value.into();
+        let values = &mut self.attrs
+            .entry(name.to_lowercase())
+            .or_insert_with(|| (name.to_string(), Vec::new()))
+            .1;
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+
+    /// Builder form of add_value, for creating entries in tests.
+    pub fn with_value<V: Into<Vec<u8>>>(mut self, name: &str, value: V) -> Entry {
+        self.add_value(name, value);
+        self
+    }
+
+    /// Returns all values of the named attribute, if it is present.
+    pub fn get_values(&self, name: &str) -> Option<&[Vec<u8>]> {
+        self.attrs.get(&name.to_lowercase()).map(|(_, v)| v.as_slice())
+    }
+
+    /// Returns the first value of the named attribute as a string, if the
+    /// attribute is present on this entry.
+    pub fn get_attribute_value(&self, name: &str) -> Option<String> {
+        self.get_values(name)
+            .and_then(|v| v.first())
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+    }
+
+    /// Returns true if the named attribute is present.
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attrs.contains_key(&name.to_lowercase())
+    }
+
+    /// Returns true if the named attribute holds this value.
+    pub fn has_value(&self, name: &str, value: &[u8]) -> bool {
+        self.get_values(name).map(|v| v.iter().any(|x| x.as_slice() == value)).unwrap_or(false)
+    }
+
+    /// Returns the names of the attributes present on this entry.
+    pub fn get_attribute_names(&self) -> Vec<String> {
+        self.attrs.values().map(|(n, _)| n.clone()).collect()
+    }
+
+    /// Removes an attribute and all of its values from this entry.
+    pub fn remove_attribute(&mut self, name: &str) {
+        self.attrs.remove(&name.to_lowercase());
+    }
+
+    /// Remove a single value of an attribute. If it was the last value, the
+    /// attribute is removed.
+    pub fn remove_value(&mut self, name: &str, value: &[u8]) {
+        let key = name.to_lowercase();
+        let empty = match self.attrs.get_mut(&key) {
+            Some(&mut (_, ref mut values)) => {
+                values.retain(|v| v.as_slice() != value);
+                values.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            self.attrs.remove(&key);
+        }
+    }
+
+    /// Apply a modification to this entry, as Directory Server would.
+    pub fn apply_modification(&mut self, m: &Modification) {
+        match m.mod_type {
+            ModType::Add => {
+                for v in m.values.iter() {
+                    self.add_value(&m.attr, v.clone());
+                }
+            }
+            ModType::Delete => {
+                if m.values.is_empty() {
+                    self.remove_attribute(&m.attr);
+                }
+                for v in m.values.iter() {
+                    self.remove_value(&m.attr, v);
+                }
+            }
+            ModType::Replace => {
+                self.remove_attribute(&m.attr);
+                for v in m.values.iter() {
+                    self.add_value(&m.attr, v.clone());
+                }
+            }
+        }
+    }
+
+    /// Create an entry from an Entry or Add LDIF record. Other records do not
+    /// describe an entry, and return None.
+    pub fn from_ldif_record(record: &LdifRecord) -> Option<Entry> {
+        record.attributes().map(|attrs| {
+            let mut entry = Entry::new(record.dn());
+            for (name, value) in attrs.iter() {
+                entry.add_value(name, value.clone());
+            }
+            entry
+        })
+    }
+
+    /// Convert this entry to an LDIF Entry record.
+    pub fn to_ldif_record(&self) -> LdifRecord {
+        let mut attrs = Vec::new();
+        for (name, values) in self.attrs.values() {
+            for v in values.iter() {
+                attrs.push((name.clone(), v.clone()));
+            }
+        }
+        LdifRecord::Entry {
+            dn: self.dn.clone(),
+            attrs,
+        }
+    }
+
+    /// Create a new Slapi_Entry in Directory Server from this entry. The
+    /// entry is owned by the caller, and is freed when it is dropped unless
+    /// it is consumed by an operation such as an internal add. Returns None
+    /// if Directory Server could not parse the entry.
+    pub fn to_slapi_entry(&self) -> Option<Slapi_R_Owned_Entry> {
+        // slapi_str2entry modifies the buffer it parses, so it must be ours.
+        let mut buf = match CString::new(ldif::to_string(&[self.to_ldif_record()])) {
+            Ok(s) => s.into_bytes_with_nul(),
+            Err(_) => return None,
+        };
+        unsafe {
+            let e = sys::slapi_str2entry(buf.as_mut_ptr() as *mut c_char, 0);
+            if e.is_null() {
+                None
+            } else {
+                Some(Slapi_R_Owned_Entry { slapi_entry: e })
+            }
+        }
+    }
+}
+
+impl<'a, 'b> From<&'a Slapi_R_Entry<'b>> for Entry {
+    fn from(e: &'a Slapi_R_Entry<'b>) -> Entry {
+        e.to_entry()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_attributes() {
+        let mut e = Entry::new("uid=a,dc=example,dc=com")
+            .with_value("objectClass", "top")
+            .with_value("objectclass", "person")
+            .with_value("objectClass", "top")
+            .with_value("cn", "a");
+        assert_eq!(e.get_values("OBJECTCLASS").unwrap().len(), 2);
+        assert!(e.has_value("objectclass", b"person"));
+        assert!(!e.has_value("objectclass", b"Person"));
+        assert_eq!(e.get_attribute_names(), vec!["cn".to_string(), "objectClass".to_string()]);
+
+        e.apply_modification(&Modification::new(ModType::Replace, "CN").value("b"));
+        assert_eq!(e.get_attribute_value("cn"), Some("b".to_string()));
+        e.apply_modification(&Modification::new(ModType::Delete, "objectclass").value("top").value("person"));
+        assert!(!e.has_attribute("objectclass"));
+    }
+
+    #[test]
+    fn test_entry_ldif() {
+        let e = Entry::new("uid=a,dc=example,dc=com")
+            .with_value("cn", "a")
+            .with_value("userCertificate", &b"\x00\x01"[..]);
+        let records = ldif::parse_str(&ldif::to_string(&[e.to_ldif_record()])).unwrap();
+        assert_eq!(Entry::from_ldif_record(&records[0]), Some(e));
+    }
+
+    #[cfg(slapi_r_mock)]
+    #[test]
+    fn test_entry_to_slapi_entry() {
+        let e = Entry::new("uid=a,dc=example,dc=com").with_value("uid", "a");
+        let owned = e.to_slapi_entry().unwrap();
+        assert_eq!(owned.as_entry().get_dn(), "uid=a,dc=example,dc=com");
+        assert_eq!(owned.as_entry().to_entry(), e);
+        assert_eq!(owned.as_entry().get_attribute_value("uid"), Some("a".to_string()));
+        assert_eq!(owned.as_entry().get_attribute_value("u\0id"), None);
+        // The owned entry is freed as it is dropped.
+        drop(owned);
     }
 }
@@ -25,6 +25,11 @@ pub enum PluginRegistrationError {
     PostSearchFN,
     /// An error occured attempting to log a message.
     LoggingError,
+    /// Directory Server refused to register the named sub-plugin.
+    SubPlugin(String),
+    /// A plugin of the type, IE betxnpostoperation, can not register the
+    /// named callback.
+    UnsupportedCallback(String, String),
 }
 
 impl PluginRegistrationError {
@@ -35,6 +40,8 @@ impl PluginRegistrationError {
             PluginRegistrationError::LoggingError => -1,
             PluginRegistrationError::PostSearchFN => -1,
             PluginRegistrationError::Unknown => -1,
+            PluginRegistrationError::SubPlugin(_) => -1,
+            PluginRegistrationError::UnsupportedCallback(_, _) => -1,
         }
     }
 }
@@ -49,6 +56,8 @@ pub enum PluginOperationError {
     LoggingError,
     /// Unwilling to perform
     UnwillingToPerform,
+    /// The operation identity does not have the access rights required.
+    InsufficientAccess,
 }
 
 impl PluginOperationError {
@@ -60,6 +69,7 @@ impl PluginOperationError {
             PluginOperationError::LoggingError => -2,
             // From ldap.h
             PluginOperationError::UnwillingToPerform => 0x35,
+            PluginOperationError::InsufficientAccess => 0x32,
         }
     }
 }
@@ -76,6 +86,8 @@ pub enum PBlockError {
     GetFail,
     /// An error occured destroying a value in the C Slapi_PBlock
     DestroyFail,
+    /// The value in the Slapi_PBlock is not of the type that was requested.
+    TypeMismatch,
 }
 
 impl PBlockError {
@@ -87,6 +99,7 @@ impl PBlockError {
             PBlockError::SetFail => -2,
             PBlockError::GetFail => -3,
             PBlockError::DestroyFail => -4,
+            PBlockError::TypeMismatch => -5,
         }
     }
 }
@@ -97,6 +110,90 @@ impl PBlockError {
 pub enum LoggingError {
     /// An unknown error occured.
     Unknown,
+    /// The running server does not provide this log.
+    Unsupported,
 }
 
+/// This type represents the result of an access control check made on behalf
+/// of the current operation.
+#[derive(Debug)]
+pub enum AccessError {
+    /// An unknown error occured while checking access.
+    Unknown,
+    /// The operation identity was denied access. This may contain the reason
+    /// that Directory Server gave for the denial.
+    InsufficientAccess(Option<String>),
+}
+
+impl AccessError {
+    /// Convert the Rust error type to an isize that Directory Server can
+    /// interpret.
+    pub fn as_ds_isize(self) -> isize {
+        match self {
+            AccessError::Unknown => -1,
+            // From ldap.h
+            AccessError::InsufficientAccess(_) => 0x32,
+        }
+    }
+}
 
+/// This type represents an error while scheduling work on the Directory
+/// Server event queue.
+#[derive(Debug)]
+pub enum EventQueueError {
+    /// The event queue refused to schedule the work.
+    ScheduleFailed,
+}
+
+/// This type is returned by a plugin task handler to represent a failure of
+/// the task. It is converted to the nsTaskExitCode of the task entry.
+#[derive(Debug)]
+pub enum TaskError {
+    /// An unknown error occured.
+    Unknown,
+    /// The task was cancelled before it could complete.
+    Cancelled,
+    /// The task entry is missing an attribute that the task requires.
+    MissingAttribute(String),
+    /// The task failed, with a message for the task log.
+    Failed(String),
+    /// The running server does not support tasks.
+    Unsupported,
+}
+
+impl TaskError {
+    /// Convert the Rust error type to an isize that Directory Server can
+    /// interpret.
+    pub fn as_ds_isize(self) -> isize {
+        match self {
+            TaskError::Unknown => -1,
+            TaskError::Cancelled => -2,
+            // From ldap.h
+            TaskError::MissingAttribute(_) => 0x41,
+            TaskError::Failed(_) => 0x01,
+            TaskError::Unsupported => 0x35,
+        }
+    }
+}
+
+
+/// This type represents an error reading or writing LDIF.
+#[derive(Debug)]
+pub enum LdifError {
+    /// The underlying reader or writer failed.
+    Io(String),
+    /// The LDIF is not valid, at the line number given.
+    Syntax(usize, String),
+    /// A value refers to a URL that could not be read, at the line number given.
+    Url(usize, String),
+}
+
+/// This type represents an error recording plugin callbacks, or reading
+/// recorded callbacks to replay them.
+#[derive(Debug)]
+pub enum RecordError {
+    /// The record file could not be opened, written or read.
+    Io(String),
+    /// A record is not valid, at the line number given.
+    Syntax(usize, String),
+}
@@ -0,0 +1,355 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use libc;
+use slapi_r_plugin_sys as sys;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::error::EventQueueError;
+
+/// The scheduled work, and the state we need to track it. This is shared
+/// between the handle the plugin holds, and the event queue that will call it.
+struct EventJob {
+    job: Box<dyn Fn() + Send + Sync>,
+    repeat: bool,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+    // The Slapi_Eq_Context, if this was scheduled on the server event queue.
+    ctx: Mutex<usize>,
+}
+
+impl EventJob {
+    fn new<F>(job: F, repeat: bool) -> Arc<EventJob>
+        where F: Fn() + Send + Sync + 'static
+    {
+        Arc::new(EventJob {
+            job: Box::new(job),
+            repeat,
+            cancelled: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            ctx: Mutex::new(0),
+        })
+    }
+
+    /// Run the job, unless it has been cancelled.
+    fn run(&self) {
+        if !self.cancelled.load(Ordering::SeqCst) {
+            (self.job)();
+        }
+        if !self.repeat {
+            self.finished.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[derive(Clone)]
+#[allow(non_camel_case_types)]
+/// Slapi_R_Event_Handle is returned when work is scheduled on an event queue.
+/// It can be used to cancel the work before it runs, or to stop work that
+/// repeats.
+pub struct Slapi_R_Event_Handle {
+    job: Arc<EventJob>,
+}
+
+impl Slapi_R_Event_Handle {
+    /// Cancel this scheduled work. Once this returns, the work will not be
+    /// started again. It is safe to cancel work that has already run, or to
+    /// cancel the same work twice.
+    pub fn cancel(&self) {
+        if self.job.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let ctx = *self.job.ctx.lock().unwrap();
+        if ctx == 0 {
+            return;
+        }
+        // If the server removed the event from its queue, it will never call
+        // us again, so we own the reference we gave it. Otherwise a once
+        // event has already fired and released it.
+        let removed = unsafe { sys::slapi_eq_cancel(ctx as *mut libc::c_void) } != 0;
+        if removed {
+            unsafe {
+                drop(Arc::from_raw(Arc::as_ptr(&self.job)));
+            }
+        }
+    }
+
+    /// Has this work been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.job.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Has this work run to completion. Repeating work is never finished,
+    /// it can only be cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.job.finished.load(Ordering::SeqCst)
+    }
+}
+
+/// Slapi_Event_Queue defines how a plugin may schedule work to run in the
+/// background, after a delay or on an interval. Plugins should be written
+/// against this trait so that the work can be tested with the
+/// MockEventQueue, rather than the Directory Server event queue.
+#[allow(non_camel_case_types)]
+pub trait Slapi_Event_Queue {
+    /// The current time of this queue, in seconds since the epoch.
+    fn now(&self) -> i64;
+    /// Run the job once, after the delay has passed.
+    fn once<F>(&self, delay: Duration, job: F) -> Result<Slapi_R_Event_Handle, EventQueueError>
+        where F: Fn() + Send + Sync + 'static;
+    /// Run the job every interval, with the first run after one interval.
+    fn repeat<F>(&self, interval: Duration, job: F) -> Result<Slapi_R_Event_Handle, EventQueueError>
+        where F: Fn() + Send + Sync + 'static;
+}
+
+// All the work that has been scheduled on the server event queue. This is
+// used to cancel the work when the plugin is closed.
+static SCHEDULED: Mutex<Vec<Slapi_R_Event_Handle>> = Mutex::new(Vec::new());
+
+/// The callback that the Directory Server event queue calls. We get our
+/// EventJob back from the arg pointer and run it.
+extern "C" fn slapi_r_eq_cb(_when: libc::time_t, arg: *mut libc::c_void) {
+    if arg.is_null() {
+        return;
+    }
+    let job: *const EventJob = arg as *const EventJob;
+    unsafe {
+        (*job).run();
+        if !(*job).repeat {
+            // The server will not call a once event again, so release the
+            // reference that we gave to it.
+            drop(Arc::from_raw(job));
+        }
+    }
+}
+
+#[allow(non_camel_case_types)]
+/// Slapi_R_Event_Queue schedules work on the Directory Server event queue.
+/// Work scheduled here is automatically cancelled when the plugin is closed.
+pub struct Slapi_R_Event_Queue {}
+
+impl Slapi_R_Event_Queue {
+    /// Access the Directory Server event queue.
+    pub fn new() -> Slapi_R_Event_Queue {
+        Slapi_R_Event_Queue {}
+    }
+
+    fn schedule(&self, job: Arc<EventJob>, delay: Duration, interval: Option<Duration>) -> Result<Slapi_R_Event_Handle, EventQueueError> {
+        let when: libc::time_t = self.now() as libc::time_t + delay.as_secs() as libc::time_t;
+        // Hold the lock while scheduling, so that the ctx is set before the
+        // handle can be cancelled by cancel_all.
+        let mut scheduled = SCHEDULED.lock().unwrap();
+        scheduled.retain(|h| !h.is_finished() && !h.is_cancelled());
+
+        let arg: *mut libc::c_void = Arc::into_raw(job.clone()) as *mut libc::c_void;
+        let ctx = unsafe {
+            match interval {
+                Some(i) => sys::slapi_eq_repeat(Some(slapi_r_eq_cb), arg, when, duration_as_millis(i) as libc::c_ulong),
+                None => sys::slapi_eq_once(Some(slapi_r_eq_cb), arg, when),
+            }
+        };
+        if ctx.is_null() {
+            unsafe {
+                drop(Arc::from_raw(arg as *const EventJob));
+            }
+            return Err(EventQueueError::ScheduleFailed);
+        }
+        *job.ctx.lock().unwrap() = ctx as usize;
+
+        let handle = Slapi_R_Event_Handle { job };
+        scheduled.push(handle.clone());
+        Ok(handle)
+    }
+}
+
+impl Default for Slapi_R_Event_Queue {
+    fn default() -> Slapi_R_Event_Queue {
+        Slapi_R_Event_Queue::new()
+    }
+}
+
+impl Slapi_Event_Queue for Slapi_R_Event_Queue {
+    /// The current time from slapi_current_time.
+    fn now(&self) -> i64 {
+        unsafe { sys::slapi_current_time() as i64 }
+    }
+
+    /// Run the job once on the server event queue. The server event queue has
+    /// a resolution of one second.
+    fn once<F>(&self, delay: Duration, job: F) -> Result<Slapi_R_Event_Handle, EventQueueError>
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.schedule(EventJob::new(job, false), delay, None)
+    }
+
+    /// Run the job every interval on the server event queue.
+    fn repeat<F>(&self, interval: Duration, job: F) -> Result<Slapi_R_Event_Handle, EventQueueError>
+        where F: Fn() + Send + Sync + 'static
+    {
+        self.schedule(EventJob::new(job, true), interval, Some(interval))
+    }
+}
+
+/// Cancel all work that has been scheduled on the server event queue. This is
+/// called by the Slapi_R_Plugin_Manager as the plugin closes, so you should
+/// not need to call it yourself.
+pub fn cancel_all() {
+    let mut scheduled = SCHEDULED.lock().unwrap();
+    for handle in scheduled.drain(..) {
+        handle.cancel();
+    }
+}
+
+fn duration_as_millis(d: Duration) -> u64 {
+    d.as_secs() * 1000 + d.subsec_millis() as u64
+}
+
+struct MockEvent {
+    due: i64,
+    interval: Option<i64>,
+    job: Arc<EventJob>,
+}
+
+/// MockEventQueue is an event queue with a mock clock, for testing plugin
+/// work without Directory Server. Time only moves when advance() is called,
+/// and any work that becomes due is run on the calling thread.
+pub struct MockEventQueue {
+    now: Mutex<i64>,
+    events: Mutex<Vec<MockEvent>>,
+}
+
+impl MockEventQueue {
+    /// Create a new mock event queue, with the clock starting at the given
+    /// time in seconds.
+    pub fn new(now: i64) -> MockEventQueue {
+        MockEventQueue {
+            now: Mutex::new(now),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Move the mock clock forward, running all work that becomes due in the
+    /// order that it is due. Returns the number of jobs that were run.
+    pub fn advance(&self, by: Duration) -> usize {
+        let target = *self.now.lock().unwrap() + by.as_secs() as i64;
+        let mut ran = 0;
+        loop {
+            // Take the next due event, and run it without holding the locks so
+            // that the job may schedule more work on this queue.
+            let next = {
+                let mut events = self.events.lock().unwrap();
+                events.retain(|e| !e.job.cancelled.load(Ordering::SeqCst));
+                let idx = events.iter()
+                    .enumerate()
+                    .filter(|&(_, e)| e.due <= target)
+                    .min_by_key(|&(_, e)| e.due)
+                    .map(|(i, _)| i);
+                match idx {
+                    Some(i) => events.remove(i),
+                    None => break,
+                }
+            };
+            *self.now.lock().unwrap() = next.due;
+            next.job.run();
+            ran += 1;
+            if let Some(interval) = next.interval {
+                self.events.lock().unwrap().push(MockEvent {
+                    due: next.due + interval,
+                    interval: Some(interval),
+                    job: next.job,
+                });
+            }
+        }
+        *self.now.lock().unwrap() = target;
+        ran
+    }
+
+    /// The number of jobs waiting to run on this queue.
+    pub fn pending(&self) -> usize {
+        self.events.lock().unwrap()
+            .iter()
+            .filter(|e| !e.job.cancelled.load(Ordering::SeqCst))
+            .count()
+    }
+
+    fn schedule(&self, job: Arc<EventJob>, delay: Duration, interval: Option<Duration>) -> Slapi_R_Event_Handle {
+        // Like the server queue, an interval of less than a second still
+        // has to advance the clock, or advance() would never finish.
+        let interval = interval.map(|i| ::std::cmp::max(i.as_secs() as i64, 1));
+        self.events.lock().unwrap().push(MockEvent {
+            due: self.now() + delay.as_secs() as i64,
+            interval,
+            job: job.clone(),
+        });
+        Slapi_R_Event_Handle { job }
+    }
+}
+
+impl Slapi_Event_Queue for MockEventQueue {
+    /// The current time of the mock clock.
+    fn now(&self) -> i64 {
+        *self.now.lock().unwrap()
+    }
+
+    /// Run the job once, when the mock clock passes the delay.
+    fn once<F>(&self, delay: Duration, job: F) -> Result<Slapi_R_Event_Handle, EventQueueError>
+        where F: Fn() + Send + Sync + 'static
+    {
+        Ok(self.schedule(EventJob::new(job, false), delay, None))
+    }
+
+    /// Run the job every time the mock clock passes the interval.
+    fn repeat<F>(&self, interval: Duration, job: F) -> Result<Slapi_R_Event_Handle, EventQueueError>
+        where F: Fn() + Send + Sync + 'static
+    {
+        Ok(self.schedule(EventJob::new(job, true), interval, Some(interval)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_mock_event_queue_once_and_repeat() {
+        let eq = MockEventQueue::new(1000);
+        let once_count = Arc::new(AtomicUsize::new(0));
+        let repeat_count = Arc::new(AtomicUsize::new(0));
+
+        let c = once_count.clone();
+        let once = eq.once(Duration::from_secs(10), move || { c.fetch_add(1, Ordering::SeqCst); }).unwrap();
+        let c = repeat_count.clone();
+        let repeat = eq.repeat(Duration::from_secs(5), move || { c.fetch_add(1, Ordering::SeqCst); }).unwrap();
+
+        eq.advance(Duration::from_secs(9));
+        assert_eq!(once_count.load(Ordering::SeqCst), 0);
+        assert_eq!(repeat_count.load(Ordering::SeqCst), 1);
+
+        eq.advance(Duration::from_secs(11));
+        assert_eq!(eq.now(), 1020);
+        assert_eq!(once_count.load(Ordering::SeqCst), 1);
+        assert_eq!(repeat_count.load(Ordering::SeqCst), 4);
+        assert!(once.is_finished());
+
+        repeat.cancel();
+        eq.advance(Duration::from_secs(60));
+        assert_eq!(repeat_count.load(Ordering::SeqCst), 4);
+        assert_eq!(eq.pending(), 0);
+    }
+}
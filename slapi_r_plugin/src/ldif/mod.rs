@@ -0,0 +1,737 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use std::fs;
+use std::io::BufRead;
+use std::io::Write;
+
+use super::error::LdifError;
+use super::mods::ModType;
+use super::mods::Modification;
+
+/// The column that long lines are folded at when writing.
+const LDIF_LINE_WIDTH: usize = 76;
+
+/// A single record of an LDIF file. Files of entries, such as exports, contain
+/// Entry records. Change files, such as enable.ldif, contain the records with
+/// a changetype.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LdifRecord {
+    /// An entry, from a record with no changetype.
+    Entry {
+        /// The DN of the entry.
+        dn: String,
+        /// The attribute values of the entry, in the order they were given.
+        attrs: Vec<(String, Vec<u8>)>,
+    },
+    /// changetype: add
+    Add {
+        /// The DN of the entry to add.
+        dn: String,
+        /// The attribute values of the entry, in the order they were given.
+        attrs: Vec<(String, Vec<u8>)>,
+    },
+    /// changetype: delete
+    Delete {
+        /// The DN of the entry to delete.
+        dn: String,
+    },
+    /// changetype: modify
+    Modify {
+        /// The DN of the entry to modify.
+        dn: String,
+        /// The modifications, in the order they apply.
+        mods: Vec<Modification>,
+    },
+    /// changetype: modrdn or moddn
+    ModRdn {
+        /// The DN of the entry to rename.
+        dn: String,
+        /// The new RDN of the entry.
+        new_rdn: String,
+        /// Remove the values of the old RDN from the entry.
+        delete_old_rdn: bool,
+        /// The new parent of the entry, if it is moved.
+        new_superior: Option<String>,
+    },
+}
+
+impl LdifRecord {
+    /// The DN that this record applies to.
+    pub fn dn(&self) -> &str {
+        match *self {
+            LdifRecord::Entry { ref dn, .. } => dn,
+            LdifRecord::Add { ref dn, .. } => dn,
+            LdifRecord::Delete { ref dn } => dn,
+            LdifRecord::Modify { ref dn, .. } => dn,
+            LdifRecord::ModRdn { ref dn, .. } => dn,
+        }
+    }
+
+    /// The attribute values of an Entry or Add record.
+    pub fn attributes(&self) -> Option<&[(String, Vec<u8>)]> {
+        match *self {
+            LdifRecord::Entry { ref attrs, .. } => Some(attrs),
+            LdifRecord::Add { ref attrs, .. } => Some(attrs),
+            _ => None,
+        }
+    }
+
+    /// The modifications of a Modify record.
+    pub fn modifications(&self) -> Option<&[Modification]> {
+        match *self {
+            LdifRecord::Modify { ref mods, .. } => Some(mods),
+            _ => None,
+        }
+    }
+
+    /// Consume a Modify record, returning the modifications.
+    pub fn into_modifications(self) -> Option<Vec<Modification>> {
+        match self {
+            LdifRecord::Modify { mods, .. } => Some(mods),
+            _ => None,
+        }
+    }
+}
+
+const BASE64_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as base64, with padding.
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | (b[2] as u32);
+        out.push(BASE64_CHARS[(n >> 18) as usize & 0x3f] as char);
+        out.push(BASE64_CHARS[(n >> 12) as usize & 0x3f] as char);
+        out.push(if chunk.len() > 1 { BASE64_CHARS[(n >> 6) as usize & 0x3f] as char } else { '=' });
+        out.push(if chunk.len() > 2 { BASE64_CHARS[n as usize & 0x3f] as char } else { '=' });
+    }
+    out
+}
+
+/// Decode base64, ignoring whitespace. Returns None if the input is not
+/// valid base64.
+pub fn base64_decode(data: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() / 4 * 3);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut padding = 0;
+    for c in data.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return None,
+        };
+        // Data after padding is not valid.
+        if padding > 0 {
+            return None;
+        }
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if padding > 2 || bits >= 6 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Can this value be written as is, or must it be base64 encoded. This is
+/// the SAFE-STRING of RFC 2849, and values with trailing spaces are encoded
+/// so that they are not lost.
+fn is_safe_string(value: &[u8]) -> bool {
+    match value.first() {
+        None => return true,
+        Some(&b' ') | Some(&b':') | Some(&b'<') => return false,
+        _ => {}
+    }
+    if value.last() == Some(&b' ') {
+        return false;
+    }
+    value.iter().all(|&c| c != 0 && c != b'\n' && c != b'\r' && c < 0x80)
+}
+
+/// A logical line of a record, after unfolding, with the line number it
+/// started on.
+type LdifLine = (usize, String);
+
+/// LdifReader parses a stream of LDIF into LdifRecords, one record at a time,
+/// so that large files need not be held in memory. It is an Iterator, and
+/// stops at the end of the stream.
+pub struct LdifReader<R: BufRead> {
+    reader: R,
+    line_no: usize,
+    first: bool,
+    done: bool,
+}
+
+impl<R: BufRead> LdifReader<R> {
+    /// Create a reader over a buffered stream of LDIF.
+    pub fn new(reader: R) -> LdifReader<R> {
+        LdifReader {
+            reader,
+            line_no: 0,
+            first: true,
+            done: false,
+        }
+    }
+
+    /// Read the logical lines of the next record, joining folded lines and
+    /// skipping comments. Returns an empty Vec at the end of the stream.
+    fn read_lines(&mut self) -> Result<Vec<LdifLine>, LdifError> {
+        let mut lines: Vec<LdifLine> = Vec::new();
+        let mut in_comment = false;
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            let n = match self.reader.read_line(&mut buf) {
+                Ok(n) => n,
+                Err(e) => return Err(LdifError::Io(e.to_string())),
+            };
+            if n == 0 {
+                self.done = true;
+                return Ok(lines);
+            }
+            self.line_no += 1;
+            let line = buf.trim_end_matches('\n').trim_end_matches('\r');
+
+            if line.is_empty() {
+                if lines.is_empty() {
+                    in_comment = false;
+                    continue;
+                }
+                return Ok(lines);
+            }
+            if let Some(continued) = line.strip_prefix(' ') {
+                if in_comment {
+                    continue;
+                }
+                match lines.last_mut() {
+                    Some(&mut (_, ref mut last)) => last.push_str(continued),
+                    None => return Err(LdifError::Syntax(self.line_no, "continuation line without a preceding line".to_string())),
+                }
+                continue;
+            }
+            if line.starts_with('#') {
+                in_comment = true;
+                continue;
+            }
+            in_comment = false;
+            lines.push((self.line_no, line.to_string()));
+        }
+    }
+
+    /// Read and parse the next record.
+    fn read_record(&mut self) -> Result<Option<LdifRecord>, LdifError> {
+        loop {
+            if self.done {
+                return Ok(None);
+            }
+            let mut lines = self.read_lines()?;
+            if self.first && !lines.is_empty() {
+                self.first = false;
+                let (line_no, ref line) = lines[0];
+                if let Some((attr, value)) = split_line(line_no, line)? {
+                    if attr.eq_ignore_ascii_case("version") {
+                        if value != b"1" {
+                            return Err(LdifError::Syntax(line_no, "only LDIF version 1 is supported".to_string()));
+                        }
+                        lines.remove(0);
+                    }
+                }
+            }
+            if lines.is_empty() {
+                continue;
+            }
+            return parse_record(lines).map(Some);
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for LdifReader<R> {
+    type Item = Result<LdifRecord, LdifError>;
+
+    fn next(&mut self) -> Option<Result<LdifRecord, LdifError>> {
+        match self.read_record() {
+            Ok(Some(r)) => Some(Ok(r)),
+            Ok(None) => None,
+            Err(e) => {
+                // The stream position is unknown after an error.
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Split a logical line into the attribute and its decoded value. A line of
+/// "-" has no value, and returns None.
+fn split_line(line_no: usize, line: &str) -> Result<Option<(String, Vec<u8>)>, LdifError> {
+    if line == "-" {
+        return Ok(None);
+    }
+    let (attr, rest) = match line.find(':') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => return Err(LdifError::Syntax(line_no, format!("expected attribute: value, found {}", line))),
+    };
+    if attr.is_empty() {
+        return Err(LdifError::Syntax(line_no, "missing attribute name".to_string()));
+    }
+    if !is_attribute_description(attr) {
+        return Err(LdifError::Syntax(line_no, format!("invalid attribute description {}", attr)));
+    }
+    let value = if let Some(encoded) = rest.strip_prefix(':') {
+        match base64_decode(encoded.trim()) {
+            Some(v) => v,
+            None => return Err(LdifError::Syntax(line_no, format!("invalid base64 value for {}", attr))),
+        }
+    } else if let Some(url) = rest.strip_prefix('<') {
+        read_url(line_no, url.trim())?
+    } else {
+        rest.trim_start_matches(' ').as_bytes().to_vec()
+    };
+    Ok(Some((attr.to_string(), value)))
+}
+
+/// Is this an attribute description, an attribute type and its options such
+/// as cn;lang-en. Only ASCII letters, digits, - and ; may appear in one.
+pub fn is_attribute_description(attr: &str) -> bool {
+    !attr.is_empty() && attr.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b';')
+}
+
+/// Read the value of a URL. Only local files are supported, as file:///path.
+fn read_url(line_no: usize, url: &str) -> Result<Vec<u8>, LdifError> {
+    if !url.starts_with("file://") {
+        return Err(LdifError::Url(line_no, format!("unsupported URL {}, only file:// is supported", url)));
+    }
+    // The path follows an empty authority. Anything else names a host, and
+    // is not a local file.
+    let path = &url["file://".len()..];
+    if !path.starts_with('/') {
+        return Err(LdifError::Url(line_no, format!("unsupported URL {}, only local files are supported", url)));
+    }
+    match fs::read(path) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(LdifError::Url(line_no, format!("unable to read {}: {}", path, e))),
+    }
+}
+
+fn value_string(line_no: usize, attr: &str, value: Vec<u8>) -> Result<String, LdifError> {
+    match String::from_utf8(value) {
+        Ok(s) => Ok(s),
+        Err(_) => Err(LdifError::Syntax(line_no, format!("{} is not valid UTF-8", attr))),
+    }
+}
+
+/// Parse the logical lines of a record.
+fn parse_record(lines: Vec<LdifLine>) -> Result<LdifRecord, LdifError> {
+    let mut iter = lines.into_iter().peekable();
+
+    let (line_no, line) = iter.next().unwrap();
+    let dn = match split_line(line_no, &line)? {
+        Some((ref attr, ref value)) if attr.eq_ignore_ascii_case("dn") => value_string(line_no, "dn", value.clone())?,
+        _ => return Err(LdifError::Syntax(line_no, "a record must start with dn:".to_string())),
+    };
+
+    // Controls only have meaning to a server applying the change.
+    let mut changetype: Option<(usize, String)> = None;
+    let mut attrs: Vec<(usize, String, Vec<u8>)> = Vec::new();
+    for (line_no, line) in iter {
+        match split_line(line_no, &line)? {
+            Some((attr, value)) => {
+                if attrs.is_empty() && changetype.is_none() && attr.eq_ignore_ascii_case("control") {
+                    continue;
+                }
+                if attrs.is_empty() && changetype.is_none() && attr.eq_ignore_ascii_case("changetype") {
+                    changetype = Some((line_no, value_string(line_no, &attr, value)?.to_lowercase()));
+                    continue;
+                }
+                attrs.push((line_no, attr, value));
+            }
+            None => attrs.push((line_no, "-".to_string(), Vec::new())),
+        }
+    }
+
+    let (ct_line, changetype) = match changetype {
+        Some(c) => c,
+        None => {
+            return Ok(LdifRecord::Entry {
+                dn,
+                attrs: plain_attrs(attrs)?,
+            })
+        }
+    };
+
+    match changetype.as_str() {
+        "add" => Ok(LdifRecord::Add {
+            dn,
+            attrs: plain_attrs(attrs)?,
+        }),
+        "delete" => match attrs.first() {
+            Some(&(line_no, ref attr, _)) => Err(LdifError::Syntax(line_no, format!("unexpected {} in a delete record", attr))),
+            None => Ok(LdifRecord::Delete { dn }),
+        },
+        "modrdn" | "moddn" => {
+            let mut new_rdn = None;
+            let mut delete_old_rdn = None;
+            let mut new_superior = None;
+            for (line_no, attr, value) in attrs {
+                match attr.to_lowercase().as_str() {
+                    "newrdn" => new_rdn = Some(value_string(line_no, &attr, value)?),
+                    "deleteoldrdn" => {
+                        delete_old_rdn = match value.as_slice() {
+                            b"0" => Some(false),
+                            b"1" => Some(true),
+                            _ => return Err(LdifError::Syntax(line_no, "deleteoldrdn must be 0 or 1".to_string())),
+                        }
+                    }
+                    "newsuperior" => new_superior = Some(value_string(line_no, &attr, value)?),
+                    _ => return Err(LdifError::Syntax(line_no, format!("unexpected {} in a modrdn record", attr))),
+                }
+            }
+            match (new_rdn, delete_old_rdn) {
+                (Some(new_rdn), Some(delete_old_rdn)) => Ok(LdifRecord::ModRdn {
+                    dn,
+                    new_rdn,
+                    delete_old_rdn,
+                    new_superior,
+                }),
+                _ => Err(LdifError::Syntax(ct_line, "a modrdn record requires newrdn and deleteoldrdn".to_string())),
+            }
+        }
+        "modify" => {
+            let mut mods: Vec<Modification> = Vec::new();
+            let mut current: Option<Modification> = None;
+            for (line_no, attr, value) in attrs {
+                if attr == "-" {
+                    match current.take() {
+                        Some(m) => mods.push(m),
+                        None => return Err(LdifError::Syntax(line_no, "unexpected -".to_string())),
+                    }
+                    continue;
+                }
+                match current {
+                    Some(ref mut m) => {
+                        if !attr.eq_ignore_ascii_case(&m.attr) {
+                            return Err(LdifError::Syntax(line_no, format!("expected a value of {} or -, found {}", m.attr, attr)));
+                        }
+                        m.values.push(value);
+                    }
+                    None => {
+                        let mod_type = match attr.to_lowercase().as_str() {
+                            "add" => ModType::Add,
+                            "delete" => ModType::Delete,
+                            "replace" => ModType::Replace,
+                            _ => return Err(LdifError::Syntax(line_no, format!("expected add, delete or replace, found {}", attr))),
+                        };
+                        let mod_attr = value_string(line_no, &attr, value)?;
+                        if !is_attribute_description(&mod_attr) {
+                            return Err(LdifError::Syntax(line_no, format!("invalid attribute description {}", mod_attr)));
+                        }
+                        current = Some(Modification::new(mod_type, &mod_attr));
+                    }
+                }
+            }
+            // Be lenient of a missing - at the end of the record.
+            if let Some(m) = current {
+                mods.push(m);
+            }
+            Ok(LdifRecord::Modify { dn, mods })
+        }
+        _ => Err(LdifError::Syntax(ct_line, format!("unknown changetype {}", changetype))),
+    }
+}
+
+fn plain_attrs(attrs: Vec<(usize, String, Vec<u8>)>) -> Result<Vec<(String, Vec<u8>)>, LdifError> {
+    let mut out = Vec::with_capacity(attrs.len());
+    for (line_no, attr, value) in attrs {
+        if attr == "-" {
+            return Err(LdifError::Syntax(line_no, "unexpected -".to_string()));
+        }
+        out.push((attr, value));
+    }
+    Ok(out)
+}
+
+/// Parse a string of LDIF into its records.
+///
+/// # Failures
+/// The first error in the LDIF is returned.
+pub fn parse_str(ldif: &str) -> Result<Vec<LdifRecord>, LdifError> {
+    LdifReader::new(ldif.as_bytes()).collect()
+}
+
+/// LdifWriter writes LdifRecords as LDIF to a stream, base64 encoding values
+/// where required and folding long lines.
+pub struct LdifWriter<W: Write> {
+    writer: W,
+    first: bool,
+}
+
+impl<W: Write> LdifWriter<W> {
+    /// Create a writer over a stream.
+    pub fn new(writer: W) -> LdifWriter<W> {
+        LdifWriter {
+            writer,
+            first: true,
+        }
+    }
+
+    /// Return the underlying stream.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_folded(&mut self, line: &str) -> Result<(), LdifError> {
+        // Values that are not ASCII are base64 encoded, but the attribute
+        // names of a record that was not read from LDIF may not be, so lines
+        // are only folded between characters.
+        let mut out = String::with_capacity(line.len() + line.len() / LDIF_LINE_WIDTH * 2 + 1);
+        let mut rest = line;
+        let mut width = LDIF_LINE_WIDTH;
+        while rest.len() > width {
+            let mut end = width;
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            out.push_str(&rest[..end]);
+            out.push_str("\n ");
+            rest = &rest[end..];
+            width = LDIF_LINE_WIDTH - 1;
+        }
+        out.push_str(rest);
+        out.push('\n');
+        match self.writer.write_all(out.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(LdifError::Io(e.to_string())),
+        }
+    }
+
+    fn write_value(&mut self, attr: &str, value: &[u8]) -> Result<(), LdifError> {
+        if value.is_empty() {
+            self.write_folded(&format!("{}:", attr))
+        } else if is_safe_string(value) {
+            // is_safe_string only allows ASCII.
+            self.write_folded(&format!("{}: {}", attr, String::from_utf8_lossy(value)))
+        } else {
+            self.write_folded(&format!("{}:: {}", attr, base64_encode(value)))
+        }
+    }
+
+    /// Write a record.
+    ///
+    /// # Failures
+    /// If the stream fails, LdifError::Io is returned.
+    pub fn write_record(&mut self, record: &LdifRecord) -> Result<(), LdifError> {
+        if !self.first {
+            if let Err(e) = self.writer.write_all(b"\n") {
+                return Err(LdifError::Io(e.to_string()));
+            }
+        }
+        self.first = false;
+        self.write_value("dn", record.dn().as_bytes())?;
+        match *record {
+            LdifRecord::Entry { ref attrs, .. } => {
+                for (attr, value) in attrs.iter() {
+                    self.write_value(attr, value)?;
+                }
+            }
+            LdifRecord::Add { ref attrs, .. } => {
+                self.write_folded("changetype: add")?;
+                for (attr, value) in attrs.iter() {
+                    self.write_value(attr, value)?;
+                }
+            }
+            LdifRecord::Delete { .. } => {
+                self.write_folded("changetype: delete")?;
+            }
+            LdifRecord::Modify { ref mods, .. } => {
+                self.write_folded("changetype: modify")?;
+                for m in mods.iter() {
+                    self.write_value(m.mod_type.as_str(), m.attr.as_bytes())?;
+                    for value in m.values.iter() {
+                        self.write_value(&m.attr, value)?;
+                    }
+                    self.write_folded("-")?;
+                }
+            }
+            LdifRecord::ModRdn { ref new_rdn, delete_old_rdn, ref new_superior, .. } => {
+                self.write_folded("changetype: modrdn")?;
+                self.write_value("newrdn", new_rdn.as_bytes())?;
+                self.write_folded(if delete_old_rdn { "deleteoldrdn: 1" } else { "deleteoldrdn: 0" })?;
+                if let Some(ref s) = *new_superior {
+                    self.write_value("newsuperior", s.as_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write records to a string of LDIF.
+pub fn to_string(records: &[LdifRecord]) -> String {
+    let mut writer = LdifWriter::new(Vec::new());
+    for r in records {
+        // Writing to a Vec can not fail.
+        let _ = writer.write_record(r);
+    }
+    String::from_utf8(writer.into_inner()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mods::ModType;
+    use std::slice;
+
+    #[test]
+    fn test_ldif_base64() {
+        for v in [&b""[..], b"a", b"ab", b"abc", b"\x00\xff binary"].iter() {
+            assert_eq!(base64_decode(&base64_encode(v)).unwrap(), v.to_vec());
+        }
+        assert_eq!(base64_encode(b"hello"), "aGVsbG8=");
+        assert_eq!(base64_decode("aGVs\n bG8="), Some(b"hello".to_vec()));
+        assert_eq!(base64_decode("a*"), None);
+    }
+
+    #[test]
+    fn test_ldif_enable_fixture() {
+        let records = parse_str(include_str!("../../../plugins/ro_replica/enable.ldif")).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].dn(), "cn=ro_replica,cn=plugins,cn=config");
+        match records[0] {
+            LdifRecord::Add { ref attrs, .. } => {
+                assert!(attrs.contains(&("nsslapd-pluginType".to_string(), b"preoperation".to_vec())));
+            }
+            _ => panic!("expected an add record"),
+        }
+    }
+
+    #[test]
+    fn test_ldif_parse_changes() {
+        let ldif = "version: 1\n\
+# A comment\n \
+that is folded\n\
+dn:: dWlkPWEsZGM9ZXhhbXBsZSxkYz1jb20=\n\
+changetype: modify\n\
+replace: descr\n \
+iption\n\
+description: one\n\
+description:: dHdvIA==\n\
+-\n\
+delete: mail\n\
+-\n\
+\n\
+dn: uid=a,dc=example,dc=com\n\
+changetype: modrdn\n\
+newrdn: uid=b\n\
+deleteoldrdn: 1\n\
+\n\
+dn: uid=b,dc=example,dc=com\n\
+changetype: delete\n";
+        let records = parse_str(ldif).unwrap();
+        assert_eq!(records.len(), 3);
+        let mods = records[0].modifications().unwrap();
+        assert_eq!(records[0].dn(), "uid=a,dc=example,dc=com");
+        assert_eq!(mods[0], Modification::new(ModType::Replace, "description").value("one").value("two "));
+        assert_eq!(mods[1], Modification::new(ModType::Delete, "mail"));
+        assert_eq!(records[1], LdifRecord::ModRdn {
+            dn: "uid=a,dc=example,dc=com".to_string(),
+            new_rdn: "uid=b".to_string(),
+            delete_old_rdn: true,
+            new_superior: None,
+        });
+        assert_eq!(records[2], LdifRecord::Delete { dn: "uid=b,dc=example,dc=com".to_string() });
+
+        // Writing and reading again gives the same records.
+        assert_eq!(parse_str(&to_string(&records)).unwrap(), records);
+    }
+
+    #[test]
+    fn test_ldif_write_folds_and_encodes() {
+        let long = "x".repeat(100);
+        let record = LdifRecord::Entry {
+            dn: "cn=a,dc=example,dc=com".to_string(),
+            attrs: vec![
+                ("description".to_string(), long.clone().into_bytes()),
+                ("cn".to_string(), " leading space".as_bytes().to_vec()),
+                ("sn".to_string(), "vertical tab\x0b".as_bytes().to_vec()),
+                ("title".to_string(), Vec::new()),
+            ],
+        };
+        let out = to_string(slice::from_ref(&record));
+        for line in out.lines() {
+            assert!(line.len() <= 76);
+        }
+        assert!(out.contains("cn:: IGxlYWRpbmcgc3BhY2U=\n"));
+        assert_eq!(parse_str(&out).unwrap(), vec![record]);
+    }
+
+    #[test]
+    fn test_ldif_errors() {
+        match parse_str("cn: a\n") {
+            Err(LdifError::Syntax(1, _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        match parse_str("dn: cn=a\nchangetype: modify\nreplace: cn\nsn: b\n") {
+            Err(LdifError::Syntax(4, _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        match parse_str("dn: cn=a\njpegPhoto:< http://example.com/a.jpg\n") {
+            Err(LdifError::Url(2, _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        match parse_str("dn: cn=a\njpegPhoto:< file://example.com/etc/passwd\n") {
+            Err(LdifError::Url(2, _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        match parse_str("dn: cn=a\ncn;lang-en: a\n\u{20ac}\u{20ac}: b\n") {
+            Err(LdifError::Syntax(3, _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+        match parse_str("dn: cn=a\nchangetype: modify\nreplace: c n\n") {
+            Err(LdifError::Syntax(3, _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_ldif_write_folds_between_characters() {
+        // Records built in code are not checked, and must still be written.
+        let attr = "\u{20ac}".repeat(40);
+        let record = LdifRecord::Entry {
+            dn: "cn=a,dc=example,dc=com".to_string(),
+            attrs: vec![(attr.clone(), b"y".to_vec())],
+        };
+        let out = to_string(&[record]);
+        let unfolded = out.replace("\n ", "");
+        assert!(unfolded.contains(&format!("{}: y\n", attr)));
+        for line in out.lines() {
+            assert!(line.len() <= 76);
+        }
+    }
+}
@@ -14,7 +14,21 @@
 
 #![cfg_attr(feature="clippy", plugin(clippy))]
 
-extern crate libc;
+// Miri can not run log.c, so the mock backend defines its variadic functions.
+#![cfg_attr(all(slapi_r_mock, miri), feature(c_variadic))]
+
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+// Exported so that slapi_r_plugin_init! does not need plugins to depend on it.
+#[doc(hidden)]
+pub extern crate libc;
+// Renamed, as our own log module would shadow it.
+extern crate log as rust_log;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate slapi_r_plugin_sys;
 
 ///
 /// Constants Module
@@ -58,6 +72,77 @@ pub mod entry;
 /// may contain other related data.
 pub mod operation;
 ///
+/// Modification Module
+///
+/// This module contains the types that describe a change to the attributes of
+/// an entry, as used by modify operations and LDIF change records.
+pub mod mods;
+///
+/// LDIF Module
+///
+/// This module reads and writes LDIF, as described by RFC 2849. Records are
+/// read one at a time from a stream, so that large exports can be processed,
+/// and values are base64 encoded and folded as required when written.
+pub mod ldif;
+///
+/// Access Control Module
+///
+/// This module contains the types used to ask Directory Server if the identity
+/// of the current operation is permitted to read, write, compare or search
+/// the attributes of an entry. The checks themselves are performed through the
+/// pblock, as the identity is a property of the operation.
+pub mod acl;
+///
+/// Event Queue Module
+///
+/// This module wraps the Directory Server event queue, so that plugins can
+/// schedule background work such as expiring entries. Work is given as a Rust
+/// closure, and can be cancelled through the handle that is returned. A mock
+/// event queue is provided so that scheduled work can be tested.
+pub mod eventq;
+///
+/// Task Module
+///
+/// This module wraps the Directory Server administrative task framework.
+/// Plugins register a handler by name, and when an administrator adds an
+/// entry under cn=<name>,cn=tasks,cn=config the handler is run on a worker
+/// thread with a task context that can report status and progress.
+pub mod task;
+///
+/// Metrics Module
+///
+/// This module provides counters, gauges and latency histograms that plugins
+/// can update from their callbacks. The registry is served as a read only
+/// monitor entry at cn=<plugin>,cn=monitor, and the plugin manager records
+/// the number of calls and errors of every callback into it.
+pub mod metrics;
+///
+/// Capability Module
+///
+/// This module detects which optional features the running Directory Server
+/// provides, by looking up the functions they require. Plugins can use this
+/// to disable a feature on older servers, rather than failing on a missing
+/// symbol.
+pub mod capability;
+///
+/// Replay Module
+///
+/// This module records the pblock of every plugin callback to a file of JSON
+/// lines, when the plugin configuration sets rustRecordFile. The records can
+/// then be replayed against the plugin callbacks with the mock backend in
+/// cargo test, so that the operations of an incident become a regression
+/// test.
+pub mod replay;
+///
+/// Mock Module
+///
+/// This module is built in place of libslapd when build.rs can not find it.
+/// It implements enough of the Directory Server plugin API to run plugin
+/// callbacks in tests, and provides functions to populate a pblock with the
+/// operation and entries that a callback expects.
+#[cfg(slapi_r_mock)]
+pub mod mock;
+///
 /// Pblock Module
 ///
 /// This module wraps the Directory Server Parameter Block data structure.
@@ -12,20 +12,68 @@
 
 #![warn(missing_docs)]
 
-// use libc;
+use libc;
+use slapi_r_plugin_sys as sys;
+use std::borrow::Cow;
+use std::cmp;
+use std::ffi::CStr;
 use std::ffi::CString;
+use std::mem;
 use std::os::raw::c_char;
 
+use super::capability::slapi_r_server_version;
 use super::error::LoggingError;
 use super::constants;
 use super::constants::LogLevel;
+use super::ldif::LdifRecord;
+use super::ldif::LdifWriter;
+use super::mods::ModType;
+use super::mods::Modification;
+use super::operation::Slapi_R_Operation;
+use super::pblock::Slapi_PBlock_V3;
+use super::plugin::plugin_name;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
+use rust_log;
 
-extern {
-    fn slapi_log_error(level: isize, system: *const c_char, message: *const c_char) -> isize;
+
+/// Returns true if Directory Server will write messages at this level to the
+/// Error Log. Use this to avoid building expensive messages that would be
+/// discarded. The logging macros in this module check it for you.
+pub fn slapi_r_log_is_enabled(level: LogLevel) -> bool {
+    unsafe { sys::slapi_is_loglevel_set(level as libc::c_int) != 0 }
 }
 
 
+// The subsystems that have logged, so that a CString is not allocated for
+// every message. Subsystems are normally constants, but the cache is bounded
+// in case they are not.
+const SUBSYSTEM_CACHE_SIZE: usize = 64;
+static SUBSYSTEMS: RwLock<Vec<&'static CStr>> = RwLock::new(Vec::new());
+
+fn c_subsystem(subsystem: &str) -> Cow<'static, CStr> {
+    let cached = SUBSYSTEMS.read().unwrap().iter().cloned().find(|c| c.to_bytes() == subsystem.as_bytes());
+    if let Some(c) = cached {
+        return Cow::Borrowed(c);
+    }
+    // A subsystem can not contain a nul byte, so log under the name without them.
+    let c = CString::new(subsystem).unwrap_or_else(|_| CString::new(subsystem.replace('\0', "")).unwrap_or_default());
+    let mut cache = SUBSYSTEMS.write().unwrap();
+    // Another thread may have added it since the read lock was dropped.
+    if let Some(cached) = cache.iter().cloned().find(|cached| *cached == c.as_c_str()) {
+        return Cow::Borrowed(cached);
+    }
+    if cache.len() >= SUBSYSTEM_CACHE_SIZE {
+        return Cow::Owned(c);
+    }
+    let c: &'static CStr = Box::leak(c.into_boxed_c_str());
+    cache.push(c);
+    Cow::Borrowed(c)
+}
+
 /// Write a message to the Directory Server Error Log.
 /// Directory Server performs *no formatting* of this message. You must use
 /// format!() to message, to pre-format the message. This is useful anyway,
@@ -37,13 +85,17 @@ extern {
 /// error is transmitted correctly.
 ///
 pub fn slapi_r_log_error(level: LogLevel, subsystem: &str, message: String) -> Result<(), LoggingError> {
-    let res: isize;
-    let c_subsystem = CString::new(subsystem).unwrap();
-    let c_message = CString::new(message).unwrap();
+    let res: libc::c_int;
+    let c_subsystem = c_subsystem(subsystem);
+    let len = cmp::min(message.len(), libc::c_int::MAX as usize) as libc::c_int;
     unsafe {
-        res = slapi_log_error(level as isize, c_subsystem.as_ptr(), c_message.as_ptr());
+        // The message is an argument, not the format, so that a % in it is
+        // not interpreted. It is given with its length, so that it does not
+        // need to be copied into a CString to add the nul.
+        res = sys::slapi_log_error(level as libc::c_int, c_subsystem.as_ptr() as *mut c_char,
+                                   b"%.*s\0".as_ptr() as *mut c_char, len, message.as_ptr());
     }
-    match res {
+    match res as isize {
         constants::LDAP_SUCCESS => Ok(()),
         // Ds logging error codes here are a bit meaningless right now
         _ => Err(LoggingError::Unknown),
@@ -52,27 +104,752 @@ pub fn slapi_r_log_error(level: LogLevel, subsystem: &str, message: String) -> R
 
 /// This macro wraps and discards the result of a slapi_r_log_error call. It's good for
 /// quick development, but you probably want the checked version that will return a
-/// plugin error.
+/// plugin error. The message is only evaluated if the level is enabled.
 #[macro_export]
 macro_rules! slapi_r_log_error_unchecked {
-    ( $level:ident, $subsystem:ident, $message:ident ) => (
-        match slapi_r_log_error($level, $subsystem, $message) {
-            Ok(_) => {},
-            Err(_) => {},
-        };
+    ( $level:expr, $subsystem:expr, $message:expr ) => (
+        if $crate::log::slapi_r_log_is_enabled($level) {
+            match $crate::log::slapi_r_log_error($level, $subsystem, $message) {
+                Ok(_) => {},
+                Err(_) => {},
+            };
+        }
     );
 }
 
 /// This macro is for plugins which return a PluginOperationError.
 /// If the logging fails, we immedately return the error and the plugin stops
-/// processing as this is a bad server state!
+/// processing as this is a bad server state! The message is only evaluated if
+/// the level is enabled, so format!() costs nothing when it is not.
 #[macro_export]
 macro_rules! slapi_r_log_error_plugin {
     ( $level:expr, $subsystem:expr, $message:expr ) => (
-        match slapi_r_log_error($level, $subsystem, $message) {
-            Ok(_) => {},
-            Err(_) => return Err(PluginOperationError::LoggingError),
+        if $crate::log::slapi_r_log_is_enabled($level) {
+            match $crate::log::slapi_r_log_error($level, $subsystem, $message) {
+                Ok(_) => {},
+                Err(_) => return Err($crate::error::PluginOperationError::LoggingError),
+            };
+        }
+    );
+}
+
+
+/// The attribute of the plugin configuration entry that selects the format
+/// of structured log records.
+pub const LOG_FORMAT_ATTR: &str = "rustLogFormat";
+
+/// The format that structured log records are rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Render as space separated key=value pairs. Values containing spaces or
+    /// quotes are quoted. This is the default.
+    KeyValue,
+    /// Render as a single line JSON object.
+    Json,
+}
+
+impl LogFormat {
+    /// Parse the value of the rustLogFormat attribute. Returns None if the
+    /// value is not a known format.
+    pub fn from_config(value: &str) -> Option<LogFormat> {
+        match value.trim().to_lowercase().as_str() {
+            "keyvalue" | "key=value" | "text" => Some(LogFormat::KeyValue),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+static LOG_FORMAT: Mutex<LogFormat> = Mutex::new(LogFormat::KeyValue);
+
+/// Set the format of structured log records. This is called by
+/// Slapi_R_Plugin_Manager::register from the plugin configuration entry, so
+/// you should not need to call it yourself.
+pub fn slapi_r_log_set_format(format: LogFormat) {
+    *LOG_FORMAT.lock().unwrap() = format;
+}
+
+/// The format that structured log records are currently rendered in.
+pub fn slapi_r_log_format() -> LogFormat {
+    *LOG_FORMAT.lock().unwrap()
+}
+
+/// A value of a field in a structured log record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogValue {
+    /// A string value.
+    Str(String),
+    /// An integer value, such as an operation id.
+    Int(i64),
+    /// An unsigned integer value, such as a connection id.
+    UInt(u64),
+    /// A boolean value.
+    Bool(bool),
+}
+
+impl<'a> From<&'a str> for LogValue {
+    fn from(v: &'a str) -> LogValue {
+        LogValue::Str(v.to_string())
+    }
+}
+
+impl From<String> for LogValue {
+    fn from(v: String) -> LogValue {
+        LogValue::Str(v)
+    }
+}
+
+impl From<i64> for LogValue {
+    fn from(v: i64) -> LogValue {
+        LogValue::Int(v)
+    }
+}
+
+impl From<u64> for LogValue {
+    fn from(v: u64) -> LogValue {
+        LogValue::UInt(v)
+    }
+}
+
+impl From<isize> for LogValue {
+    fn from(v: isize) -> LogValue {
+        LogValue::Int(v as i64)
+    }
+}
+
+impl From<usize> for LogValue {
+    fn from(v: usize) -> LogValue {
+        LogValue::Int(v as i64)
+    }
+}
+
+impl From<bool> for LogValue {
+    fn from(v: bool) -> LogValue {
+        LogValue::Bool(v)
+    }
+}
+
+#[allow(non_camel_case_types)]
+/// Slapi_R_Log_Record is a structured log message. It is a message with a set
+/// of key/value fields, that is rendered as key=value pairs or as a JSON object
+/// depending on the plugin configuration. Fields keep the order they were
+/// added in.
+#[derive(Debug, Clone)]
+pub struct Slapi_R_Log_Record {
+    message: String,
+    fields: Vec<(String, LogValue)>,
+}
+
+impl Slapi_R_Log_Record {
+    /// Create a new record with a message for the current operation of the
+    /// pblock. The plugin name, if the plugin has been registered, and the
+    /// connection id, operation id and target DN of the operation, if the
+    /// pblock has an operation, are filled in.
+    pub fn new<T: Slapi_PBlock_V3>(pb: &T, message: &str) -> Slapi_R_Log_Record {
+        let record = Slapi_R_Log_Record::with_message(message);
+        match pb.get_operation() {
+            Some(op) => record.operation(&op),
+            None => record,
+        }
+    }
+
+    fn with_message(message: &str) -> Slapi_R_Log_Record {
+        let mut record = Slapi_R_Log_Record {
+            message: message.to_string(),
+            fields: Vec::new(),
         };
+        if let Some(name) = plugin_name() {
+            record = record.field("plugin", name);
+        }
+        record
+    }
+
+    /// Add a field to the record. If the key is already present, its value is
+    /// replaced.
+    pub fn field<V: Into<LogValue>>(mut self, key: &str, value: V) -> Slapi_R_Log_Record {
+        let value = value.into();
+        match self.fields.iter().position(|(k, _)| k == key) {
+            Some(i) => self.fields[i].1 = value,
+            None => self.fields.push((key.to_string(), value)),
+        }
+        self
+    }
+
+    /// Record the outcome of the operation, IE allow or reject.
+    pub fn outcome(self, outcome: &str) -> Slapi_R_Log_Record {
+        self.field("outcome", outcome)
+    }
+
+    fn operation(self, op: &Slapi_R_Operation) -> Slapi_R_Log_Record {
+        let record = self
+            .field("conn", op.conn_id())
+            .field("op", op.op_id());
+        match op.target_dn() {
+            Some(dn) => record.field("target_dn", dn),
+            None => record,
+        }
+    }
+
+    /// Render the record in the requested format, without a trailing newline.
+    pub fn render(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::KeyValue => {
+                let mut out = String::new();
+                for (k, v) in self.fields.iter() {
+                    out.push_str(k);
+                    out.push('=');
+                    out.push_str(&render_kv_value(v));
+                    out.push(' ');
+                }
+                out.push_str("msg=");
+                out.push_str(&quote_kv(&self.message));
+                out
+            }
+            LogFormat::Json => {
+                let mut out = String::from("{");
+                for (k, v) in self.fields.iter() {
+                    out.push_str(&quote_json(k));
+                    out.push(':');
+                    match *v {
+                        LogValue::Str(ref s) => out.push_str(&quote_json(s)),
+                        LogValue::Int(i) => out.push_str(&i.to_string()),
+                        LogValue::UInt(u) => out.push_str(&u.to_string()),
+                        LogValue::Bool(b) => out.push_str(if b { "true" } else { "false" }),
+                    }
+                    out.push(',');
+                }
+                out.push_str("\"msg\":");
+                out.push_str(&quote_json(&self.message));
+                out.push('}');
+                out
+            }
+        }
+    }
+}
+
+fn render_kv_value(v: &LogValue) -> String {
+    match *v {
+        LogValue::Str(ref s) => quote_kv(s),
+        LogValue::Int(i) => i.to_string(),
+        LogValue::UInt(u) => u.to_string(),
+        LogValue::Bool(b) => b.to_string(),
+    }
+}
+
+/// Values are only quoted when they must be, so that simple values stay easy
+/// to read and grep.
+fn quote_kv(s: &str) -> String {
+    let needs_quote = s.is_empty() || s.chars().any(|c| c.is_whitespace() || c == '"' || c == '=' || c == '\\' || c.is_control());
+    if !needs_quote {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn quote_json(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Write a structured record to the Directory Server Error Log, in the format
+/// selected by the rustLogFormat attribute of the plugin configuration.
+///
+/// # Failures
+/// As slapi_r_log_error.
+pub fn slapi_r_log_structured(level: LogLevel, subsystem: &str, record: &Slapi_R_Log_Record) -> Result<(), LoggingError> {
+    if !slapi_r_log_is_enabled(level) {
+        return Ok(());
+    }
+    let message = format!("{}\n", record.render(slapi_r_log_format())).replace('\0', "");
+    slapi_r_log_error(level, subsystem, message)
+}
+
+/// This macro is the structured version of slapi_r_log_error_plugin. The
+/// record is only built if the level is enabled, and if the logging fails the
+/// plugin returns PluginOperationError::LoggingError.
+#[macro_export]
+macro_rules! slapi_r_log_structured_plugin {
+    ( $level:expr, $subsystem:expr, $record:expr ) => (
+        if $crate::log::slapi_r_log_is_enabled($level) {
+            match $crate::log::slapi_r_log_structured($level, $subsystem, &$record) {
+                Ok(_) => {},
+                Err(_) => return Err($crate::error::PluginOperationError::LoggingError),
+            };
+        }
     );
 }
 
+/// Write a line to the Directory Server Access Log. Directory Server adds the
+/// timestamp, and the message should be in the access log style of
+/// conn=1 op=2 KEYWORD key=value. Prefer slapi_r_log_access_note, which does
+/// this for you.
+///
+/// # Failures
+/// As slapi_r_log_error.
+pub fn slapi_r_log_access(message: &str) -> Result<(), LoggingError> {
+    let c_message = CString::new(format!("{}\n", message).replace('\0', "")).unwrap();
+    let res = unsafe {
+        sys::slapi_log_access(constants::LDAP_DEBUG_STATS as libc::c_int, b"%s\0".as_ptr() as *const c_char, c_message.as_ptr())
+    };
+    match res as isize {
+        constants::LDAP_SUCCESS => Ok(()),
+        _ => Err(LoggingError::Unknown),
+    }
+}
+
+#[allow(non_camel_case_types)]
+/// Slapi_R_Access_Note is an annotation of an operation in the access log. It
+/// is written as a PLUGIN line of the connection and operation, IE
+/// conn=4 op=2 PLUGIN plugin=ro_replica action=reject
+///
+/// The notes= field of the RESULT line is a fixed set of flags that the server
+/// sets, such as U for an unindexed search, so a plugin has its own line.
+#[derive(Debug, Clone)]
+pub struct Slapi_R_Access_Note {
+    fields: Vec<(String, String)>,
+}
+
+impl Slapi_R_Access_Note {
+    /// Create a new note, with the plugin name if the plugin has been
+    /// registered.
+    pub fn new() -> Slapi_R_Access_Note {
+        let note = Slapi_R_Access_Note { fields: Vec::new() };
+        match plugin_name() {
+            Some(name) => note.field("plugin", &name),
+            None => note,
+        }
+    }
+
+    /// Add a field to the note. Values with spaces or quotes are quoted.
+    pub fn field(mut self, key: &str, value: &str) -> Slapi_R_Access_Note {
+        self.fields.push((key.to_string(), quote_kv(value)));
+        self
+    }
+
+    /// Record the action the plugin took on the operation, IE reject.
+    pub fn action(self, action: &str) -> Slapi_R_Access_Note {
+        self.field("action", action)
+    }
+
+    /// Render the note for an operation, without a trailing newline.
+    pub fn render(&self, conn_id: u64, op_id: isize) -> String {
+        let mut out = format!("conn={} op={} PLUGIN", conn_id, op_id);
+        for (k, v) in self.fields.iter() {
+            out.push(' ');
+            out.push_str(k);
+            out.push('=');
+            out.push_str(v);
+        }
+        out
+    }
+}
+
+impl Default for Slapi_R_Access_Note {
+    fn default() -> Slapi_R_Access_Note {
+        Slapi_R_Access_Note::new()
+    }
+}
+
+/// Annotate an operation in the access log. Operations that have asked not to
+/// be logged, such as some internal operations, are not annotated.
+///
+/// # Failures
+/// As slapi_r_log_error.
+pub fn slapi_r_log_access_note(op: &Slapi_R_Operation, note: &Slapi_R_Access_Note) -> Result<(), LoggingError> {
+    if op.is_action_nolog() {
+        return Ok(());
+    }
+    slapi_r_log_access(&note.render(op.conn_id(), op.op_id()))
+}
+
+#[allow(non_camel_case_types)]
+/// Slapi_R_Audit_Record is a record for the Directory Server Audit Log. It is
+/// written as the LDIF of the change, as the server writes the changes it
+/// audits, so that the audit log remains a valid record of changes for
+/// compliance.
+#[derive(Debug, Clone)]
+pub struct Slapi_R_Audit_Record {
+    time: i64,
+    record: LdifRecord,
+}
+
+impl Slapi_R_Audit_Record {
+    /// Create a record of a change. The time of the record is now. An Entry
+    /// record is audited as an add of the entry. If the plugin has been
+    /// registered, it is recorded as the modifiersName of an add or modify.
+    pub fn new(record: LdifRecord) -> Slapi_R_Audit_Record {
+        let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(_) => 0,
+        };
+        let mut record = match record {
+            LdifRecord::Entry { dn, attrs } => LdifRecord::Add { dn, attrs },
+            r => r,
+        };
+        if let Some(name) = plugin_name() {
+            let modifier = format!("cn={},cn=plugins,cn=config", name);
+            match record {
+                LdifRecord::Add { ref mut attrs, .. } => {
+                    attrs.push(("modifiersName".to_string(), modifier.into_bytes()));
+                }
+                LdifRecord::Modify { ref mut mods, .. } => {
+                    mods.push(Modification::new(ModType::Replace, "modifiersName").value(modifier));
+                }
+                _ => {}
+            }
+        }
+        Slapi_R_Audit_Record {
+            time: now,
+            record,
+        }
+    }
+
+    /// Set the time of the record, in seconds since the epoch.
+    pub fn time(mut self, time: i64) -> Slapi_R_Audit_Record {
+        self.time = time;
+        self
+    }
+
+    /// The change that is audited.
+    pub fn record(&self) -> &LdifRecord {
+        &self.record
+    }
+
+    /// Render the record, ending with the blank line that separates audit
+    /// records.
+    pub fn render(&self) -> String {
+        let mut writer = LdifWriter::new(format!("time: {}\n", audit_time(self.time)).into_bytes());
+        // Writing to a Vec can not fail.
+        let _ = writer.write_record(&self.record);
+        let mut out = String::from_utf8(writer.into_inner()).unwrap();
+        out.push('\n');
+        out
+    }
+}
+
+/// Format seconds since the epoch as the UTC YYYYMMDDhhmmss of the audit log.
+fn audit_time(secs: i64) -> String {
+    let days = secs.div_euclid(86400);
+    let rem = secs.rem_euclid(86400);
+    // Convert days since the epoch to a civil date.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}{:02}{:02}{:02}", y, m, d, rem / 3600, (rem % 3600) / 60, rem % 60)
+}
+
+/// The audit log function of a server release, and the signature it was
+/// declared with in that release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AuditLogFn {
+    /// int slapd_log_audit_proc(char *buffer, int buf_len), 1.3.0 to 1.3.4.
+    Proc,
+    /// int slapd_log_audit(char *buffer, int buf_len, int sourcelog), 1.3.5
+    /// and later 1.3 releases, where the audit fail log was added.
+    SourceLog,
+}
+
+// The sourcelog of slapd_log_audit that selects the audit log, rather than
+// the audit fail log.
+const SLAPD_AUDIT_LOG: libc::c_int = 0x4;
+
+impl AuditLogFn {
+    /// The audit log function of a server version, IE 1.3.5.1. Versions that
+    /// have not been checked against the server source have none, as calling
+    /// a function with the wrong signature is undefined behaviour.
+    fn for_version(version: &str) -> Option<AuditLogFn> {
+        let mut parts = version.split('.').map(|p| p.parse::<u32>().ok());
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Some(1)), Some(Some(3)), Some(Some(p))) if p < 5 => Some(AuditLogFn::Proc),
+            (Some(Some(1)), Some(Some(3)), Some(Some(_))) => Some(AuditLogFn::SourceLog),
+            _ => None,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match *self {
+            AuditLogFn::Proc => "slapd_log_audit_proc",
+            AuditLogFn::SourceLog => "slapd_log_audit",
+        }
+    }
+}
+
+/// Write a record to the Directory Server Audit Log. Nothing is written if the
+/// audit log is disabled.
+///
+/// Directory Server has no public function to write to the audit log, so the
+/// private one of the server is called. Its signature has changed between
+/// releases, so it is only called on the 1.3 releases, where it is known.
+/// Other servers, and servers that do not report their version, are not
+/// supported.
+///
+/// # Failures
+/// As slapi_r_log_error. If the server version is not supported,
+/// LoggingError::Unsupported is returned.
+pub fn slapi_r_log_audit(record: &Slapi_R_Audit_Record) -> Result<(), LoggingError> {
+    let audit_fn = match slapi_r_server_version().as_ref().and_then(|v| AuditLogFn::for_version(v)) {
+        Some(f) => f,
+        None => return Err(LoggingError::Unsupported),
+    };
+    // The function is private to the server, so it is looked up rather than
+    // linked, to fail cleanly if it is not exported.
+    let c_name = CString::new(audit_fn.symbol()).unwrap();
+    let f = unsafe { libc::dlsym(libc::RTLD_DEFAULT, c_name.as_ptr()) };
+    if f.is_null() {
+        return Err(LoggingError::Unsupported);
+    }
+    let rendered = record.render().replace('\0', "");
+    let len = rendered.len() as libc::c_int;
+    let mut buffer = CString::new(rendered).unwrap().into_bytes_with_nul();
+    let buffer = buffer.as_mut_ptr() as *mut c_char;
+    let res = unsafe {
+        match audit_fn {
+            AuditLogFn::Proc => {
+                let log_audit: unsafe extern "C" fn(*mut c_char, libc::c_int) -> libc::c_int = mem::transmute(f);
+                log_audit(buffer, len)
+            }
+            AuditLogFn::SourceLog => {
+                let log_audit: unsafe extern "C" fn(*mut c_char, libc::c_int, libc::c_int) -> libc::c_int = mem::transmute(f);
+                log_audit(buffer, len, SLAPD_AUDIT_LOG)
+            }
+        }
+    };
+    match res as isize {
+        constants::LDAP_SUCCESS => Ok(()),
+        _ => Err(LoggingError::Unknown),
+    }
+}
+
+/// Slapi_R_Logger is an implementation of the log crate facade that writes
+/// to the Directory Server Error Log. This allows the log::info!() family of
+/// macros to be used in plugins, and means that messages from the crates a
+/// plugin depends on are not lost. The module path of the caller is used as
+/// the subsystem.
+///
+/// This is installed by Slapi_R_Plugin_Manager::register, so you should not
+/// need to install it yourself.
+#[allow(non_camel_case_types)]
+pub struct Slapi_R_Logger;
+
+static LOGGER: Slapi_R_Logger = Slapi_R_Logger;
+
+impl Slapi_R_Logger {
+    /// Map a log crate level to the Directory Server level it is written at.
+    pub fn level_for(level: rust_log::Level) -> LogLevel {
+        match level {
+            rust_log::Level::Error => LogLevel::ERR,
+            rust_log::Level::Warn => LogLevel::WARNING,
+            rust_log::Level::Info => LogLevel::INFO,
+            rust_log::Level::Debug => LogLevel::DEBUG,
+            rust_log::Level::Trace => LogLevel::TRACE,
+        }
+    }
+}
+
+impl rust_log::Log for Slapi_R_Logger {
+    fn enabled(&self, metadata: &rust_log::Metadata) -> bool {
+        slapi_r_log_is_enabled(Slapi_R_Logger::level_for(metadata.level()))
+    }
+
+    fn log(&self, record: &rust_log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let subsystem = record.module_path().unwrap_or("slapi_r_plugin");
+        // Directory Server expects the message to end with a newline. Interior
+        // nul bytes would make the message unrepresentable, so drop them.
+        let message = format!("{}\n", record.args()).replace('\0', "");
+        let _ = slapi_r_log_error(Slapi_R_Logger::level_for(record.level()), subsystem, message);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the Slapi_R_Logger as the log crate logger. If a logger is already
+/// installed, IE when several rust plugins share a process, it is left in
+/// place.
+pub fn slapi_r_log_init() {
+    if rust_log::set_logger(&LOGGER).is_ok() {
+        rust_log::set_max_level(rust_log::LevelFilter::Trace);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Slapi_R_Logger;
+    use super::Slapi_R_Log_Record;
+    use super::LogFormat;
+    use super::Slapi_R_Access_Note;
+    use super::Slapi_R_Audit_Record;
+    use super::AuditLogFn;
+    use super::c_subsystem;
+    use constants::LogLevel;
+    use ldif::LdifRecord;
+    use mods::ModType;
+    use mods::Modification;
+    use rust_log::Level;
+
+    #[test]
+    fn test_loglevel_values() {
+        // These must match SLAPI_LOG_* in slapi-plugin.h
+        assert_eq!(LogLevel::FATAL as isize, 0);
+        assert_eq!(LogLevel::ACL as isize, 8);
+        assert_eq!(LogLevel::PLUGIN as isize, 14);
+        assert_eq!(LogLevel::ERR as isize, 22);
+        assert_eq!(LogLevel::DEBUG as isize, 26);
+    }
+
+    #[test]
+    fn test_c_subsystem() {
+        assert_eq!(c_subsystem("plugins::test").to_bytes(), b"plugins::test");
+        assert_eq!(c_subsystem("plugins::\0test").to_bytes(), b"plugins::test");
+    }
+
+    #[test]
+    fn test_slapi_r_logger_level_for() {
+        assert_eq!(Slapi_R_Logger::level_for(Level::Error), LogLevel::ERR);
+        assert_eq!(Slapi_R_Logger::level_for(Level::Warn), LogLevel::WARNING);
+        assert_eq!(Slapi_R_Logger::level_for(Level::Info), LogLevel::INFO);
+        assert_eq!(Slapi_R_Logger::level_for(Level::Debug), LogLevel::DEBUG);
+        assert_eq!(Slapi_R_Logger::level_for(Level::Trace), LogLevel::TRACE);
+    }
+
+    #[test]
+    fn test_log_record_render() {
+        let record = Slapi_R_Log_Record::with_message("rejected \"write\"")
+            .field("conn", 12isize)
+            .field("target_dn", "uid=a b,dc=example,dc=com")
+            .outcome("reject");
+        assert_eq!(record.render(LogFormat::KeyValue),
+                   "conn=12 target_dn=\"uid=a b,dc=example,dc=com\" outcome=reject msg=\"rejected \\\"write\\\"\"");
+        assert_eq!(record.render(LogFormat::Json),
+                   "{\"conn\":12,\"target_dn\":\"uid=a b,dc=example,dc=com\",\"outcome\":\"reject\",\"msg\":\"rejected \\\"write\\\"\"}");
+        assert_eq!(LogFormat::from_config("JSON"), Some(LogFormat::Json));
+        assert_eq!(LogFormat::from_config("xml"), None);
+    }
+
+    #[cfg(slapi_r_mock)]
+    #[test]
+    fn test_log_record_pblock() {
+        use constants;
+        use operation::OperationType;
+        use mock;
+        use pblock::Slapi_R_PBlock;
+
+        let pb = Slapi_R_PBlock::new();
+        assert_eq!(Slapi_R_Log_Record::new(&pb, "no operation").render(LogFormat::KeyValue), "msg=\"no operation\"");
+        mock::set_operation(&pb, mock::MockOperation::new(OperationType::Add).with_target_dn("uid=a,dc=example,dc=com"));
+        mock::set_conn_id(&pb, 4);
+        mock::set_int(&pb, constants::SLAPI_OPERATION_ID, 2);
+        assert_eq!(Slapi_R_Log_Record::new(&pb, "added").outcome("allow").render(LogFormat::KeyValue),
+                   "conn=4 op=2 target_dn=\"uid=a,dc=example,dc=com\" outcome=allow msg=added");
+        pb.destroy();
+    }
+
+    #[test]
+    fn test_audit_log_fn_for_version() {
+        assert_eq!(AuditLogFn::for_version("1.3.4.14"), Some(AuditLogFn::Proc));
+        assert_eq!(AuditLogFn::for_version("1.3.5.1"), Some(AuditLogFn::SourceLog));
+        assert_eq!(AuditLogFn::for_version("1.3.6.1.20161019git"), Some(AuditLogFn::SourceLog));
+        assert_eq!(AuditLogFn::for_version("1.4.0.1"), None);
+        assert_eq!(AuditLogFn::for_version("1.2.11.15"), None);
+        assert_eq!(AuditLogFn::for_version("1.3"), None);
+    }
+
+    #[test]
+    fn test_access_and_audit_render() {
+        let note = Slapi_R_Access_Note { fields: Vec::new() }.action("reject").field("reason", "not a replica");
+        assert_eq!(note.render(u64::MAX, 2), "conn=18446744073709551615 op=2 PLUGIN action=reject reason=\"not a replica\"");
+        let record = Slapi_R_Audit_Record::new(LdifRecord::Modify {
+            dn: "uid=a,dc=example,dc=com".to_string(),
+            mods: vec![
+                Modification::new(ModType::Replace, "nsAccountLock").value("true"),
+                Modification::new(ModType::Add, "description").value(" leading space"),
+            ],
+        }).time(1476878400);
+        assert_eq!(record.render(), "time: 20161019120000\ndn: uid=a,dc=example,dc=com\nchangetype: modify\n\
+                                     replace: nsAccountLock\nnsAccountLock: true\n-\n\
+                                     add: description\ndescription:: IGxlYWRpbmcgc3BhY2U=\n-\n\n");
+        let record = Slapi_R_Audit_Record::new(LdifRecord::Entry {
+            dn: "uid=b,dc=example,dc=com".to_string(),
+            attrs: vec![("uid".to_string(), b"b".to_vec())],
+        }).time(0);
+        assert_eq!(record.render(), "time: 19700101000000\ndn: uid=b,dc=example,dc=com\nchangetype: add\nuid: b\n\n");
+    }
+
+    #[cfg(slapi_r_mock)]
+    #[test]
+    fn test_access_note_and_audit_log() {
+        use super::slapi_r_log_access_note;
+        use super::slapi_r_log_audit;
+        use error::LoggingError;
+        use constants;
+        use constants::OP_FLAG_ACTION_NOLOG;
+        use operation::OperationType;
+        use mock;
+        use pblock::Slapi_PBlock_V3;
+        use pblock::Slapi_R_PBlock;
+
+        let pb = Slapi_R_PBlock::new();
+        mock::set_operation(&pb, mock::MockOperation::new(OperationType::Add));
+        mock::set_conn_id(&pb, 4);
+        mock::set_int(&pb, constants::SLAPI_OPERATION_ID, 2);
+        let op = pb.get_operation().unwrap();
+        let note = Slapi_R_Access_Note { fields: Vec::new() }.action("reject");
+        assert!(slapi_r_log_access_note(&op, &note).is_ok());
+        assert_eq!(mock::take_access_log(), vec!["conn=4 op=2 PLUGIN action=reject".to_string()]);
+        pb.destroy();
+
+        let pb = Slapi_R_PBlock::new();
+        mock::set_operation(&pb, mock::MockOperation::new(OperationType::Add).with_flag(OP_FLAG_ACTION_NOLOG));
+        let op = pb.get_operation().unwrap();
+        assert!(slapi_r_log_access_note(&op, &note).is_ok());
+        assert!(mock::take_access_log().is_empty());
+        pb.destroy();
+
+        let record = Slapi_R_Audit_Record::new(LdifRecord::Delete { dn: "uid=a,dc=example,dc=com".to_string() }).time(0);
+        assert!(matches!(slapi_r_log_audit(&record), Err(LoggingError::Unsupported)));
+        mock::set_server_version(Some("1.4.0.1"));
+        assert!(matches!(slapi_r_log_audit(&record), Err(LoggingError::Unsupported)));
+        assert!(mock::take_audit_log().is_empty());
+        mock::set_server_version(Some("1.3.4.9"));
+        assert!(slapi_r_log_audit(&record).is_ok());
+        mock::set_server_version(Some("1.3.5.1"));
+        assert!(slapi_r_log_audit(&record).is_ok());
+        mock::set_server_version(None);
+        assert_eq!(mock::take_audit_log(), vec![record.render(), record.render()]);
+    }
+}
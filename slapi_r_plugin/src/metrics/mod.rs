@@ -0,0 +1,456 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use libc;
+use slapi_r_plugin_sys as sys;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::constants::*;
+use super::entry::Entry;
+use super::entry::Slapi_R_Entry;
+use super::error::PluginOperationError;
+use super::log::slapi_r_log_error;
+use super::pblock::Slapi_R_PBlock;
+
+const SUBSYSTEM: &str = "slapi_r_plugin::metrics::mod";
+
+/// The default bucket bounds of a histogram, in microseconds.
+const DEFAULT_BUCKETS: [u64; 10] = [50, 100, 250, 500, 1000, 2500, 5000, 10000, 50000, 100000];
+
+/// A counter is a value that only ever goes up, such as the number of
+/// operations that were rejected.
+#[derive(Debug)]
+pub struct Counter {
+    value: AtomicU64,
+}
+
+impl Counter {
+    fn new() -> Counter {
+        Counter { value: AtomicU64::new(0) }
+    }
+
+    /// Increment the counter by one.
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Increment the counter by n.
+    pub fn add(&self, n: u64) {
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// The current value of the counter.
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// A gauge is a value that can go up and down, such as the number of entries
+/// in a plugin cache.
+#[derive(Debug)]
+pub struct Gauge {
+    value: AtomicI64,
+}
+
+impl Gauge {
+    fn new() -> Gauge {
+        Gauge { value: AtomicI64::new(0) }
+    }
+
+    /// Set the gauge to a value.
+    pub fn set(&self, v: i64) {
+        self.value.store(v, Ordering::Relaxed);
+    }
+
+    /// Increment the gauge by one.
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Decrement the gauge by one.
+    pub fn dec(&self) {
+        self.value.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// The current value of the gauge.
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// A histogram records the distribution of latencies, such as how long a
+/// plugin callback takes to process. Values are counted into buckets by
+/// their upper bound in microseconds.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<u64>,
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &[u64]) -> Histogram {
+        Histogram {
+            bounds: bounds.to_vec(),
+            // One extra bucket for values above the largest bound.
+            buckets: (0..bounds.len() + 1).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    /// Record a duration into the histogram.
+    pub fn observe(&self, d: Duration) {
+        let micros = d.as_secs() * 1_000_000 + d.subsec_micros() as u64;
+        self.observe_micros(micros);
+    }
+
+    /// Record a value in microseconds into the histogram.
+    pub fn observe_micros(&self, micros: u64) {
+        let idx = self.bounds.iter().position(|b| micros <= *b).unwrap_or(self.bounds.len());
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// Run the function, and record how long it took.
+    pub fn time<F, R>(&self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        let start = Instant::now();
+        let r = f();
+        self.observe(start.elapsed());
+        r
+    }
+
+    /// The number of values recorded.
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// The sum of all values recorded, in microseconds.
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    /// The cumulative count of values at or below each bucket bound. The
+    /// final bucket has no bound, and is the total count.
+    pub fn buckets(&self) -> Vec<(Option<u64>, u64)> {
+        let mut total = 0;
+        self.buckets.iter().enumerate().map(|(i, b)| {
+            total += b.load(Ordering::Relaxed);
+            (self.bounds.get(i).cloned(), total)
+        }).collect()
+    }
+}
+
+#[allow(non_camel_case_types)]
+/// Slapi_R_Metrics_Registry holds the named metrics of a plugin. Metrics are
+/// created on first use, and the same metric is returned for the same name,
+/// so callbacks can look them up as needed.
+///
+/// A name must start with a letter, and only contain letters, digits, _ and
+/// -, so that it is a valid attribute of the monitor entry. A metric with any
+/// other name still works, but is not added to the registry or monitored.
+pub struct Slapi_R_Metrics_Registry {
+    counters: Mutex<BTreeMap<String, Arc<Counter>>>,
+    gauges: Mutex<BTreeMap<String, Arc<Gauge>>>,
+    histograms: Mutex<BTreeMap<String, Arc<Histogram>>>,
+}
+
+impl Slapi_R_Metrics_Registry {
+    /// Create a new empty registry. Plugins should normally use the shared
+    /// registry from registry(), as that is the one that is monitored.
+    pub const fn new() -> Slapi_R_Metrics_Registry {
+        Slapi_R_Metrics_Registry {
+            counters: Mutex::new(BTreeMap::new()),
+            gauges: Mutex::new(BTreeMap::new()),
+            histograms: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Get or create the named counter. Only creating the counter allocates,
+    /// but the registry is locked, so a callback that counts on every call
+    /// should keep the counter rather than look it up each time.
+    pub fn counter(&self, name: &str) -> Arc<Counter> {
+        get_or_create(&self.counters, name, Counter::new)
+    }
+
+    /// Get or create the named gauge.
+    pub fn gauge(&self, name: &str) -> Arc<Gauge> {
+        get_or_create(&self.gauges, name, Gauge::new)
+    }
+
+    /// Get or create the named latency histogram, with the default buckets.
+    pub fn histogram(&self, name: &str) -> Arc<Histogram> {
+        get_or_create(&self.histograms, name, || Histogram::new(&DEFAULT_BUCKETS))
+    }
+
+    /// Render every metric as attribute and value pairs, in the form they
+    /// are shown in the monitor entry. Names are converted to valid LDAP
+    /// attribute names, so pre_search_calls becomes pre-search-calls.
+    pub fn render(&self) -> Vec<(String, String)> {
+        let mut attrs: Vec<(String, String)> = Vec::new();
+        for (name, c) in self.counters.lock().unwrap().iter() {
+            attrs.push((attribute_name(name), c.get().to_string()));
+        }
+        for (name, g) in self.gauges.lock().unwrap().iter() {
+            attrs.push((attribute_name(name), g.get().to_string()));
+        }
+        for (name, h) in self.histograms.lock().unwrap().iter() {
+            let base = attribute_name(name);
+            attrs.push((format!("{}-count", base), h.count().to_string()));
+            attrs.push((format!("{}-sum-us", base), h.sum().to_string()));
+            for (bound, count) in h.buckets() {
+                let le = match bound {
+                    Some(b) => b.to_string(),
+                    None => "inf".to_string(),
+                };
+                attrs.push((format!("{}-le-{}", base, le), count.to_string()));
+            }
+        }
+        attrs
+    }
+}
+
+impl Default for Slapi_R_Metrics_Registry {
+    fn default() -> Slapi_R_Metrics_Registry {
+        Slapi_R_Metrics_Registry::new()
+    }
+}
+
+// Look the metric up before inserting it, as the entry API would allocate the
+// name on every call.
+fn get_or_create<M, F>(metrics: &Mutex<BTreeMap<String, Arc<M>>>, name: &str, new: F) -> Arc<M>
+    where F: FnOnce() -> M
+{
+    if !valid_name(name) {
+        let _ = slapi_r_log_error(LogLevel::WARNING, SUBSYSTEM,
+                                  format!("Metric name {:?} is not valid, it will not be monitored\n", name));
+        return Arc::new(new());
+    }
+    let mut metrics = metrics.lock().unwrap();
+    if let Some(m) = metrics.get(name) {
+        return m.clone();
+    }
+    let m = Arc::new(new());
+    metrics.insert(name.to_string(), m.clone());
+    m
+}
+
+/// Is the name usable as a metric, IE it is a valid LDAP attribute name once
+/// converted by attribute_name.
+fn valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Convert a metric name into a valid LDAP attribute name.
+fn attribute_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect()
+}
+
+static REGISTRY: Slapi_R_Metrics_Registry = Slapi_R_Metrics_Registry::new();
+
+/// The shared metrics registry of this plugin. This is the registry that is
+/// served as the plugin monitor entry, and where the plugin manager records
+/// the callback counts.
+pub fn registry() -> &'static Slapi_R_Metrics_Registry {
+    &REGISTRY
+}
+
+/// The monitor entry we are serving, and the identity that created it.
+struct Monitor {
+    dn: CString,
+    plugin_identity: usize,
+}
+
+static MONITOR: Mutex<Option<Monitor>> = Mutex::new(None);
+
+/// Called by Directory Server for a search of the monitor entry. We fill in
+/// the current value of every metric.
+extern "C" fn slapi_r_monitor_search_cb(_slapi_pblock: *mut sys::Slapi_PBlock,
+                                        slapi_entry: *mut sys::Slapi_Entry,
+                                        _slapi_entry_after: *mut sys::Slapi_Entry,
+                                       returncode: *mut libc::c_int,
+                                       _returntext: *mut c_char,
+                                       _arg: *mut libc::c_void) -> libc::c_int {
+    // The entry is only valid until we return.
+    let entry = unsafe { Slapi_R_Entry::new(slapi_entry as *const libc::c_void) };
+    for (attr, value) in registry().render() {
+        // Names are checked as metrics are created, so this never skips one.
+        let (c_attr, c_value) = match (CString::new(attr), CString::new(value)) {
+            (Ok(a), Ok(v)) => (a, v),
+            _ => continue,
+        };
+        unsafe {
+            sys::slapi_entry_attr_set_charptr(entry.as_ptr(), c_attr.as_ptr(), c_value.as_ptr());
+        }
+    }
+    unsafe { *returncode = LDAP_SUCCESS as libc::c_int; }
+    SLAPI_DSE_CALLBACK_OK as libc::c_int
+}
+
+/// Called by Directory Server for any change to the monitor entry. The
+/// monitor is read only, so these are always rejected.
+extern "C" fn slapi_r_monitor_readonly_cb(_slapi_pblock: *mut sys::Slapi_PBlock,
+                                          _slapi_entry: *mut sys::Slapi_Entry,
+                                          _slapi_entry_after: *mut sys::Slapi_Entry,
+                                         returncode: *mut libc::c_int,
+                                         _returntext: *mut c_char,
+                                         _arg: *mut libc::c_void) -> libc::c_int {
+    unsafe { *returncode = LDAP_UNWILLING_TO_PERFORM as libc::c_int; }
+    SLAPI_DSE_CALLBACK_ERROR as libc::c_int
+}
+
+const MONITOR_FILTER: &[u8] = b"(objectclass=*)\0";
+const MONITOR_WRITE_OPS: [usize; 3] = [SLAPI_OPERATION_MODIFY, SLAPI_OPERATION_DELETE, SLAPI_OPERATION_MODRDN];
+
+/// Create the read only monitor entry cn=<name>,cn=monitor and serve the
+/// metrics registry from it. This is called by the Slapi_R_Plugin_Manager as
+/// the plugin starts, so you should not need to call it yourself.
+pub fn slapi_r_monitor_start(name: &str, plugin_identity: *const libc::c_void) -> Result<(), PluginOperationError> {
+    let dn = CString::new(format!("cn={},cn=monitor", name)).unwrap();
+    let monitor = Entry::new(&format!("cn={},cn=monitor", name))
+        .with_value("objectClass", "top")
+        .with_value("objectClass", "extensibleObject")
+        .with_value("cn", name);
+    let filter = MONITOR_FILTER.as_ptr() as *const c_char;
+
+    unsafe {
+        // Add the entry to cn=monitor. If it already exists from a previous
+        // start we can still serve it.
+        let e = match monitor.to_slapi_entry() {
+            Some(e) => e,
+            None => return Err(PluginOperationError::Unknown),
+        };
+        let pb = Slapi_R_PBlock::new();
+        sys::slapi_add_entry_internal_set_pb(pb.as_ptr(), e.into_raw(), ptr::null_mut(), plugin_identity as *mut sys::Slapi_ComponentId, 0);
+        sys::slapi_add_internal_pb(pb.as_ptr());
+        let result = pb.get_plugin_intop_result();
+        pb.destroy();
+        match result {
+            Some(LDAP_SUCCESS) | Some(LDAP_ALREADY_EXISTS) => {}
+            _ => return Err(PluginOperationError::Unknown),
+        }
+
+        sys::slapi_config_register_callback(SLAPI_OPERATION_SEARCH as libc::c_int, DSE_FLAG_PREOP as libc::c_int, dn.as_ptr(),
+                                            LDAP_SCOPE_BASE as libc::c_int, filter, Some(slapi_r_monitor_search_cb), ptr::null_mut());
+        for op in MONITOR_WRITE_OPS.iter() {
+            sys::slapi_config_register_callback(*op as libc::c_int, DSE_FLAG_PREOP as libc::c_int, dn.as_ptr(),
+                                                LDAP_SCOPE_BASE as libc::c_int, filter, Some(slapi_r_monitor_readonly_cb), ptr::null_mut());
+        }
+    }
+
+    *MONITOR.lock().unwrap() = Some(Monitor {
+        dn,
+        plugin_identity: plugin_identity as usize,
+    });
+    Ok(())
+}
+
+/// Stop serving and remove the monitor entry. This is called by the
+/// Slapi_R_Plugin_Manager as the plugin closes.
+pub fn slapi_r_monitor_stop() {
+    let monitor = match MONITOR.lock().unwrap().take() {
+        Some(m) => m,
+        None => return,
+    };
+    let filter = MONITOR_FILTER.as_ptr() as *const c_char;
+    unsafe {
+        sys::slapi_config_remove_callback(SLAPI_OPERATION_SEARCH as libc::c_int, DSE_FLAG_PREOP as libc::c_int, monitor.dn.as_ptr(),
+                                          LDAP_SCOPE_BASE as libc::c_int, filter, Some(slapi_r_monitor_search_cb));
+        for op in MONITOR_WRITE_OPS.iter() {
+            sys::slapi_config_remove_callback(*op as libc::c_int, DSE_FLAG_PREOP as libc::c_int, monitor.dn.as_ptr(),
+                                              LDAP_SCOPE_BASE as libc::c_int, filter, Some(slapi_r_monitor_readonly_cb));
+        }
+        let pb = Slapi_R_PBlock::new();
+        sys::slapi_delete_internal_set_pb(pb.as_ptr(), monitor.dn.as_ptr(), ptr::null_mut(), ptr::null(),
+                                          monitor.plugin_identity as *mut sys::Slapi_ComponentId, 0);
+        sys::slapi_delete_internal_pb(pb.as_ptr());
+        pb.destroy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_registry_render() {
+        let reg = Slapi_R_Metrics_Registry::new();
+        reg.counter("pre_search_calls").inc();
+        reg.counter("pre_search_calls").add(2);
+        reg.gauge("cache_size").set(-4);
+        let h = reg.histogram("latency");
+        h.observe_micros(75);
+        h.observe_micros(200000);
+
+        let attrs = reg.render();
+        assert!(attrs.contains(&("pre-search-calls".to_string(), "3".to_string())));
+        assert!(attrs.contains(&("cache-size".to_string(), "-4".to_string())));
+        assert!(attrs.contains(&("latency-count".to_string(), "2".to_string())));
+        assert!(attrs.contains(&("latency-le-50".to_string(), "0".to_string())));
+        assert!(attrs.contains(&("latency-le-100".to_string(), "1".to_string())));
+        assert!(attrs.contains(&("latency-le-inf".to_string(), "2".to_string())));
+    }
+
+    // Invalid names are logged.
+    #[cfg(slapi_r_mock)]
+    #[test]
+    fn test_metrics_registry_invalid_names() {
+        let reg = Slapi_R_Metrics_Registry::new();
+        reg.counter("bad\0name").inc();
+        reg.counter("1st_calls").inc();
+        reg.gauge("").set(1);
+        reg.counter("good_calls").inc();
+        assert_eq!(reg.render(), vec![("good-calls".to_string(), "1".to_string())]);
+        assert!(valid_name("pre_search-calls2"));
+        assert!(!valid_name("pre search"));
+    }
+
+    #[cfg(slapi_r_mock)]
+    #[test]
+    fn test_monitor_start() {
+        use super::super::mock;
+
+        let identity = ptr::null();
+        let dn = "cn=test_monitor_start,cn=monitor".to_string();
+        assert!(slapi_r_monitor_start("test_monitor_start", identity).is_ok());
+        assert_eq!(mock::internal_entries(), vec![dn.clone()]);
+        // A monitor entry left from a previous start is served again.
+        assert!(slapi_r_monitor_start("test_monitor_start", identity).is_ok());
+        assert_eq!(mock::internal_entries(), vec![dn]);
+        slapi_r_monitor_stop();
+        assert!(mock::internal_entries().is_empty());
+
+        mock::refuse_internal_add(Some(LDAP_UNWILLING_TO_PERFORM));
+        assert!(matches!(slapi_r_monitor_start("test_monitor_start", identity), Err(PluginOperationError::Unknown)));
+        mock::refuse_internal_add(None);
+        assert!(MONITOR.lock().unwrap().is_none());
+    }
+}
@@ -0,0 +1,74 @@
+/*
+ * BEGIN COPYRIGHT BLOCK
+ * Copyright (C) 2016 Red Hat, Inc.
+ * All rights reserved.
+ *
+ * License: GPL (version 3 or any later version).
+ * See LICENSE for details. 
+ * END COPYRIGHT BLOCK
+ *
+ * Author: William Brown <wibrown@redhat.com>
+ */
+
+/*
+ * The variadic functions of the mock backend. These format the message, and
+ * pass it to the rest of the mock backend in src/mock/mod.rs.
+ */
+
+#include <stdarg.h>
+#include <stdio.h>
+
+#define MOCK_MESSAGE_LEN 4096
+
+extern void slapi_r_mock_log(const char *log, int level, const char *subsystem, const char *message);
+extern void slapi_r_mock_task_log(void *task, int status, const char *message);
+
+int
+slapi_log_error(int loglevel, char *subsystem, char *fmt, ...)
+{
+    char message[MOCK_MESSAGE_LEN];
+    va_list ap;
+
+    va_start(ap, fmt);
+    vsnprintf(message, sizeof(message), fmt, ap);
+    va_end(ap);
+    slapi_r_mock_log("error", loglevel, subsystem, message);
+    return 0;
+}
+
+int
+slapi_log_access(int level, const char *fmt, ...)
+{
+    char message[MOCK_MESSAGE_LEN];
+    va_list ap;
+
+    va_start(ap, fmt);
+    vsnprintf(message, sizeof(message), fmt, ap);
+    va_end(ap);
+    slapi_r_mock_log("access", level, "", message);
+    return 0;
+}
+
+void
+slapi_task_log_status(void *task, char *format, ...)
+{
+    char message[MOCK_MESSAGE_LEN];
+    va_list ap;
+
+    va_start(ap, format);
+    vsnprintf(message, sizeof(message), format, ap);
+    va_end(ap);
+    slapi_r_mock_task_log(task, 1, message);
+}
+
+void
+slapi_task_log_notice(void *task, char *format, ...)
+{
+    char message[MOCK_MESSAGE_LEN];
+    va_list ap;
+
+    va_start(ap, format);
+    vsnprintf(message, sizeof(message), format, ap);
+    va_end(ap);
+    slapi_r_mock_task_log(task, 0, message);
+}
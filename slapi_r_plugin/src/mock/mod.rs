@@ -0,0 +1,1158 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use libc;
+use std::cell::RefCell;
+use std::mem;
+use std::ptr;
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+use std::os::raw::c_ulong;
+use std::os::raw::c_void;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use slapi_r_plugin_sys as sys;
+use super::constants::*;
+use super::entry::Entry;
+use super::error::PluginRegistrationError;
+use super::ldif;
+use super::mods::Modification;
+use super::operation::OperationType;
+use super::pblock::Slapi_R_PBlock;
+use super::plugin::Slapi_R_Plugin_Manager;
+
+// The mock backend is built in place of libslapd when build.rs can not find
+// it. It implements the functions that slapi_r_plugin calls, well enough to
+// run plugin callbacks in cargo test. Pblocks store their slots, entries and
+// operations are held in Rust types, and everything else succeeds and does
+// nothing. The variadic logging functions are in log.c, except under Miri.
+
+// The value of a pblock slot. Directory Server copies ints, longs and times
+// into the slot, and stores everything else as the pointer it was given.
+#[derive(Clone, Copy)]
+enum Slot {
+    Int(c_int),
+    Long(u64),
+    Time(libc::time_t),
+    Ptr(*mut c_void),
+}
+
+struct MockPBlock {
+    slots: BTreeMap<c_int, Slot>,
+    result: Option<(c_int, Option<String>)>,
+    denied: Vec<String>,
+    // Values that were set through this module, and are freed with the pblock.
+    entries: Vec<*mut sys::Slapi_Entry>,
+    operations: Vec<*mut MockOperation>,
+    strings: Vec<CString>,
+    mods: Vec<MockMods>,
+    // The DN that an internal delete is set up to remove.
+    delete_dn: Option<String>,
+}
+
+impl MockPBlock {
+    fn new() -> MockPBlock {
+        MockPBlock {
+            slots: BTreeMap::new(),
+            result: None,
+            denied: Vec::new(),
+            entries: Vec::new(),
+            operations: Vec::new(),
+            strings: Vec::new(),
+            mods: Vec::new(),
+            delete_dn: None,
+        }
+    }
+}
+
+impl Drop for MockPBlock {
+    fn drop(&mut self) {
+        for e in self.entries.drain(..) {
+            unsafe { slapi_entry_free(e) };
+        }
+        // Directory Server frees the copy of the result entry once it is sent.
+        if let Some(&Slot::Ptr(e)) = self.slots.get(&sys::SLAPI_SEARCH_ENTRY_COPY) {
+            unsafe { slapi_entry_free(e as *mut sys::Slapi_Entry) };
+        }
+        for op in self.operations.drain(..) {
+            drop(unsafe { Box::from_raw(op) });
+        }
+    }
+}
+
+unsafe fn mock_pblock<'a>(pb: *mut sys::Slapi_PBlock) -> &'a mut MockPBlock {
+    &mut *(pb as *mut MockPBlock)
+}
+
+/// MockOperation is the operation in the SLAPI_OPERATION slot of a pblock,
+/// as Directory Server would provide it to a plugin callback.
+pub struct MockOperation {
+    op_type: c_ulong,
+    flags: c_int,
+    target_dn: Option<CString>,
+}
+
+impl MockOperation {
+    /// Create an operation of the given type, with no target and no flags.
+    pub fn new(op_type: OperationType) -> MockOperation {
+        let op_type = match op_type {
+            OperationType::Add => SLAPI_OPERATION_ADD,
+            OperationType::Modify => SLAPI_OPERATION_MODIFY,
+            OperationType::Delete => SLAPI_OPERATION_DELETE,
+            OperationType::ModRdn => SLAPI_OPERATION_MODRDN,
+            OperationType::Search => SLAPI_OPERATION_SEARCH,
+            OperationType::Compare => SLAPI_OPERATION_COMPARE,
+            OperationType::Bind => SLAPI_OPERATION_BIND,
+            OperationType::Unbind => SLAPI_OPERATION_UNBIND,
+            OperationType::Abandon => SLAPI_OPERATION_ABANDON,
+            OperationType::Extended => SLAPI_OPERATION_EXTENDED,
+        };
+        MockOperation {
+            op_type: op_type as c_ulong,
+            flags: 0,
+            target_dn: None,
+        }
+    }
+
+    /// Set the DN that the operation targets.
+    pub fn with_target_dn(mut self, dn: &str) -> MockOperation {
+        self.target_dn = CString::new(dn).ok();
+        self
+    }
+
+    /// Set one of the OP_FLAG_* flags, IE OP_FLAG_REPLICATED.
+    pub fn with_flag(mut self, flag: isize) -> MockOperation {
+        self.flags |= flag as c_int;
+        self
+    }
+}
+
+/// Store the operation in the SLAPI_OPERATION slot of the pblock. It is freed
+/// when the pblock is destroyed.
+pub fn set_operation(pb: &Slapi_R_PBlock, op: MockOperation) {
+    let mpb = unsafe { mock_pblock(pb.as_ptr()) };
+    let op = Box::into_raw(Box::new(op));
+    mpb.slots.insert(SLAPI_OPERATION as c_int, Slot::Ptr(op as *mut c_void));
+    mpb.operations.push(op);
+}
+
+/// Store a copy of the entry in the given slot of the pblock, IE
+/// SLAPI_SEARCH_RESULT_ENTRY. It is freed when the pblock is destroyed.
+pub fn set_entry(pb: &Slapi_R_PBlock, pblock_type: isize, entry: &Entry) {
+    let e = match entry.to_slapi_entry() {
+        Some(e) => e.into_raw(),
+        None => return,
+    };
+    let mpb = unsafe { mock_pblock(pb.as_ptr()) };
+    mpb.slots.insert(pblock_type as c_int, Slot::Ptr(e as *mut c_void));
+    mpb.entries.push(e);
+}
+
+/// Store an int in the given slot of the pblock, IE SLAPI_OPERATION_ID.
+pub fn set_int(pb: &Slapi_R_PBlock, pblock_type: isize, value: isize) {
+    let mut value = value as libc::c_int;
+    unsafe { slapi_pblock_set(pb.as_ptr(), pblock_type as c_int, &mut value as *mut c_int as *mut c_void) };
+}
+
+/// Store the id of the connection in the SLAPI_CONN_ID slot of the pblock.
+/// This is a 64 bit value, so set_int can not be used for it.
+pub fn set_conn_id(pb: &Slapi_R_PBlock, conn_id: u64) {
+    let mut value = conn_id;
+    unsafe { slapi_pblock_set(pb.as_ptr(), SLAPI_CONN_ID as c_int, &mut value as *mut u64 as *mut c_void) };
+}
+
+/// Store a copy of the string in the given slot of the pblock, IE
+/// SLAPI_REQUESTOR_DN. It is freed when the pblock is destroyed.
+pub fn set_string(pb: &Slapi_R_PBlock, pblock_type: isize, value: &str) {
+    let value = match CString::new(value) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+    let mpb = unsafe { mock_pblock(pb.as_ptr()) };
+    mpb.slots.insert(pblock_type as c_int, Slot::Ptr(value.as_ptr() as *mut c_void));
+    mpb.strings.push(value);
+}
+
+// The LDAPMod array of a modify operation, and the values it points to. The
+// vectors are not changed once built, so the pointers into them stay valid
+// when this is moved.
+struct MockMods {
+    _types: Vec<CString>,
+    _values: Vec<Vec<u8>>,
+    _bervals: Vec<sys::berval>,
+    _berval_ptrs: Vec<Vec<*mut sys::berval>>,
+    _mods: Vec<sys::LDAPMod>,
+    mod_ptrs: Vec<*mut sys::LDAPMod>,
+}
+
+impl MockMods {
+    fn new(modifications: &[Modification]) -> MockMods {
+        let mut types = Vec::new();
+        let mut values: Vec<Vec<u8>> = Vec::new();
+        for m in modifications.iter() {
+            types.push(CString::new(m.attr.as_str()).unwrap_or_default());
+            values.extend(m.values.iter().cloned());
+        }
+        let mut bervals: Vec<sys::berval> = values.iter_mut()
+            .map(|v| sys::berval { bv_len: v.len() as c_ulong, bv_val: v.as_mut_ptr() as *mut c_char })
+            .collect();
+        let mut berval_ptrs = Vec::new();
+        let mut next = 0;
+        for m in modifications.iter() {
+            let mut ptrs: Vec<*mut sys::berval> = bervals[next..next + m.values.len()].iter_mut()
+                .map(|bv| bv as *mut sys::berval)
+                .collect();
+            ptrs.push(ptr::null_mut());
+            next += m.values.len();
+            berval_ptrs.push(ptrs);
+        }
+        let mut mods: Vec<sys::LDAPMod> = modifications.iter().zip(types.iter()).zip(berval_ptrs.iter_mut())
+            .map(|((m, t), ptrs)| sys::LDAPMod {
+                mod_op: (m.mod_type.as_ds_isize() | LDAP_MOD_BVALUES) as c_int,
+                mod_type: t.as_ptr() as *mut c_char,
+                mod_vals: sys::ldapmod_mod_vals_u { modv_bvals: ptrs.as_mut_ptr() },
+            })
+            .collect();
+        let mut mod_ptrs: Vec<*mut sys::LDAPMod> = mods.iter_mut().map(|m| m as *mut sys::LDAPMod).collect();
+        mod_ptrs.push(ptr::null_mut());
+        MockMods {
+            _types: types,
+            _values: values,
+            _bervals: bervals,
+            _berval_ptrs: berval_ptrs,
+            _mods: mods,
+            mod_ptrs,
+        }
+    }
+}
+
+/// Store the modifications in the SLAPI_MODIFY_MODS slot of the pblock, as
+/// the LDAPMod array of a modify operation. It is freed when the pblock is
+/// destroyed.
+pub fn set_mods(pb: &Slapi_R_PBlock, modifications: &[Modification]) {
+    let mut mods = MockMods::new(modifications);
+    let mpb = unsafe { mock_pblock(pb.as_ptr()) };
+    mpb.slots.insert(SLAPI_MODIFY_MODS as c_int, Slot::Ptr(mods.mod_ptrs.as_mut_ptr() as *mut c_void));
+    mpb.mods.push(mods);
+}
+
+/// Deny the identity of the operation access to the named attribute, for
+/// every access right. By default all access is allowed.
+pub fn deny_access(pb: &Slapi_R_PBlock, attr: &str) {
+    unsafe { mock_pblock(pb.as_ptr()) }.denied.push(attr.to_lowercase());
+}
+
+/// Returns the result code and message of the last call to
+/// slapi_send_ldap_result on the pblock, if there was one.
+pub fn last_result(pb: &Slapi_R_PBlock) -> Option<(isize, Option<String>)> {
+    unsafe { mock_pblock(pb.as_ptr()) }.result.clone().map(|(code, text)| (code as isize, text))
+}
+
+/// Returns the lines written to the access log on this thread since the last
+/// call, without the trailing newline.
+pub fn take_access_log() -> Vec<String> {
+    ACCESS_LOG.with(|a| a.borrow_mut().drain(..).collect())
+}
+
+/// Set the version that the server reports on this thread, IE 1.3.5.1. By
+/// default the server does not report a version.
+pub fn set_server_version(version: Option<&str>) {
+    SERVER_VERSION.with(|v| *v.borrow_mut() = version.map(|v| v.to_string()));
+}
+
+/// Returns the records written to the audit log on this thread since the
+/// last call.
+pub fn take_audit_log() -> Vec<String> {
+    AUDIT_LOG.with(|a| a.borrow_mut().drain(..).collect())
+}
+
+/// Register the plugin into a new pblock, as Directory Server does when it
+/// loads the plugin. The callbacks of the plugin can then be called with
+/// call_plugin_fn.
+///
+/// # Failures
+/// The error of Slapi_R_Plugin_Manager::register is returned.
+pub fn register(manager: Slapi_R_Plugin_Manager) -> Result<Slapi_R_PBlock, PluginRegistrationError> {
+    let pb = Slapi_R_PBlock::new();
+    let ptr = pb.as_ptr() as *const c_void;
+    match manager.register(pb) {
+        Ok(_) => Ok(Slapi_R_PBlock::build(ptr)),
+        Err(e) => {
+            Slapi_R_PBlock::build(ptr).destroy();
+            Err(e)
+        }
+    }
+}
+
+thread_local! {
+    // The type, name and pblock of each sub-plugin registered on this thread.
+    static SUB_PLUGINS: RefCell<Vec<(String, String, *mut sys::Slapi_PBlock)>> = const { RefCell::new(Vec::new()) };
+    // The name of a sub-plugin that slapi_register_plugin refuses.
+    static REFUSED_SUB_PLUGIN: RefCell<Option<String>> = const { RefCell::new(None) };
+    // The lines written to the access log on this thread.
+    static ACCESS_LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    // The version that the server reports on this thread, IE 1.3.5.1.
+    static SERVER_VERSION: RefCell<Option<String>> = const { RefCell::new(None) };
+    // The records written to the audit log on this thread.
+    static AUDIT_LOG: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    // The DNs of the entries added by internal operations on this thread.
+    static INTERNAL_ENTRIES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    // The result that slapi_add_internal_pb gives instead of adding the entry.
+    static REFUSED_INTERNAL_ADD: RefCell<Option<c_int>> = const { RefCell::new(None) };
+}
+
+/// Returns the type, name and pblock of each sub-plugin registered on this
+/// thread since the last call, as Directory Server would hold them. The
+/// caller must destroy the pblocks.
+pub fn take_sub_plugins() -> Vec<(String, String, Slapi_R_PBlock)> {
+    SUB_PLUGINS.with(|s| s.borrow_mut().drain(..)
+        .map(|(kind, name, pb)| (kind, name, Slapi_R_PBlock::build(pb as *const c_void)))
+        .collect())
+}
+
+/// Make slapi_register_plugin refuse the named sub-plugin on this thread,
+/// without calling its init function. None accepts every sub-plugin again.
+pub fn refuse_sub_plugin(name: Option<&str>) {
+    REFUSED_SUB_PLUGIN.with(|r| *r.borrow_mut() = name.map(|n| n.to_string()));
+}
+
+/// Returns the DNs of the entries that internal operations on this thread
+/// have added, and not yet deleted.
+pub fn internal_entries() -> Vec<String> {
+    INTERNAL_ENTRIES.with(|e| e.borrow().clone())
+}
+
+/// Make internal adds on this thread fail with the given LDAP result, IE
+/// LDAP_UNWILLING_TO_PERFORM, or succeed again with None.
+pub fn refuse_internal_add(result: Option<isize>) {
+    REFUSED_INTERNAL_ADD.with(|r| *r.borrow_mut() = result.map(|r| r as c_int));
+}
+
+/// Call the plugin function registered in the given slot of the pblock, IE
+/// SLAPI_PLUGIN_PRE_SEARCH_FN, as Directory Server would, and return its
+/// result. None is returned if no function is registered.
+pub fn call_plugin_fn(pb: &Slapi_R_PBlock, pblock_type: isize) -> Option<isize> {
+    let f = match unsafe { mock_pblock(pb.as_ptr()) }.slots.get(&(pblock_type as c_int)) {
+        Some(&Slot::Ptr(f)) if !f.is_null() => f,
+        _ => return None,
+    };
+    // Only _set_pb_fn_ptr stores functions into the plugin slots.
+    let f: extern "C" fn(*const c_void) -> c_int = unsafe { mem::transmute(f) };
+    Some(f(pb.as_ptr() as *const c_void) as isize)
+}
+
+// pblock.c
+
+#[no_mangle]
+extern "C" fn slapi_pblock_new() -> *mut sys::Slapi_PBlock {
+    Box::into_raw(Box::new(MockPBlock::new())) as *mut sys::Slapi_PBlock
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_pblock_init(pb: *mut sys::Slapi_PBlock) {
+    *mock_pblock(pb) = MockPBlock::new();
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_pblock_destroy(pb: *mut sys::Slapi_PBlock) {
+    if !pb.is_null() {
+        drop(Box::from_raw(pb as *mut MockPBlock));
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_pblock_get(pb: *mut sys::Slapi_PBlock, arg: c_int, value: *mut c_void) -> c_int {
+    if value.is_null() {
+        return -1;
+    }
+    let slot = mock_pblock(pb).slots.get(&arg).cloned();
+    match arg {
+        sys::SLAPI_CONN_ID => *(value as *mut u64) = match slot { Some(Slot::Long(v)) => v, _ => 0 },
+        sys::SLAPI_OPINITIATED_TIME => *(value as *mut libc::time_t) = match slot { Some(Slot::Time(v)) => v, _ => 0 },
+        sys::SLAPI_PLUGIN_TYPE |
+        sys::SLAPI_PLUGIN_OPRETURN |
+        sys::SLAPI_PLUGIN_INTOP_RESULT |
+        sys::SLAPI_IS_REPLICATED_OPERATION |
+        sys::SLAPI_OPERATION_ID => *(value as *mut c_int) = match slot { Some(Slot::Int(v)) => v, _ => 0 },
+        _ => *(value as *mut *mut c_void) = match slot { Some(Slot::Ptr(v)) => v, _ => ptr::null_mut() },
+    }
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_pblock_set(pb: *mut sys::Slapi_PBlock, arg: c_int, value: *mut c_void) -> c_int {
+    let slot = match arg {
+        _ if value.is_null() => Slot::Ptr(value),
+        sys::SLAPI_CONN_ID => Slot::Long(*(value as *const u64)),
+        sys::SLAPI_OPINITIATED_TIME => Slot::Time(*(value as *const libc::time_t)),
+        sys::SLAPI_PLUGIN_TYPE |
+        sys::SLAPI_PLUGIN_OPRETURN |
+        sys::SLAPI_PLUGIN_INTOP_RESULT |
+        sys::SLAPI_IS_REPLICATED_OPERATION |
+        sys::SLAPI_OPERATION_ID => Slot::Int(*(value as *const c_int)),
+        _ => Slot::Ptr(value),
+    };
+    mock_pblock(pb).slots.insert(arg, slot);
+    0
+}
+
+// result.c
+
+#[no_mangle]
+unsafe extern "C" fn slapi_send_ldap_result(pb: *mut sys::Slapi_PBlock, err: c_int, _matched: *mut c_char,
+                                                text: *mut c_char, _nentries: c_int, _urls: *mut *mut sys::berval) {
+    let text = if text.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(text).to_string_lossy().into_owned())
+    };
+    mock_pblock(pb).result = Some((err, text));
+}
+
+// acl
+
+#[no_mangle]
+unsafe extern "C" fn slapi_access_allowed(pb: *mut sys::Slapi_PBlock, _e: *mut sys::Slapi_Entry, attr: *mut c_char,
+                                              _val: *mut sys::berval, _access: c_int) -> c_int {
+    let attr = CStr::from_ptr(attr).to_string_lossy().to_lowercase();
+    if mock_pblock(pb).denied.contains(&attr) {
+        sys::LDAP_INSUFFICIENT_ACCESS
+    } else {
+        sys::LDAP_SUCCESS
+    }
+}
+
+#[no_mangle]
+extern "C" fn slapi_acl_check_mods(_pb: *mut sys::Slapi_PBlock, _e: *mut sys::Slapi_Entry, _mods: *mut *mut sys::LDAPMod,
+                                       _errbuf: *mut *mut c_char) -> c_int {
+    sys::LDAP_SUCCESS
+}
+
+// ch_malloc.c
+
+#[no_mangle]
+unsafe extern "C" fn slapi_ch_free_string(s: *mut *mut c_char) {
+    if !s.is_null() && !(*s).is_null() {
+        libc::free(*s as *mut c_void);
+        *s = ptr::null_mut();
+    }
+}
+
+// Copy the bytes into memory that slapi_ch_free_string can free.
+unsafe fn ch_strdup(value: &[u8]) -> *mut c_char {
+    let s = libc::malloc(value.len() + 1) as *mut u8;
+    if !s.is_null() {
+        ptr::copy_nonoverlapping(value.as_ptr(), s, value.len());
+        *s.add(value.len()) = 0;
+    }
+    s as *mut c_char
+}
+
+// log.c
+
+/// Called by log.c with each formatted message.
+#[no_mangle]
+unsafe extern "C" fn slapi_r_mock_log(log: *const c_char, level: c_int, subsystem: *const c_char, message: *const c_char) {
+    let log = CStr::from_ptr(log).to_string_lossy();
+    let message = CStr::from_ptr(message).to_string_lossy();
+    eprintln!("[mock {} log] {} {} - {}", log, level, CStr::from_ptr(subsystem).to_string_lossy(), message.trim_end());
+    if log == "access" {
+        ACCESS_LOG.with(|a| a.borrow_mut().push(message.trim_end().to_string()));
+    }
+}
+
+static LOG_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Set if every log level is enabled, which is the default, or none are. With
+/// logging disabled, benchmarks measure a callback without the cost of its
+/// log messages.
+pub fn set_log_enabled(enabled: bool) {
+    LOG_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+#[no_mangle]
+extern "C" fn slapi_is_loglevel_set(_loglevel: c_int) -> c_int {
+    LOG_ENABLED.load(Ordering::Relaxed) as c_int
+}
+
+// Only looked up, to detect backend transactions.
+#[no_mangle]
+extern "C" fn slapi_back_transaction_begin(_pb: *mut sys::Slapi_PBlock) -> c_int {
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapd_log_audit_proc(buffer: *mut c_char, buf_len: c_int) -> c_int {
+    let buffer = ::std::slice::from_raw_parts(buffer as *const u8, buf_len as usize);
+    AUDIT_LOG.with(|a| a.borrow_mut().push(String::from_utf8_lossy(buffer).into_owned()));
+    0
+}
+
+// The 1.3.5 version, where a sourcelog of 0x4 is the audit log rather than the
+// audit fail log.
+#[no_mangle]
+unsafe extern "C" fn slapd_log_audit(buffer: *mut c_char, buf_len: c_int, sourcelog: c_int) -> c_int {
+    if sourcelog != 0x4 {
+        return -1;
+    }
+    slapd_log_audit_proc(buffer, buf_len)
+}
+
+// entry.c, attr.c and value.c
+
+struct MockValue {
+    data: Vec<u8>,
+    bv: sys::berval,
+}
+
+struct MockAttr {
+    name: CString,
+    values: Vec<MockValue>,
+}
+
+struct MockEntry {
+    dn: CString,
+    attrs: Vec<MockAttr>,
+}
+
+impl MockAttr {
+    fn new(name: &str, values: &[Vec<u8>]) -> MockAttr {
+        let values = values.iter().map(|v| {
+            let mut data = v.clone();
+            // The data is not changed after this, so the pointer remains valid
+            // when the value is moved.
+            let bv = sys::berval { bv_len: data.len() as c_ulong, bv_val: data.as_mut_ptr() as *mut c_char };
+            MockValue { data, bv }
+        }).collect();
+        MockAttr {
+            name: CString::new(name).unwrap_or_default(),
+            values,
+        }
+    }
+}
+
+unsafe fn mock_entry<'a>(e: *const sys::Slapi_Entry) -> &'a mut MockEntry {
+    &mut *(e as *mut MockEntry)
+}
+
+fn attr_index(entry: &MockEntry, name: &str) -> Option<usize> {
+    entry.attrs.iter().position(|a| a.name.to_string_lossy().eq_ignore_ascii_case(name))
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_first_attr(e: *const sys::Slapi_Entry, attr: *mut *mut sys::Slapi_Attr) -> c_int {
+    match mock_entry(e).attrs.first_mut() {
+        Some(a) => {
+            *attr = a as *mut MockAttr as *mut sys::Slapi_Attr;
+            0
+        }
+        None => {
+            *attr = ptr::null_mut();
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_next_attr(e: *const sys::Slapi_Entry, prevattr: *mut sys::Slapi_Attr,
+                                               attr: *mut *mut sys::Slapi_Attr) -> c_int {
+    let entry = mock_entry(e);
+    let prev = prevattr as *const MockAttr;
+    let next = entry.attrs.iter().position(|a| ptr::eq(a, prev)).map(|i| i + 1);
+    match next.and_then(|i| entry.attrs.get_mut(i)) {
+        Some(a) => {
+            *attr = a as *mut MockAttr as *mut sys::Slapi_Attr;
+            0
+        }
+        None => {
+            *attr = ptr::null_mut();
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_attr_delete(e: *mut sys::Slapi_Entry, type_: *const c_char) -> c_int {
+    let entry = mock_entry(e);
+    match attr_index(entry, &CStr::from_ptr(type_).to_string_lossy()) {
+        Some(i) => {
+            entry.attrs.remove(i);
+            0
+        }
+        None => 1,
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_attr_get_charptr(e: *const sys::Slapi_Entry, type_: *const c_char) -> *mut c_char {
+    let entry = mock_entry(e);
+    match attr_index(entry, &CStr::from_ptr(type_).to_string_lossy()) {
+        Some(i) => match entry.attrs[i].values.first() {
+            Some(v) => ch_strdup(&v.data),
+            None => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_attr_set_charptr(e: *mut sys::Slapi_Entry, type_: *const c_char, value: *const c_char) {
+    let entry = mock_entry(e);
+    let name = CStr::from_ptr(type_).to_string_lossy().into_owned();
+    let index = attr_index(entry, &name);
+    if value.is_null() {
+        if let Some(i) = index {
+            entry.attrs.remove(i);
+        }
+        return;
+    }
+    let attr = MockAttr::new(&name, &[CStr::from_ptr(value).to_bytes().to_vec()]);
+    match index {
+        Some(i) => entry.attrs[i] = attr,
+        None => entry.attrs.push(attr),
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_get_dn_const(e: *const sys::Slapi_Entry) -> *const c_char {
+    mock_entry(e).dn.as_ptr()
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_free(e: *mut sys::Slapi_Entry) {
+    if !e.is_null() {
+        drop(Box::from_raw(e as *mut MockEntry));
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_entry_dup(e: *const sys::Slapi_Entry) -> *mut sys::Slapi_Entry {
+    let entry = mock_entry(e);
+    let attrs = entry.attrs.iter().map(|a| {
+        let values: Vec<Vec<u8>> = a.values.iter().map(|v| v.data.clone()).collect();
+        MockAttr::new(&a.name.to_string_lossy(), &values)
+    }).collect();
+    Box::into_raw(Box::new(MockEntry {
+        dn: entry.dn.clone(),
+        attrs,
+    })) as *mut sys::Slapi_Entry
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_str2entry(s: *mut c_char, _flags: c_int) -> *mut sys::Slapi_Entry {
+    let records = match ldif::parse_str(&CStr::from_ptr(s).to_string_lossy()) {
+        Ok(r) => r,
+        Err(_) => return ptr::null_mut(),
+    };
+    let entry = match records.first().and_then(Entry::from_ldif_record) {
+        Some(e) => e,
+        None => return ptr::null_mut(),
+    };
+    let attrs = entry.get_attribute_names().iter()
+        .map(|name| MockAttr::new(name, entry.get_values(name).unwrap_or(&[])))
+        .collect();
+    Box::into_raw(Box::new(MockEntry {
+        dn: CString::new(entry.get_dn()).unwrap_or_default(),
+        attrs,
+    })) as *mut sys::Slapi_Entry
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_attr_get_type(attr: *const sys::Slapi_Attr, type_: *mut *mut c_char) -> c_int {
+    *type_ = (*(attr as *const MockAttr)).name.as_ptr() as *mut c_char;
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_attr_first_value(a: *mut sys::Slapi_Attr, v: *mut *mut sys::Slapi_Value) -> c_int {
+    slapi_attr_next_value(a, -1, v)
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_attr_next_value(a: *mut sys::Slapi_Attr, hint: c_int, v: *mut *mut sys::Slapi_Value) -> c_int {
+    let attr = &mut *(a as *mut MockAttr);
+    let next = (hint + 1) as usize;
+    match attr.values.get_mut(next) {
+        Some(value) => {
+            *v = value as *mut MockValue as *mut sys::Slapi_Value;
+            next as c_int
+        }
+        None => {
+            *v = ptr::null_mut();
+            -1
+        }
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_value_get_berval(value: *const sys::Slapi_Value) -> *const sys::berval {
+    &(*(value as *const MockValue)).bv
+}
+
+// operation.c and dn.c
+
+#[no_mangle]
+unsafe extern "C" fn slapi_op_get_type(op: *mut sys::Slapi_Operation) -> c_ulong {
+    (*(op as *const MockOperation)).op_type
+}
+
+#[no_mangle]
+unsafe extern "C" fn operation_is_flag_set(op: *mut sys::Slapi_Operation, flag: c_int) -> c_int {
+    (*(op as *const MockOperation)).flags & flag
+}
+
+// The target of a mock operation is its DN, as a CString.
+#[no_mangle]
+unsafe extern "C" fn operation_get_target_spec(op: *mut sys::Slapi_Operation) -> *mut sys::Slapi_DN {
+    match (*(op as *const MockOperation)).target_dn {
+        Some(ref dn) => dn as *const CString as *mut sys::Slapi_DN,
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_sdn_get_dn(sdn: *const sys::Slapi_DN) -> *const c_char {
+    (*(sdn as *const CString)).as_ptr()
+}
+
+// dse.c
+
+#[no_mangle]
+extern "C" fn slapi_config_register_callback(_operation: c_int, _flags: c_int, _base: *const c_char, _scope: c_int,
+                                                 _filter: *const c_char, _fn: sys::dseCallbackFn, _fn_arg: *mut c_void) -> c_int {
+    1
+}
+
+#[no_mangle]
+extern "C" fn slapi_config_remove_callback(_operation: c_int, _flags: c_int, _base: *const c_char, _scope: c_int,
+                                               _filter: *const c_char, _fn: sys::dseCallbackFn) -> c_int {
+    1
+}
+
+// plugin_internal_op.c
+
+#[no_mangle]
+unsafe extern "C" fn slapi_add_entry_internal_set_pb(pb: *mut sys::Slapi_PBlock, e: *mut sys::Slapi_Entry,
+                                                         _controls: *mut *mut sys::LDAPControl,
+                                                         _plugin_identity: *mut sys::Slapi_ComponentId,
+                                                         _operation_flags: c_int) {
+    // The add consumes the entry.
+    mock_pblock(pb).entries.push(e);
+}
+
+// The result of an internal operation is in SLAPI_PLUGIN_INTOP_RESULT, and
+// the return value only reports if the pblock was usable.
+#[no_mangle]
+unsafe extern "C" fn slapi_add_internal_pb(pb: *mut sys::Slapi_PBlock) -> c_int {
+    let mpb = mock_pblock(pb);
+    let dn = match mpb.entries.last() {
+        Some(&e) => mock_entry(e).dn.to_string_lossy().into_owned(),
+        None => return -1,
+    };
+    let result = match REFUSED_INTERNAL_ADD.with(|r| *r.borrow()) {
+        Some(result) => result,
+        None => INTERNAL_ENTRIES.with(|entries| {
+            let mut entries = entries.borrow_mut();
+            if entries.contains(&dn) {
+                sys::LDAP_ALREADY_EXISTS
+            } else {
+                entries.push(dn);
+                sys::LDAP_SUCCESS
+            }
+        }),
+    };
+    mpb.slots.insert(sys::SLAPI_PLUGIN_INTOP_RESULT, Slot::Int(result));
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_delete_internal_set_pb(pb: *mut sys::Slapi_PBlock, dn: *const c_char, _controls: *mut *mut sys::LDAPControl,
+                                                      _uniqueid: *const c_char, _plugin_identity: *mut sys::Slapi_ComponentId,
+                                                      _operation_flags: c_int) {
+    mock_pblock(pb).delete_dn = Some(CStr::from_ptr(dn).to_string_lossy().into_owned());
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_delete_internal_pb(pb: *mut sys::Slapi_PBlock) -> c_int {
+    let mpb = mock_pblock(pb);
+    let dn = match mpb.delete_dn.take() {
+        Some(dn) => dn,
+        None => return -1,
+    };
+    let result = INTERNAL_ENTRIES.with(|entries| {
+        let mut entries = entries.borrow_mut();
+        let before = entries.len();
+        entries.retain(|e| *e != dn);
+        if entries.len() < before { sys::LDAP_SUCCESS } else { sys::LDAP_NO_SUCH_OBJECT }
+    });
+    mpb.slots.insert(sys::SLAPI_PLUGIN_INTOP_RESULT, Slot::Int(result));
+    0
+}
+
+// eventq.c. Events are never run, MockEventQueue should be used to test
+// scheduled work.
+
+static NEXT_EQ_CONTEXT: AtomicUsize = AtomicUsize::new(1);
+
+#[no_mangle]
+extern "C" fn slapi_eq_once(_fn: sys::slapi_eq_fn_t, _arg: *mut c_void, _when: libc::time_t) -> sys::Slapi_Eq_Context {
+    NEXT_EQ_CONTEXT.fetch_add(1, Ordering::SeqCst) as sys::Slapi_Eq_Context
+}
+
+#[no_mangle]
+extern "C" fn slapi_eq_repeat(_fn: sys::slapi_eq_fn_t, _arg: *mut c_void, _when: libc::time_t,
+                                  _interval: c_ulong) -> sys::Slapi_Eq_Context {
+    NEXT_EQ_CONTEXT.fetch_add(1, Ordering::SeqCst) as sys::Slapi_Eq_Context
+}
+
+#[no_mangle]
+extern "C" fn slapi_eq_cancel(ctx: sys::Slapi_Eq_Context) -> c_int {
+    if ctx.is_null() { 0 } else { 1 }
+}
+
+#[no_mangle]
+extern "C" fn slapi_current_time() -> libc::time_t {
+    // Not libc::time, which Miri does not provide.
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as libc::time_t).unwrap_or(0)
+}
+
+// task.c
+
+struct MockTask {
+    dn: String,
+    data: *mut c_void,
+    destructor_fn: sys::TaskCallbackFn,
+    refcount: c_int,
+    result: Option<c_int>,
+}
+
+// The names of the registered task handlers, and the DN and result of each
+// task that has been destroyed.
+static TASK_HANDLERS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static DESTROYED_TASKS: Mutex<Vec<(String, c_int)>> = Mutex::new(Vec::new());
+
+/// Returns the names of the task handlers that are registered.
+pub fn task_handlers() -> Vec<String> {
+    TASK_HANDLERS.lock().unwrap().clone()
+}
+
+/// Wait up to five seconds for the task of the DN to finish and be released
+/// by every thread, then return its nsTaskExitCode.
+pub fn wait_task_destroyed(dn: &str) -> Option<isize> {
+    for _ in 0..500 {
+        if let Some(&(_, rc)) = DESTROYED_TASKS.lock().unwrap().iter().find(|t| t.0 == dn) {
+            return Some(rc as isize);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    None
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_task_register_handler(name: *const c_char, _func: sys::dseCallbackFn) -> c_int {
+    TASK_HANDLERS.lock().unwrap().push(CStr::from_ptr(name).to_string_lossy().into_owned());
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_plugin_task_unregister_handler(name: *const c_char, _func: sys::dseCallbackFn) -> c_int {
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    TASK_HANDLERS.lock().unwrap().retain(|n| !n.eq_ignore_ascii_case(&name));
+    0
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_new_task(dn: *const c_char) -> *mut sys::Slapi_Task {
+    Box::into_raw(Box::new(MockTask {
+        dn: CStr::from_ptr(dn).to_string_lossy().into_owned(),
+        data: ptr::null_mut(),
+        destructor_fn: None,
+        refcount: 0,
+        result: None,
+    })) as *mut sys::Slapi_Task
+}
+
+unsafe fn mock_task<'a>(task: *mut sys::Slapi_Task) -> &'a mut MockTask {
+    &mut *(task as *mut MockTask)
+}
+
+// The task is destroyed once it has finished and no thread holds it.
+unsafe fn task_release(task: *mut sys::Slapi_Task) {
+    let t = mock_task(task);
+    if let (Some(rc), true) = (t.result, t.refcount <= 0) {
+        if let Some(f) = t.destructor_fn {
+            f(task);
+        }
+        let t = Box::from_raw(task as *mut MockTask);
+        DESTROYED_TASKS.lock().unwrap().push((t.dn, rc));
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_task_set_data(task: *mut sys::Slapi_Task, data: *mut c_void) {
+    mock_task(task).data = data;
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_task_get_data(task: *mut sys::Slapi_Task) -> *mut c_void {
+    mock_task(task).data
+}
+
+// Tasks are never cancelled, so the cancel function is not kept.
+#[no_mangle]
+extern "C" fn slapi_task_set_cancel_fn(_task: *mut sys::Slapi_Task, _func: sys::TaskCallbackFn) {
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_task_set_destructor_fn(task: *mut sys::Slapi_Task, func: sys::TaskCallbackFn) {
+    mock_task(task).destructor_fn = func;
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_task_inc_refcount(task: *mut sys::Slapi_Task) {
+    mock_task(task).refcount += 1;
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_task_dec_refcount(task: *mut sys::Slapi_Task) {
+    mock_task(task).refcount -= 1;
+    task_release(task);
+}
+
+#[no_mangle]
+extern "C" fn slapi_task_begin(_task: *mut sys::Slapi_Task, _total_work: c_int) {
+}
+
+#[no_mangle]
+extern "C" fn slapi_task_inc_progress(_task: *mut sys::Slapi_Task) {
+}
+
+#[no_mangle]
+unsafe extern "C" fn slapi_task_finish(task: *mut sys::Slapi_Task, rc: c_int) {
+    eprintln!("[mock task] finished - {}", rc);
+    mock_task(task).result = Some(rc);
+    task_release(task);
+}
+
+/// Called by log.c with each formatted task status or notice.
+#[no_mangle]
+unsafe extern "C" fn slapi_r_mock_task_log(_task: *mut c_void, status: c_int, message: *const c_char) {
+    let kind = if status != 0 { "status" } else { "notice" };
+    eprintln!("[mock task] {} - {}", kind, CStr::from_ptr(message).to_string_lossy().trim_end());
+}
+
+// Miri can not run log.c, so these replace it. slapi_r_plugin always passes
+// the message as the argument of the format "%s", or "%.*s" for the error
+// log.
+
+#[cfg(miri)]
+#[no_mangle]
+unsafe extern "C" fn slapi_log_error(loglevel: c_int, subsystem: *mut c_char, _fmt: *mut c_char, mut args: ...) -> c_int {
+    let len = args.next_arg::<c_int>() as usize;
+    let message = ::std::slice::from_raw_parts(args.next_arg::<*const u8>(), len);
+    let message = CString::new(message.split(|&c| c == 0).next().unwrap_or(&[])).unwrap();
+    slapi_r_mock_log(b"error\0".as_ptr() as *const c_char, loglevel, subsystem, message.as_ptr());
+    0
+}
+
+#[cfg(miri)]
+#[no_mangle]
+unsafe extern "C" fn slapi_log_access(level: c_int, _fmt: *const c_char, mut args: ...) -> c_int {
+    slapi_r_mock_log(b"access\0".as_ptr() as *const c_char, level, b"\0".as_ptr() as *const c_char,
+                     args.next_arg::<*const c_char>());
+    0
+}
+
+#[cfg(miri)]
+#[no_mangle]
+unsafe extern "C" fn slapi_task_log_status(task: *mut sys::Slapi_Task, _format: *mut c_char, mut args: ...) {
+    slapi_r_mock_task_log(task as *mut c_void, 1, args.next_arg::<*const c_char>());
+}
+
+#[cfg(miri)]
+#[no_mangle]
+unsafe extern "C" fn slapi_task_log_notice(task: *mut sys::Slapi_Task, _format: *mut c_char, mut args: ...) {
+    slapi_r_mock_task_log(task as *mut c_void, 0, args.next_arg::<*const c_char>());
+}
+
+// plugin.c
+
+#[no_mangle]
+unsafe extern "C" fn slapi_register_plugin(plugintype: *const c_char, _enabled: c_int, _initsymbol: *const c_char,
+                                               initfunc: sys::slapi_plugin_init_fnptr, name: *const c_char,
+                                               _argv: *mut *mut c_char, _group_identity: *mut c_void) -> c_int {
+    let kind = CStr::from_ptr(plugintype).to_string_lossy().into_owned();
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    if REFUSED_SUB_PLUGIN.with(|r| r.borrow().as_ref() == Some(&name)) {
+        return -1;
+    }
+    let initfunc = match initfunc {
+        Some(f) => f,
+        None => return -1,
+    };
+    let pb = slapi_pblock_new();
+    let rc = initfunc(pb);
+    if rc != 0 {
+        slapi_pblock_destroy(pb);
+        return rc;
+    }
+    SUB_PLUGINS.with(|s| s.borrow_mut().push((kind, name, pb)));
+    0
+}
+
+// Not part of slapi-plugin.h. The version is set with set_server_version.
+#[no_mangle]
+unsafe extern "C" fn slapd_get_version_value() -> *mut c_char {
+    match SERVER_VERSION.with(|v| v.borrow().clone()) {
+        Some(version) => ch_strdup(version.as_bytes()),
+        None => ptr::null_mut(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::entry::Slapi_R_Entry;
+    use super::super::error::PluginOperationError;
+    use super::super::pblock::Slapi_PBlock_V3;
+
+    #[test]
+    fn test_mock_pblock_operation() {
+        let pb = Slapi_R_PBlock::new();
+        set_operation(&pb, MockOperation::new(OperationType::Add)
+                      .with_target_dn("uid=a,dc=example,dc=com")
+                      .with_flag(OP_FLAG_REPLICATED));
+        set_int(&pb, SLAPI_OPERATION_ID, 7);
+        set_conn_id(&pb, u64::MAX);
+        {
+            let op = pb.get_operation().unwrap();
+            assert_eq!(op.conn_id(), u64::MAX);
+            assert_eq!(op.op_type(), Some(OperationType::Add));
+            assert_eq!(op.target_dn(), Some("uid=a,dc=example,dc=com".to_string()));
+            assert!(op.is_replicated());
+            assert_eq!(op.op_id(), 7);
+        }
+        pb.send_ldap_result(PluginOperationError::UnwillingToPerform, "no");
+        assert_eq!(last_result(&pb), Some((LDAP_UNWILLING_TO_PERFORM, Some("no".to_string()))));
+        pb.destroy();
+    }
+
+    #[test]
+    fn test_mock_nul_bytes() {
+        use super::super::acl::Access;
+
+        let pb = Slapi_R_PBlock::new();
+        let e = Entry::new("uid=a,dc=example,dc=com").with_value("cn", "a");
+        set_entry(&pb, SLAPI_SEARCH_RESULT_ENTRY, &e);
+        {
+            let entry: Slapi_R_Entry = pb.get_search_result_entry().unwrap();
+            assert!(pb.access_allowed(&entry, "cn", Access::Read));
+            assert!(!pb.access_allowed(&entry, "c\0n", Access::Read));
+        }
+        pb.send_ldap_result(PluginOperationError::UnwillingToPerform, "n\0o");
+        assert_eq!(last_result(&pb), Some((LDAP_UNWILLING_TO_PERFORM, Some("no".to_string()))));
+        pb.destroy();
+    }
+
+    #[test]
+    fn test_mock_operation_flags() {
+        let pb = Slapi_R_PBlock::new();
+        set_operation(&pb, MockOperation::new(OperationType::Search)
+                      .with_flag(OP_FLAG_PAGED_RESULTS)
+                      .with_flag(OP_FLAG_REVERSE_CANDIDATE_ORDER)
+                      .with_flag(OP_FLAG_NEVER_CACHE)
+                      .with_flag(OP_FLAG_NOOP));
+        {
+            let op = pb.get_operation().unwrap();
+            assert!(op.is_paged_results());
+            assert!(op.is_reverse_candidate_order());
+            assert!(op.is_never_cache());
+            assert!(op.is_noop());
+            assert!(!op.is_server_side_sorting());
+            assert!(!op.is_tombstone_fixup());
+            assert!(!op.is_bulk_import());
+            assert!(!op.is_replicated());
+        }
+        set_operation(&pb, MockOperation::new(OperationType::Add)
+                      .with_flag(OP_FLAG_SERVER_SIDE_SORTING)
+                      .with_flag(OP_FLAG_TOMBSTONE_FIXUP)
+                      .with_flag(OP_FLAG_BULK_IMPORT));
+        {
+            let op = pb.get_operation().unwrap();
+            assert!(op.is_server_side_sorting());
+            assert!(op.is_tombstone_fixup());
+            assert!(op.is_bulk_import());
+            assert!(!op.is_paged_results());
+            assert!(!op.is_reverse_candidate_order());
+            assert!(!op.is_never_cache());
+            assert!(!op.is_noop());
+        }
+        pb.destroy();
+    }
+
+    #[test]
+    fn test_mock_entry() {
+        let pb = Slapi_R_PBlock::new();
+        let e = Entry::new("uid=a,dc=example,dc=com")
+            .with_value("cn", "a")
+            .with_value("userPassword", "secret");
+        set_entry(&pb, SLAPI_SEARCH_RESULT_ENTRY, &e);
+        deny_access(&pb, "userpassword");
+        {
+            let entry: Slapi_R_Entry = pb.get_search_result_entry().unwrap();
+            assert_eq!(entry.to_entry(), e);
+        }
+        pb.redact_entry();
+        {
+            // The result entry may be shared with the entry cache, so only
+            // the copy is redacted.
+            let entry: Slapi_R_Entry = pb.get_search_result_entry().unwrap();
+            assert_eq!(entry.to_entry(), e);
+            let copy = pb.get_search_entry_copy().unwrap();
+            assert_eq!(copy.get_attribute_names(), vec!["cn".to_string()]);
+            assert_eq!(copy.get_attribute_value("CN"), Some("a".to_string()));
+            assert_eq!(copy.get_dn(), e.get_dn());
+        }
+        // A second redaction changes the copy, rather than copying again.
+        deny_access(&pb, "cn");
+        let copy = pb.get_search_entry_copy().unwrap().as_ptr();
+        pb.redact_entry();
+        {
+            let entry = pb.get_search_entry_copy().unwrap();
+            assert_eq!(entry.as_ptr(), copy);
+            assert!(entry.get_attribute_names().is_empty());
+            assert_eq!(pb.get_search_result_entry().unwrap().to_entry(), e);
+        }
+        pb.destroy();
+    }
+
+    #[test]
+    fn test_mock_entry_nothing_to_redact() {
+        let pb = Slapi_R_PBlock::new();
+        let e = Entry::new("uid=a,dc=example,dc=com").with_value("cn", "a");
+        set_entry(&pb, SLAPI_SEARCH_RESULT_ENTRY, &e);
+        pb.redact_entry();
+        assert!(pb.get_search_entry_copy().is_none());
+        pb.destroy();
+    }
+}
@@ -0,0 +1,89 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use super::constants::*;
+
+/// The type of change that a modification makes to an attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModType {
+    /// Add the values to the attribute.
+    Add,
+    /// Delete the values from the attribute. With no values, the attribute
+    /// is removed.
+    Delete,
+    /// Replace all values of the attribute. With no values, the attribute
+    /// is removed.
+    Replace,
+}
+
+impl ModType {
+    /// Convert the modification type to the LDAP_MOD_* value that Directory
+    /// Server expects.
+    pub fn as_ds_isize(&self) -> isize {
+        match *self {
+            ModType::Add => LDAP_MOD_ADD,
+            ModType::Delete => LDAP_MOD_DELETE,
+            ModType::Replace => LDAP_MOD_REPLACE,
+        }
+    }
+
+    /// Convert an LDAP_MOD_* value into the modification type. Flags such as
+    /// LDAP_MOD_BVALUES are ignored.
+    pub fn from_ds_isize(op: isize) -> Option<ModType> {
+        match op & 0x0f {
+            LDAP_MOD_ADD => Some(ModType::Add),
+            LDAP_MOD_DELETE => Some(ModType::Delete),
+            LDAP_MOD_REPLACE => Some(ModType::Replace),
+            _ => None,
+        }
+    }
+
+    /// The name of the modification type as used in LDIF, IE add.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ModType::Add => "add",
+            ModType::Delete => "delete",
+            ModType::Replace => "replace",
+        }
+    }
+}
+
+/// A single change to one attribute of an entry, as part of a modify
+/// operation. Values are bytes, as attributes may hold binary data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Modification {
+    /// The type of change.
+    pub mod_type: ModType,
+    /// The attribute that is changed.
+    pub attr: String,
+    /// The values that are added, deleted or replaced.
+    pub values: Vec<Vec<u8>>,
+}
+
+impl Modification {
+    /// Create a new modification with no values.
+    pub fn new(mod_type: ModType, attr: &str) -> Modification {
+        Modification {
+            mod_type,
+            attr: attr.to_string(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Add a value to the modification.
+    pub fn value<V: Into<Vec<u8>>>(mut self, value: V) -> Modification {
+        self.values.push(value.into());
+        self
+    }
+}
@@ -13,40 +13,212 @@
 #![warn(missing_docs)]
 
 use libc;
+use slapi_r_plugin_sys as sys;
+use std::ptr;
+use std::marker::PhantomData;
+use std::ffi::CStr;
+use std::os::raw::c_char;
 use super::constants::*;
 
 // Possible to make this a trait based type?
 
+/// The type of LDAP operation that the server is processing. This is derived
+/// from the SLAPI_OPERATION_* values that slapi_op_get_type returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationType {
+    /// An add operation.
+    Add,
+    /// A modify operation.
+    Modify,
+    /// A delete operation.
+    Delete,
+    /// A modrdn (moddn) operation.
+    ModRdn,
+    /// A search operation.
+    Search,
+    /// A compare operation.
+    Compare,
+    /// A bind operation.
+    Bind,
+    /// An unbind operation.
+    Unbind,
+    /// An abandon operation.
+    Abandon,
+    /// An extended operation.
+    Extended,
+}
+
+impl OperationType {
+    /// Convert the value from slapi_op_get_type into an OperationType. If the
+    /// value is not a known operation type, None is returned.
+    pub fn from_ds_type(op_type: usize) -> Option<OperationType> {
+        match op_type {
+            SLAPI_OPERATION_ADD => Some(OperationType::Add),
+            SLAPI_OPERATION_MODIFY => Some(OperationType::Modify),
+            SLAPI_OPERATION_DELETE => Some(OperationType::Delete),
+            SLAPI_OPERATION_MODRDN => Some(OperationType::ModRdn),
+            SLAPI_OPERATION_SEARCH => Some(OperationType::Search),
+            SLAPI_OPERATION_COMPARE => Some(OperationType::Compare),
+            SLAPI_OPERATION_BIND => Some(OperationType::Bind),
+            SLAPI_OPERATION_UNBIND => Some(OperationType::Unbind),
+            SLAPI_OPERATION_ABANDON => Some(OperationType::Abandon),
+            SLAPI_OPERATION_EXTENDED => Some(OperationType::Extended),
+            _ => None,
+        }
+    }
+
+    /// The name of the operation type, IE add.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            OperationType::Add => "add",
+            OperationType::Modify => "modify",
+            OperationType::Delete => "delete",
+            OperationType::ModRdn => "modrdn",
+            OperationType::Search => "search",
+            OperationType::Compare => "compare",
+            OperationType::Bind => "bind",
+            OperationType::Unbind => "unbind",
+            OperationType::Abandon => "abandon",
+            OperationType::Extended => "extended",
+        }
+    }
+
+    /// Convert the name from as_str back into an OperationType. If the name
+    /// is not a known operation type, None is returned.
+    pub fn from_name(name: &str) -> Option<OperationType> {
+        match name {
+            "add" => Some(OperationType::Add),
+            "modify" => Some(OperationType::Modify),
+            "delete" => Some(OperationType::Delete),
+            "modrdn" => Some(OperationType::ModRdn),
+            "search" => Some(OperationType::Search),
+            "compare" => Some(OperationType::Compare),
+            "bind" => Some(OperationType::Bind),
+            "unbind" => Some(OperationType::Unbind),
+            "abandon" => Some(OperationType::Abandon),
+            "extended" => Some(OperationType::Extended),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this operation type can change the content of the
+    /// directory.
+    pub fn is_write(&self) -> bool {
+        matches!(*self, OperationType::Add | OperationType::Modify | OperationType::Delete | OperationType::ModRdn)
+    }
+}
+
 #[derive(Debug)]
 #[allow(non_camel_case_types)]
 /// Slapi_R_Operation is a container for the slapi_operation C type
 /// This represents the current operation being processed by this thread.
 /// By making this opaque, we prevent having to deal with C types in rust.
-pub struct Slapi_R_Operation {
-    slapi_operation: *const libc::c_void,
-}
-
-extern {
-    fn operation_is_flag_set(op: *const libc::c_void, flag: isize) -> isize;
+/// The operation borrows the pblock it came from for the lifetime 'a, so it
+/// can not be kept after the callback returns.
+pub struct Slapi_R_Operation<'a> {
+    slapi_pblock: *mut sys::Slapi_PBlock,
+    slapi_operation: *mut sys::Slapi_Operation,
+    _owner: PhantomData<&'a ()>,
 }
 
-impl Slapi_R_Operation {
+impl<'a> Slapi_R_Operation<'a> {
     /// Build a new Slapi_R_Operation from the pointer to the slapi_operation
-    /// you should not need to do this yourself, you should be getting the
-    /// Slapi_R_Operation from the pblock methods.
-    pub fn new(slapi_operation: *const libc::c_void) -> Slapi_R_Operation {
+    /// and the pblock it was retrieved from. You should not need to do this
+    /// yourself, you should be getting the Slapi_R_Operation from the pblock
+    /// methods.
+    ///
+    /// # Safety
+    /// Both pointers must be valid and non null for all of 'a.
+    pub unsafe fn new(slapi_pblock: *const libc::c_void, slapi_operation: *const libc::c_void) -> Slapi_R_Operation<'a> {
         Slapi_R_Operation {
-            slapi_operation: slapi_operation
+            slapi_pblock: slapi_pblock as *mut sys::Slapi_PBlock,
+            slapi_operation: slapi_operation as *mut sys::Slapi_Operation,
+            _owner: PhantomData,
         }
     }
 
     /// This is a private "c like" function which handles requesting the flag
     /// from the operation in directory Server.
     fn is_flag_set(&self, flag: isize) -> bool {
-        if unsafe { operation_is_flag_set(self.slapi_operation, flag) } != 0 {
-            true
-        } else {
-            false
+        unsafe { sys::operation_is_flag_set(self.slapi_operation, flag as libc::c_int) != 0 }
+    }
+
+    /// This is a private wrapper to retrieve a value about this operation
+    /// from the pblock it belongs to. T must be the C type of the slot, IE
+    /// c_int for SLAPI_OPERATION_ID, as Directory Server writes that many
+    /// bytes.
+    fn _get_pblock_value<T: Copy + Default>(&self, pblock_type: isize) -> T {
+        let mut value: T = T::default();
+        let value_ptr: *mut libc::c_void = &mut value as *mut _ as *mut libc::c_void;
+        unsafe {
+            sys::slapi_pblock_get(self.slapi_pblock, pblock_type as libc::c_int, value_ptr);
+        }
+        value
+    }
+
+    /// This is a private wrapper to retrieve a string value about this
+    /// operation from the pblock it belongs to.
+    fn _get_pblock_string(&self, pblock_type: isize) -> Option<String> {
+        let mut value: *const c_char = ptr::null();
+        let value_ptr: *mut libc::c_void = &mut value as *mut _ as *mut libc::c_void;
+        unsafe {
+            sys::slapi_pblock_get(self.slapi_pblock, pblock_type as libc::c_int, value_ptr);
+            if value.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(value).to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    /// The type of this operation, IE add, modify, search. If Directory Server
+    /// returns a type we do not know about, None is returned.
+    pub fn op_type(&self) -> Option<OperationType> {
+        OperationType::from_ds_type(unsafe { sys::slapi_op_get_type(self.slapi_operation) } as usize)
+    }
+
+    /// The DN that this operation is targeting. For a search this is the
+    /// search base. Not all operations have a target, IE unbind.
+    pub fn target_dn(&self) -> Option<String> {
+        unsafe {
+            let sdn = sys::operation_get_target_spec(self.slapi_operation);
+            if sdn.is_null() {
+                return None;
+            }
+            let dn = sys::slapi_sdn_get_dn(sdn);
+            if dn.is_null() {
+                None
+            } else {
+                Some(CStr::from_ptr(dn).to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    /// The time this operation was started, in seconds since the epoch.
+    pub fn start_time(&self) -> i64 {
+        let time: libc::time_t = self._get_pblock_value(SLAPI_OPINITIATED_TIME);
+        time as i64
+    }
+
+    /// The id of this operation on its connection. This is the op= value
+    /// shown in the access log.
+    pub fn op_id(&self) -> isize {
+        self._get_pblock_value::<libc::c_int>(SLAPI_OPERATION_ID) as isize
+    }
+
+    /// The id of the connection this operation arrived on. This is the conn=
+    /// value shown in the access log.
+    pub fn conn_id(&self) -> u64 {
+        self._get_pblock_value::<u64>(SLAPI_CONN_ID)
+    }
+
+    /// The DN this operation is authorised as. When proxied authorisation is
+    /// in use, this is the proxied identity rather than the bound DN. An
+    /// anonymous operation returns None.
+    pub fn authorised_dn(&self) -> Option<String> {
+        match self._get_pblock_string(SLAPI_REQUESTOR_DN) {
+            Some(ref dn) if dn.is_empty() => None,
+            dn => dn,
         }
     }
 
@@ -59,4 +231,121 @@ impl Slapi_R_Operation {
     pub fn is_internal(&self) -> bool {
         self.is_flag_set(OP_FLAG_INTERNAL)
     }
+
+    /// Is this operation a persistent search.
+    pub fn is_persistent_search(&self) -> bool {
+        self.is_flag_set(OP_FLAG_PS)
+    }
+
+    /// Is this operation a persistent search that only returns changes.
+    pub fn is_persistent_search_changes_only(&self) -> bool {
+        self.is_flag_set(OP_FLAG_PS_CHANGESONLY)
+    }
+
+    /// Is this operation a search requesting effective rights.
+    pub fn is_get_effective_rights(&self) -> bool {
+        self.is_flag_set(OP_FLAG_GET_EFFECTIVE_RIGHTS)
+    }
+
+    /// Is this operation a replication fixup, such as conflict resolution.
+    pub fn is_repl_fixup(&self) -> bool {
+        self.is_flag_set(OP_FLAG_REPL_FIXUP)
+    }
+
+    /// Will this operation be written to the access log.
+    pub fn is_action_log_access(&self) -> bool {
+        self.is_flag_set(OP_FLAG_ACTION_LOG_ACCESS)
+    }
+
+    /// Will this operation be written to the audit log.
+    pub fn is_action_log_audit(&self) -> bool {
+        self.is_flag_set(OP_FLAG_ACTION_LOG_AUDIT)
+    }
+
+    /// Will this operation be schema checked.
+    pub fn is_action_schema_check(&self) -> bool {
+        self.is_flag_set(OP_FLAG_ACTION_SCHEMA_CHECK)
+    }
+
+    /// Will this operation be written to the changelog.
+    pub fn is_action_log_changes(&self) -> bool {
+        self.is_flag_set(OP_FLAG_ACTION_LOG_CHANGES)
+    }
+
+    /// Will plugins be invoked for this operation even though it is replicated.
+    pub fn is_action_invoke_for_replop(&self) -> bool {
+        self.is_flag_set(OP_FLAG_ACTION_INVOKE_FOR_REPLOP)
+    }
+
+    /// Is this operation prevented from being chained to a remote server.
+    pub fn is_never_chain(&self) -> bool {
+        self.is_flag_set(OP_FLAG_NEVER_CHAIN)
+    }
+
+    /// Is this operation targeting a tombstone entry.
+    pub fn is_tombstone_entry(&self) -> bool {
+        self.is_flag_set(OP_FLAG_TOMBSTONE_ENTRY)
+    }
+
+    /// Is this operation resurrecting a tombstone entry.
+    pub fn is_resurect_entry(&self) -> bool {
+        self.is_flag_set(OP_FLAG_RESURECT_ENTRY)
+    }
+
+    /// Is this operation excluded from the audit log and changelog.
+    pub fn is_action_nolog(&self) -> bool {
+        self.is_flag_set(OP_FLAG_ACTION_NOLOG)
+    }
+
+    /// Will this operation skip updating modifiersName and modifyTimestamp.
+    pub fn is_skip_modified_attrs(&self) -> bool {
+        self.is_flag_set(OP_FLAG_SKIP_MODIFIED_ATTRS)
+    }
+
+    /// Is this operation updating the replication RUV entry.
+    pub fn is_repl_ruv(&self) -> bool {
+        self.is_flag_set(OP_FLAG_REPL_RUV)
+    }
+
+    /// Is this operation a search using the simple paged results control.
+    pub fn is_paged_results(&self) -> bool {
+        self.is_flag_set(OP_FLAG_PAGED_RESULTS)
+    }
+
+    /// Is this operation a search using the server side sorting control.
+    pub fn is_server_side_sorting(&self) -> bool {
+        self.is_flag_set(OP_FLAG_SERVER_SIDE_SORTING)
+    }
+
+    /// Is this operation a search returning its candidates in reverse order.
+    pub fn is_reverse_candidate_order(&self) -> bool {
+        self.is_flag_set(OP_FLAG_REVERSE_CANDIDATE_ORDER)
+    }
+
+    /// Are the entries of this operation kept out of the entry cache.
+    pub fn is_never_cache(&self) -> bool {
+        self.is_flag_set(OP_FLAG_NEVER_CACHE)
+    }
+
+    /// Is this operation repairing a tombstone entry.
+    pub fn is_tombstone_fixup(&self) -> bool {
+        self.is_flag_set(OP_FLAG_TOMBSTONE_FIXUP)
+    }
+
+    /// Is this operation part of a bulk import.
+    pub fn is_bulk_import(&self) -> bool {
+        self.is_flag_set(OP_FLAG_BULK_IMPORT)
+    }
+
+    /// Is this operation processed without being applied, IE it carries the
+    /// LDAP no-op control.
+    pub fn is_noop(&self) -> bool {
+        self.is_flag_set(OP_FLAG_NOOP)
+    }
+
+    /// Is this operation being processed inside a backend transaction. This
+    /// is true for the be_txn plugin callbacks.
+    pub fn is_be_txn(&self) -> bool {
+        !self._get_pblock_value::<*const libc::c_void>(SLAPI_TXN).is_null()
+    }
 }
@@ -14,14 +14,21 @@
 
 use libc;
 use std::ptr;
-use std::mem;
+use std::slice;
+use std::any::Any;
+use std::ffi::CStr;
 use std::ffi::CString;
 use std::os::raw::c_char;
 use super::constants::*;
+use slapi_r_plugin_sys as sys;
 use super::error::PBlockError;
+use super::error::AccessError;
+use super::acl::Access;
 use super::error::PluginOperationError;
 use super::entry::Slapi_R_Entry;
 use super::operation::Slapi_R_Operation;
+use super::mods::ModType;
+use super::mods::Modification;
 // use std::ops::Drop;
 
 // Wrapper for the pblock in rust.
@@ -34,47 +41,55 @@ use super::operation::Slapi_R_Operation;
 #[allow(non_camel_case_types)]
 pub trait Slapi_PBlock_Init_V3 {
     /// Get the plugin api version
-    fn get_plugin_version(&self) -> Option<isize>;
+    fn get_plugin_version(&self) -> Option<PluginVersion>;
     /// Set the plugin api version
-    fn set_plugin_version(&self, version: isize);
+    fn set_plugin_version(&self, version: PluginVersion);
     /// Set the plugin's closing function handler. This is used by init the macros
-    fn set_plugin_close_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_close_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's start function handler. This is used by init the macros
-    fn set_plugin_start_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_start_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's post search function handler. This is used by init the macros
-    fn set_plugin_post_search_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_post_search_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre bind function handler. This is used by init the macros
-    fn set_plugin_pre_bind_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_bind_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_unbind function handler. This is used by init the macros
-    fn set_plugin_pre_unbind_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_unbind_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_search function handler. This is used by init the macros
-    fn set_plugin_pre_search_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_search_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_compare function handler. This is used by init the macros
-    fn set_plugin_pre_compare_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_compare_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_modrdn function handler. This is used by init the macros
-    fn set_plugin_pre_modrdn_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_modrdn_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_modify function handler. This is used by init the macros
-    fn set_plugin_pre_modify_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_modify_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_add function handler. This is used by init the macros
-    fn set_plugin_pre_add_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_add_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_delete function handler. This is used by init the macros
-    fn set_plugin_pre_delete_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_delete_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_abandon function handler. This is used by init the macros
-    fn set_plugin_pre_abandon_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_abandon_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_entry function handler. This is used by init the macros
-    fn set_plugin_pre_entry_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_entry_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_referal function handler. This is used by init the macros
-    fn set_plugin_pre_referal_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_referal_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_result function handler. This is used by init the macros
-    fn set_plugin_pre_result_fn(&self, func: extern fn(*const libc::c_void) -> isize);
+    fn set_plugin_pre_result_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Set the plugin's pre_extop function handler. This is used by init the macros
-    fn set_plugin_pre_extop_fn(&self, func: extern fn(*const libc::c_void) -> isize);
-    /// Set the private data into the plugin.
-    fn get_plugin_private<T>(&self) -> Option<&T>;
+    fn set_plugin_pre_extop_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
+    /// Set the plugin's function handler for a SLAPI_PLUGIN_*_FN slot. This
+    /// is used by the Slapi_R_Plugin_Manager for the slots that depend on
+    /// the plugin type, IE SLAPI_PLUGIN_BE_TXN_POST_ADD_FN.
+    fn set_plugin_fn(&self, pblock_type: isize, func: extern "C" fn(*const libc::c_void) -> libc::c_int);
     /// Get the private data from the plugin.
-    fn set_plugin_private<T>(&self, value: T);
+    fn get_plugin_private<T: Any + Send + Sync>(&self) -> Result<Option<&T>, PBlockError>;
+    /// Set the private data into the plugin.
+    fn set_plugin_private<T: Any + Send + Sync>(&self, value: T);
     /// Destroy the private data stored in the plugin
     fn destroy_plugin_private(&self) -> Result<(), PBlockError>;
+    /// Returns the identity Directory Server assigned to this plugin.
+    fn get_plugin_identity(&self) -> Option<*const libc::c_void>;
+    /// Returns the cn=<plugin>,cn=plugins,cn=config entry of this plugin.
+    fn get_plugin_config_entry<'a>(&'a self) -> Option<Slapi_R_Entry<'a>>;
 }
 
 /// Slapi_PBlock_V3 defines the set of functions that version 3 plugins expect
@@ -83,11 +98,14 @@ pub trait Slapi_PBlock_Init_V3 {
 #[allow(non_camel_case_types)]
 pub trait Slapi_PBlock_V3 {
     /// Returns the set of entries that were retrieved in this search.
-    fn get_search_result_entry(&self) -> Option<Slapi_R_Entry>;
+    fn get_search_result_entry<'a>(&'a self) -> Option<Slapi_R_Entry<'a>>;
+    /// Returns the copy of the search result entry that a pre_entry plugin
+    /// made with redact_entry, if any. This is the entry that will be sent.
+    fn get_search_entry_copy<'a>(&'a self) -> Option<Slapi_R_Entry<'a>>;
     // Returns a boolean if this operation is from a replication event.
     //fn get_is_replicated_operation(&self) -> Option<bool>;
     /// Returns the current Operation that the directory Server is processing.
-    fn get_operation(&self) -> Option<Slapi_R_Operation>;
+    fn get_operation<'a>(&'a self) -> Option<Slapi_R_Operation<'a>>;
     /// Function allowing plugins (and internal DS parts) to trigger a result send
     /// to the client.
     /// This is needed for plugins, especially if they error or are prematurely ending
@@ -95,6 +113,39 @@ pub trait Slapi_PBlock_V3 {
     /// this failed now".
     /// After you have called this, your plugin MUST return an Err() type!
     fn send_ldap_result(&self, error: PluginOperationError, message: &str);
+    /// Ask Directory Server if the identity of the current operation has the
+    /// access right to the named attribute of the entry.
+    fn access_allowed(&self, entry: &Slapi_R_Entry, attr: &str, access: Access) -> bool;
+    /// Ask Directory Server if the identity of the current operation may apply
+    /// the modifications of this modify operation to the entry.
+    fn check_modify_access(&self, entry: &Slapi_R_Entry) -> Result<(), AccessError>;
+    /// Remove every attribute from the search result entry that the identity
+    /// of the current operation is not permitted to read. The result entry
+    /// is not changed: the attributes are removed from a copy, which is sent
+    /// in its place.
+    fn redact_entry(&self);
+}
+
+/// The plugin private data. The value is boxed as Any, so that a request for
+/// the data as any other type is an error rather than a reinterpretation of
+/// the memory.
+#[allow(non_camel_case_types)]
+pub(crate) struct Slapi_R_Private {
+    value: Box<dyn Any + Send + Sync>,
+}
+
+impl Slapi_R_Private {
+    /// Store a value as plugin private data.
+    pub(crate) fn new<T: Any + Send + Sync>(value: T) -> Slapi_R_Private {
+        Slapi_R_Private {
+            value: Box::new(value),
+        }
+    }
+
+    /// Borrow the value, if it is of type T.
+    pub(crate) fn get<T: Any + Send + Sync>(&self) -> Result<&T, PBlockError> {
+        self.value.downcast_ref::<T>().ok_or(PBlockError::TypeMismatch)
+    }
 }
 
 #[derive(Debug)]
@@ -104,22 +155,12 @@ pub trait Slapi_PBlock_V3 {
 /// By making this opaque, it gives us the ability to rework or improve 
 /// the structure without breaking the API of Slapi_R_PBlock.
 pub struct Slapi_R_PBlock {
-    slapi_pblock: *const libc::c_void,
-}
-
-extern {
-    // pblock.c
-    fn slapi_pblock_new() -> *const libc::c_void;
-    fn slapi_pblock_init(pb: *const libc::c_void);
-    fn slapi_pblock_destroy(pb: *const libc::c_void);
-
-    fn slapi_pblock_get(pb: *const libc::c_void, arg: isize, value: *const libc::c_void);
-    fn slapi_pblock_set(pb: *const libc::c_void, arg: isize, value: *const libc::c_void);
-    // plugin.c
-    fn slapi_send_ldap_result(pb: *const libc::c_void, err: isize, matched: *const c_char, errmsg: *const c_char, nentries: isize, urls: *const libc::c_void);
+    slapi_pblock: *mut sys::Slapi_PBlock,
 }
 
 
+// A new pblock must be destroyed, so it is not a Default.
+#[allow(clippy::new_without_default)]
 impl Slapi_R_PBlock {
     /// Creates a new Slapi_R_PBlock, with a new C PBlock structure.
     ///
@@ -129,7 +170,7 @@ impl Slapi_R_PBlock {
     /// as pblock internal structures such as pb_plugin are *not* allocated.
     pub fn new() -> Slapi_R_PBlock {
         unsafe {
-            Slapi_R_PBlock { slapi_pblock: slapi_pblock_new() }
+            Slapi_R_PBlock { slapi_pblock: sys::slapi_pblock_new() }
         }
     }
 
@@ -137,14 +178,20 @@ impl Slapi_R_PBlock {
     /// It is very likely, that this is the function you want to use if you are
     /// woring on slapi_r_plugin internals.
     pub fn build( slapi_pblock: *const libc::c_void ) -> Slapi_R_PBlock {
-        Slapi_R_PBlock { slapi_pblock: slapi_pblock }
+        Slapi_R_PBlock { slapi_pblock: slapi_pblock as *mut sys::Slapi_PBlock }
+    }
+
+    /// Returns the pointer to the Slapi_PBlock for use by other slapi_r_plugin
+    /// modules that need to pass the pblock to Directory Server.
+    pub(crate) fn as_ptr(&self) -> *mut sys::Slapi_PBlock {
+        self.slapi_pblock
     }
 
     /// This will wipe and reset the internal contents of the Slapi_PBlock C
     /// structure.
     pub fn init(self) {
         unsafe {
-            slapi_pblock_init(self.slapi_pblock)
+            sys::slapi_pblock_init(self.slapi_pblock)
         }
     }
 
@@ -156,31 +203,33 @@ impl Slapi_R_PBlock {
     /// struct, you will likely cause a SIGSEGV
     pub fn destroy(self) {
         unsafe {
-            slapi_pblock_destroy(self.slapi_pblock)
+            sys::slapi_pblock_destroy(self.slapi_pblock)
         }
     }
 
-    /// This is an internal wrapper allowing the retrival of an isize from the
-    /// Slapi_PBlock.
-    fn _get_isize(&self, pblock_type: isize) -> Option<isize> {
-        let mut value: isize = 0;
-        let value_ptr: *const libc::c_void = &mut value as *const _ as *const libc::c_void;
+    /// This is an internal wrapper allowing the retrival of an int from the
+    /// Slapi_PBlock. The slot must hold a C int, as Directory Server only
+    /// writes that many bytes.
+    fn _get_int(&self, pblock_type: isize) -> Option<isize> {
+        let mut value: libc::c_int = 0;
+        let value_ptr: *mut libc::c_void = &mut value as *mut _ as *mut libc::c_void;
         unsafe {
             // There is potentially a crash here as pblock makes no check if
             // SLAPI_PLUGIN_TYPE has been set ...
-            slapi_pblock_get(self.slapi_pblock, pblock_type, value_ptr);
+            sys::slapi_pblock_get(self.slapi_pblock, pblock_type as libc::c_int, value_ptr);
         }
-        Some(value)
+        Some(value as isize)
     }
 
-    /// This is an internal wrapper allowing the setting of an isize from the
+    /// This is an internal wrapper allowing the setting of an int into the
     /// Slapi_PBlock.
-    fn _set_isize(&self, pblock_type: isize, mut value: isize) {
+    fn _set_int(&self, pblock_type: isize, value: isize) {
         // There is a potential crash here as pblock makes no check if pblock->pb_plugin is alloced
-        let value_ptr: *const libc::c_void = &mut value as *const _ as *const libc::c_void;
+        let mut value = value as libc::c_int;
+        let value_ptr: *mut libc::c_void = &mut value as *mut _ as *mut libc::c_void;
         unsafe {
             // This value is copied as it's an int.
-            slapi_pblock_set(self.slapi_pblock, pblock_type, value_ptr);
+            sys::slapi_pblock_set(self.slapi_pblock, pblock_type as libc::c_int, value_ptr);
         }
     }
 
@@ -189,11 +238,11 @@ impl Slapi_R_PBlock {
     fn _get_void_ptr(&self, pblock_type: isize) -> Option<*const libc::c_void> {
         let mut value: *mut libc::c_void = ptr::null::<libc::c_void>() as *mut libc::c_void; // = &mut value;
         // Make a pointer to our pointer ....
-        let value_ptr: *const libc::c_void = &mut value as *const _ as *const libc::c_void;
+        let value_ptr: *mut libc::c_void = &mut value as *mut _ as *mut libc::c_void;
         unsafe {
             // There is potentially a crash here as pblock makes no check if
             // SLAPI_PLUGIN_TYPE has been set ...
-            slapi_pblock_get(self.slapi_pblock, pblock_type, value_ptr);
+            sys::slapi_pblock_get(self.slapi_pblock, pblock_type as libc::c_int, value_ptr);
         }
         if value_ptr.is_null() || value.is_null() {
             None
@@ -203,11 +252,11 @@ impl Slapi_R_PBlock {
     }
 
     /// This is an internal wrapper allowing the setting of a platform struct
-    /// into the Slapi_PBlock.
-    fn _set_void_ptr(&self, pblock_type: isize, value: libc::c_void) {
-        let value_ptr: *const libc::c_void = &value;
+    /// pointer into the Slapi_PBlock. The pointer itself is stored, not what
+    /// it points to.
+    fn _set_void_ptr(&self, pblock_type: isize, value: *mut libc::c_void) {
         unsafe {
-            slapi_pblock_set(self.slapi_pblock, pblock_type, value_ptr);
+            sys::slapi_pblock_set(self.slapi_pblock, pblock_type as libc::c_int, value);
         }
     }
 
@@ -218,11 +267,8 @@ impl Slapi_R_PBlock {
 
     /// This is an internal wrapper allowing the setting of a function pointer
     // into the Slapi_PBlock IFP type.
-    fn _set_pb_fn_ptr(&self, pblock_type: isize, ptr: extern fn(*const libc::c_void) -> isize) {
-        let value_ptr: *const libc::c_void = ptr as *const libc::c_void;
-        unsafe {
-            slapi_pblock_set(self.slapi_pblock, pblock_type, value_ptr);
-        }
+    fn _set_pb_fn_ptr(&self, pblock_type: isize, ptr: extern "C" fn(*const libc::c_void) -> libc::c_int) {
+        self._set_void_ptr(pblock_type, ptr as *mut libc::c_void)
     }
 
     // NOTE: The bellow will probably become part of the v3 interface.
@@ -230,23 +276,96 @@ impl Slapi_R_PBlock {
     /// This will retrieve the value of SLAPI_PLUGIN_TYPE, such as BE_TXN,
     /// POST_OP etc.
     pub fn get_plugin_type(&self) -> Option<isize> {
-        self._get_isize(SLAPI_PLUGIN_TYPE)
+        self._get_int(SLAPI_PLUGIN_TYPE)
     }
 
     /// This will set the plugin type value as SLAPI_PLUGIN_TYPE, such as
     /// BE_TXN, POST_OP, etc.
     pub fn set_plugin_type(&self, plugin_type: isize) {
-        self._set_isize(SLAPI_PLUGIN_TYPE, plugin_type)
+        self._set_int(SLAPI_PLUGIN_TYPE, plugin_type)
+    }
+
+    /// This will get the plugin identity as SLAPI_PLUGIN_IDENTITY. This is
+    /// needed to perform internal operations on behalf of the plugin.
+    pub fn get_plugin_identity(&self) -> Option<*const libc::c_void> {
+        self._get_void_ptr(SLAPI_PLUGIN_IDENTITY)
     }
 
     /// This will get the operation return code as SLAPI_PLUGIN_OPRETURN
     pub fn get_plugin_opreturn(&self) -> Option<isize> {
-        self._get_isize(SLAPI_PLUGIN_OPRETURN)
+        self._get_int(SLAPI_PLUGIN_OPRETURN)
     }
 
     /// This will set the operation return code as SLAPI_PLUGIN_OPRETURN
     pub fn set_plugin_opreturn(&self, opreturn: isize) {
-        self._set_isize(SLAPI_PLUGIN_OPRETURN, opreturn)
+        self._set_int(SLAPI_PLUGIN_OPRETURN, opreturn)
+    }
+
+    /// This will get the LDAP result of an internal operation, IE
+    /// slapi_add_internal_pb, as SLAPI_PLUGIN_INTOP_RESULT.
+    pub fn get_plugin_intop_result(&self) -> Option<isize> {
+        self._get_int(SLAPI_PLUGIN_INTOP_RESULT)
+    }
+
+    /// This will retrieve the entry that an add operation is adding, as
+    /// SLAPI_ADD_ENTRY.
+    pub fn get_add_entry<'a>(&'a self) -> Option<Slapi_R_Entry<'a>> {
+        self._get_void_ptr(SLAPI_ADD_ENTRY).map(|p| unsafe { Slapi_R_Entry::new(p) })
+    }
+
+    /// This will retrieve the filter of a search operation as
+    /// SLAPI_SEARCH_STRFILTER, IE (uid=william).
+    pub fn get_search_filter(&self) -> Option<String> {
+        self._get_void_ptr(SLAPI_SEARCH_STRFILTER)
+            .map(|p| unsafe { CStr::from_ptr(p as *const c_char) }.to_string_lossy().into_owned())
+    }
+
+    /// This will retrieve a copy of the modifications of a modify operation,
+    /// as SLAPI_MODIFY_MODS. Modifications of an unknown type are skipped.
+    pub fn get_modifications(&self) -> Option<Vec<Modification>> {
+        let mods = match self._get_void_ptr(SLAPI_MODIFY_MODS) {
+            Some(m) => m as *const *const sys::LDAPMod,
+            None => return None,
+        };
+        let mut result = Vec::new();
+        unsafe {
+            // Both the array of mods and the arrays of values end in a null.
+            let mut i = 0;
+            while !(*mods.add(i)).is_null() {
+                let m = &**mods.add(i);
+                i += 1;
+                let mod_type = match ModType::from_ds_isize(m.mod_op as isize) {
+                    Some(t) => t,
+                    None => continue,
+                };
+                if m.mod_type.is_null() {
+                    continue;
+                }
+                let mut modification = Modification::new(mod_type, &CStr::from_ptr(m.mod_type).to_string_lossy());
+                if m.mod_op as isize & LDAP_MOD_BVALUES != 0 {
+                    let values = m.mod_vals.modv_bvals;
+                    let mut j = 0;
+                    while !values.is_null() && !(*values.add(j)).is_null() {
+                        let bv = &**values.add(j);
+                        modification.values.push(if bv.bv_val.is_null() {
+                            Vec::new()
+                        } else {
+                            slice::from_raw_parts(bv.bv_val as *const u8, bv.bv_len as usize).to_vec()
+                        });
+                        j += 1;
+                    }
+                } else {
+                    let values = m.mod_vals.modv_strvals;
+                    let mut j = 0;
+                    while !values.is_null() && !(*values.add(j)).is_null() {
+                        modification.values.push(CStr::from_ptr(*values.add(j)).to_bytes().to_vec());
+                        j += 1;
+                    }
+                }
+                result.push(modification);
+            }
+        }
+        Some(result)
     }
 
 
@@ -255,177 +374,198 @@ impl Slapi_R_PBlock {
 impl Slapi_PBlock_Init_V3 for Slapi_R_PBlock {
     /// This will get the plugin api version from SLAPI_PLUGIN_VERSION
     /// See also constants::PluginVersion
-    fn get_plugin_version(&self) -> Option<isize> {
-        self._get_isize(SLAPI_PLUGIN_VERSION)
+    fn get_plugin_version(&self) -> Option<PluginVersion> {
+        match self._get_void_ptr(SLAPI_PLUGIN_VERSION) {
+            Some(p) => {
+                let version = unsafe { CStr::from_ptr(p as *const c_char) };
+                PluginVersion::from_ds_version(version.to_bytes_with_nul())
+            }
+            None => None,
+        }
     }
 
     /// This will set the plugin api version from SLAPI_PLUGIN_VERSION
     /// See also constants::PluginVersion
-    fn set_plugin_version(&self, version: isize) {
-        self._set_isize(SLAPI_PLUGIN_VERSION, version)
+    fn set_plugin_version(&self, version: PluginVersion) {
+        // Directory Server keeps the pointer, so this must be static.
+        self._set_void_ptr(SLAPI_PLUGIN_VERSION, version.as_ds_version().as_ptr() as *mut libc::c_void)
     }
 
     /// This will set the close plugin callback handler as
     /// SLAPI_PLUGIN_CLOSE_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_close_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_close_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_CLOSE_FN, func)
     }
 
     /// This will set the start plugin callback handler as
     /// SLAPI_PLUGIN_START_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_start_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_start_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_START_FN, func)
     }
 
     /// This will set the post search operation plugin callback handler as
     /// SLAPI_PLUGIN_POST_SEARCH_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_post_search_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_post_search_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_POST_SEARCH_FN, func)
     }
 
     /// This will set the pre bind operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_BIND_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_bind_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_bind_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_BIND_FN, func)
     }
 
     /// This will set the pre_unbind operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_UNBIND_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_unbind_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_unbind_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_UNBIND_FN, func)
     }
 
     /// This will set the pre_search operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_search_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_search_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_search_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_SEARCH_FN, func)
     }
 
     /// This will set the pre_compare operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_COMPARE_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_compare_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_compare_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_COMPARE_FN, func)
     }
 
     /// This will set the pre_modify operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_modify_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_modify_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_modify_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_MODIFY_FN, func)
     }
 
     /// This will set the pre_modrdn operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_MODRDN_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_modrdn_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_modrdn_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_MODRDN_FN, func)
     }
 
     /// This will set the pre_add operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_ADD_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_add_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_add_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_ADD_FN, func)
     }
 
     /// This will set the pre_delete operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_DELETE_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_delete_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_delete_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_DELETE_FN, func)
     }
 
     /// This will set the pre_abandon operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_ABANDON_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_abandon_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_abandon_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_ABANDON_FN, func)
     }
 
     /// This will set the pre_entry operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_ENTRY_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_entry_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_entry_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_ENTRY_FN, func)
     }
 
     /// This will set the pre_referal operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_REFERAL_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_referal_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_referal_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_REFERAL_FN, func)
     }
 
     /// This will set the pre_result operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_RESULT_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_result_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_result_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_RESULT_FN, func)
     }
 
     /// This will set the pre_extop operation plugin callback handler as
     /// SLAPI_PLUGIN_PRE_EXTOP_FN. You should *not* call this directly
     /// as the Slapi_R_Plugin_Manager will handle this for you.
-    fn set_plugin_pre_extop_fn(&self, func: extern fn(*const libc::c_void) -> isize) {
+    fn set_plugin_pre_extop_fn(&self, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
         self._set_pb_fn_ptr(SLAPI_PLUGIN_PRE_EXTOP_FN, func)
     }
 
-    /// This will get a pointer to a structure stored in the plugin private data
-    /// stash, from Slapi_PBlock.pb_plugin->plg_private. SLAPI_PLUGIN_PRIVATE
+    /// This will set a plugin callback handler into the given
+    /// SLAPI_PLUGIN_*_FN slot. You should *not* call this directly
+    /// as the Slapi_R_Plugin_Manager will handle this for you.
+    fn set_plugin_fn(&self, pblock_type: isize, func: extern "C" fn(*const libc::c_void) -> libc::c_int) {
+        self._set_pb_fn_ptr(pblock_type, func)
+    }
+
+    /// This will get a reference to the value stored in the plugin private
+    /// data stash, from Slapi_PBlock.pb_plugin->plg_private.
+    /// SLAPI_PLUGIN_PRIVATE. The reference borrows the pblock. None is
+    /// returned if no private data has been set, and
+    /// PBlockError::TypeMismatch if it was set as a type other than T.
     /// You should *never* call this directly, as certain parts of the
     /// Slapi_R_Plugin_Manager rely on this data being un-tampered.
-    fn get_plugin_private<T>(&self) -> Option<&T> {
-                                            // Should this value here be a ptr::null? 
-        let mut value: *mut libc::c_void = 0usize as *mut libc::c_void;
-        let value_ptr: *const libc::c_void = &mut value as *const _ as *const libc::c_void;
-        unsafe {
-            // There is potentially a crash here as pblock makes no check if
-            // SLAPI_PLUGIN_TYPE has been set ...
-            slapi_pblock_get(self.slapi_pblock, SLAPI_PLUGIN_PRIVATE, value_ptr);
-            Some(&**(value_ptr as *const *const T))
+    fn get_plugin_private<T: Any + Send + Sync>(&self) -> Result<Option<&T>, PBlockError> {
+        match self._get_void_ptr(SLAPI_PLUGIN_PRIVATE) {
+            Some(p) => {
+                // Only set_plugin_private stores into SLAPI_PLUGIN_PRIVATE.
+                let private = unsafe { &*(p as *const Slapi_R_Private) };
+                private.get::<T>().map(Some)
+            }
+            None => Ok(None),
         }
     }
 
-    /// This will memory copy and set a pointer to the memory of a generic
-    /// Rust type into a 'box' stored in the Slapi_PBlock for this plugin
-    /// instance.
+    /// This will move a Rust value into a box stored in the Slapi_PBlock for
+    /// this plugin instance, along with its type. Any value that was already
+    /// stored is dropped.
     /// Certain parts of Slapi_R_Plugin_Manager rely on this, so you should
     /// *never* call this directly.
-    fn set_plugin_private<T>(&self, value: T) {
+    fn set_plugin_private<T: Any + Send + Sync>(&self, value: T) {
+        let _ = self.destroy_plugin_private();
+        let private = Box::into_raw(Box::new(Slapi_R_Private::new(value)));
         unsafe {
-            let value_ptr = libc::malloc(mem::size_of::<T>() as libc::size_t) as *mut T;
-            assert!(!value_ptr.is_null());
-            // This does a memcpy
-            ptr::write(value_ptr, value);
-            let f_value_ptr: *const libc::c_void = value_ptr as *const libc::c_void;
-            slapi_pblock_set(self.slapi_pblock, SLAPI_PLUGIN_PRIVATE, f_value_ptr);
+            sys::slapi_pblock_set(self.slapi_pblock, SLAPI_PLUGIN_PRIVATE as libc::c_int, private as *mut libc::c_void);
         }
     }
 
-    /// This will destroy (free) the contents of the pointer stored
-    /// within the Slapi_PBlock instance.
+    /// This will drop the value stored within the Slapi_PBlock instance,
+    /// running its destructor, and clear SLAPI_PLUGIN_PRIVATE.
     /// Certain parts of Slapi_R_Plugin_Manager rely on this, so you should
     /// *never* call this directly.
     fn destroy_plugin_private(&self) -> Result<(), PBlockError> {
-        let mut value: *mut libc::c_void = 0usize as *mut libc::c_void;
-        let value_ptr: *const libc::c_void = &mut value as *const _ as *const libc::c_void;
-        unsafe {
-            // There is potentially a crash here as pblock makes no check if
-            // SLAPI_PLUGIN_TYPE has been set ...
-            slapi_pblock_get(self.slapi_pblock, SLAPI_PLUGIN_PRIVATE, value_ptr);
-            if !value_ptr.is_null() {
-                libc::free(value);
+        if let Some(p) = self._get_void_ptr(SLAPI_PLUGIN_PRIVATE) {
+            unsafe {
+                sys::slapi_pblock_set(self.slapi_pblock, SLAPI_PLUGIN_PRIVATE as libc::c_int, ptr::null_mut());
+                drop(Box::from_raw(p as *mut Slapi_R_Private));
             }
         }
         Ok(())
     }
+
+    /// This will retrieve the identity of the plugin, as
+    /// SLAPI_PLUGIN_IDENTITY.
+    fn get_plugin_identity(&self) -> Option<*const libc::c_void> {
+        Slapi_R_PBlock::get_plugin_identity(self)
+    }
+
+    /// This will retrieve the configuration entry of the plugin. This is only
+    /// present in the pblock given to the plugin init function.
+    fn get_plugin_config_entry<'a>(&'a self) -> Option<Slapi_R_Entry<'a>> {
+        self._get_void_ptr(SLAPI_PLUGIN_CONFIG_ENTRY).map(|p| unsafe { Slapi_R_Entry::new(p) })
+    }
 }
 
 impl Slapi_PBlock_V3 for Slapi_R_PBlock {
@@ -433,17 +573,18 @@ impl Slapi_PBlock_V3 for Slapi_R_PBlock {
 
     /// This will retrieve the next Slapi_R_Entry from the result set
     /// in the pblock.
-    fn get_search_result_entry(&self) -> Option<Slapi_R_Entry> {
-        match self._get_void_ptr(SLAPI_SEARCH_RESULT_ENTRY) {
-            Some(p) => Some(Slapi_R_Entry::new(p)),
-            None => None,
-        }
+    fn get_search_result_entry<'a>(&'a self) -> Option<Slapi_R_Entry<'a>> {
+        self._get_void_ptr(SLAPI_SEARCH_RESULT_ENTRY).map(|p| unsafe { Slapi_R_Entry::new(p) })
+    }
+
+    fn get_search_entry_copy<'a>(&'a self) -> Option<Slapi_R_Entry<'a>> {
+        self._get_void_ptr(SLAPI_SEARCH_ENTRY_COPY).map(|p| unsafe { Slapi_R_Entry::new(p) })
     }
 
     // This will return a bool of if the current operation is replicated
     // or not.
     // fn get_is_replicated_operation(&self) -> Option<bool> {
-    //     match self._get_isize(SLAPI_IS_REPLICATED_OPERATION) {
+    //     match self._get_int(SLAPI_IS_REPLICATED_OPERATION) {
     //         Some(e) => {
     //             match e {
     //                 0 => Some(false),
@@ -455,11 +596,8 @@ impl Slapi_PBlock_V3 for Slapi_R_PBlock {
     // }
 
     /// This will retrieve the current slapi_operation if one is present
-    fn get_operation(&self) -> Option<Slapi_R_Operation> {
-        match self._get_void_ptr(SLAPI_OPERATION) {
-            Some(p) => Some(Slapi_R_Operation::new(p)),
-            None => None,
-        }
+    fn get_operation<'a>(&'a self) -> Option<Slapi_R_Operation<'a>> {
+        self._get_void_ptr(SLAPI_OPERATION).map(|p| unsafe { Slapi_R_Operation::new(self.slapi_pblock as *const libc::c_void, p) })
     }
 
     /// Function allowing plugins (and internal DS parts) to trigger a result send
@@ -468,13 +606,96 @@ impl Slapi_PBlock_V3 for Slapi_R_PBlock {
     /// an operation. This actually indicates "yes, I really want you tell the client
     /// this failed now".
     /// After you have called this, your plugin MUST return an Err() type!
+    /// Any nul bytes in the message are removed, as they can not be sent.
     fn send_ldap_result(&self, error: PluginOperationError, message: &str) {
-        let null_urls_ptr: *const libc::c_void = ptr::null() as *const libc::c_void;
-        let null_message_ptr: *const c_char = ptr::null() as *const c_char;
-        let c_message = CString::new(message).unwrap();
         // Convert the message to a cstring
+        let c_message = CString::new(message.replace('\0', "")).unwrap();
         unsafe {
-            slapi_send_ldap_result(self.slapi_pblock, error.as_ds_isize(), null_message_ptr, c_message.as_ptr(), 0, null_urls_ptr);
+            sys::slapi_send_ldap_result(self.slapi_pblock, error.as_ds_isize() as libc::c_int, ptr::null_mut(),
+                                        c_message.as_ptr() as *mut c_char, 0, ptr::null_mut());
+        }
+    }
+
+    /// Ask Directory Server if the identity of the current operation has the
+    /// access right to the named attribute of the entry. This is checked for
+    /// any value of the attribute. An attribute name containing a nul byte can
+    /// not exist, so access to it is never allowed.
+    fn access_allowed(&self, entry: &Slapi_R_Entry, attr: &str, access: Access) -> bool {
+        let c_attr = match CString::new(attr) {
+            Ok(a) => a,
+            Err(_) => return false,
+        };
+        let res = unsafe {
+            sys::slapi_access_allowed(self.slapi_pblock, entry.as_ptr(), c_attr.as_ptr() as *mut c_char,
+                                      ptr::null_mut(), access.as_ds_isize() as libc::c_int)
+        };
+        res as isize == LDAP_SUCCESS
+    }
+
+    /// Ask Directory Server if the identity of the current operation may apply
+    /// the modifications of this modify operation to the entry. If the access
+    /// is denied, the reason Directory Server gave is returned in the error.
+    fn check_modify_access(&self, entry: &Slapi_R_Entry) -> Result<(), AccessError> {
+        let mods = match self._get_void_ptr(SLAPI_MODIFY_MODS) {
+            Some(m) => m,
+            None => return Err(AccessError::Unknown),
+        };
+        let mut errbuf: *mut c_char = ptr::null_mut();
+        unsafe {
+            let res = sys::slapi_acl_check_mods(self.slapi_pblock, entry.as_ptr(),
+                                                mods as *mut *mut sys::LDAPMod, &mut errbuf);
+            let reason = if errbuf.is_null() {
+                None
+            } else {
+                let r = CStr::from_ptr(errbuf).to_string_lossy().into_owned();
+                sys::slapi_ch_free_string(&mut errbuf);
+                Some(r)
+            };
+            match res as isize {
+                LDAP_SUCCESS => Ok(()),
+                _ => Err(AccessError::InsufficientAccess(reason)),
+            }
+        }
+    }
+
+    /// Remove every attribute from the search result entry that the identity
+    /// of the current operation is not permitted to read. This is intended
+    /// for the pre_entry callback, so that only the permitted attributes are
+    /// returned.
+    ///
+    /// The result entry may be the entry in the backend's entry cache, so it
+    /// must never be changed. Instead the entry is copied with
+    /// slapi_entry_dup, the attributes are removed from the copy, and the copy
+    /// is set as SLAPI_SEARCH_ENTRY_COPY, which Directory Server sends and
+    /// then frees. If an earlier plugin already made a copy, that copy is
+    /// redacted instead.
+    fn redact_entry(&self) {
+        let entry = match self.get_search_entry_copy() {
+            Some(e) => e,
+            None => match self.get_search_result_entry() {
+                Some(e) => e,
+                None => return,
+            },
+        };
+        let denied: Vec<String> = entry.get_attribute_names().into_iter()
+            .filter(|attr| !self.access_allowed(&entry, attr, Access::Read))
+            .collect();
+        if denied.is_empty() {
+            return;
+        }
+        let mut copy = match self.get_search_entry_copy() {
+            Some(e) => e,
+            None => {
+                let copy = unsafe { sys::slapi_entry_dup(entry.as_ptr()) };
+                if copy.is_null() {
+                    return;
+                }
+                self._set_void_ptr(SLAPI_SEARCH_ENTRY_COPY, copy as *mut libc::c_void);
+                unsafe { Slapi_R_Entry::new(copy as *const libc::c_void) }
+            }
+        };
+        for attr in denied {
+            copy.remove_attribute(&attr);
         }
     }
 }
@@ -484,12 +705,22 @@ impl Slapi_PBlock_V3 for Slapi_R_PBlock {
 // impl Drop for Slapi_R_PBlock {
 //     fn drop(&mut self) {
 //         unsafe {
-//             slapi_pblock_destroy(self.slapi_pblock);
+//             sys::slapi_pblock_destroy(self.slapi_pblock);
 //         }
 //     }
 // }
 
 
+#[test]
+fn test_slapi_r_private_type_check() {
+    let private = Slapi_R_Private::new(42u32);
+    assert_eq!(private.get::<u32>().ok(), Some(&42u32));
+    match private.get::<u64>() {
+        Err(PBlockError::TypeMismatch) => {}
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
 #[test]
 fn test_slapi_r_pblock_new() {
     let pb: Slapi_R_PBlock = Slapi_R_PBlock::new();
@@ -498,7 +729,6 @@ fn test_slapi_r_pblock_new() {
         let v = pb.get_plugin_opreturn();
         assert_eq!(Some(SLAPI_PLUGIN_BE_TXN_PRE_ADD_FN), v);
     }
-    assert!(true);
     pb.destroy();
 }
 
@@ -13,8 +13,14 @@
 #![warn(missing_docs)]
 
 use libc;
+use slapi_r_plugin_sys as sys;
 
 use super::log::slapi_r_log_error;
+use super::log::slapi_r_log_init;
+use super::log::slapi_r_log_is_enabled;
+use super::log::slapi_r_log_set_format;
+use super::log::LogFormat;
+use super::log::LOG_FORMAT_ATTR;
 use super::error::PluginOperationError;
 use super::error::PluginRegistrationError;
 use super::constants::LogLevel;
@@ -22,8 +28,50 @@ use super::constants;
 use super::pblock::Slapi_R_PBlock;
 use super::pblock::Slapi_PBlock_V3;
 use super::pblock::Slapi_PBlock_Init_V3;
-
-const SUBSYSTEM: &'static str = "slapi_r_plugin::plugin::mod";
+use super::eventq;
+use super::task;
+use super::metrics;
+use super::replay;
+use super::replay::RECORD_FILE_ATTR;
+use super::entry::Entry;
+use super::capability::Capability;
+use super::capability::slapi_r_capabilities;
+use super::capability::slapi_r_server_version;
+use super::capability::slapi_r_slot_available;
+use std::any::Any;
+use std::ffi::CString;
+use std::path::Path;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+const SUBSYSTEM: &str = "slapi_r_plugin::plugin::mod";
+
+/// The precedence Directory Server gives a plugin that does not set one.
+const DEFAULT_PRECEDENCE: u32 = 50;
+const PRECEDENCE_ATTR: &str = "nsslapd-pluginprecedence";
+const DEPENDS_ON_TYPE_ATTR: &str = "nsslapd-plugin-depends-on-type";
+const DEPENDS_ON_NAMED_ATTR: &str = "nsslapd-plugin-depends-on-named";
+
+// The name the plugin registered with, used to name the monitor entry.
+static PLUGIN_NAME: Mutex<Option<String>> = Mutex::new(None);
+
+// The type and callbacks of the sub-plugin that slapi_register_plugin is
+// initialising. Directory Server calls the sub-plugin init function before
+// slapi_register_plugin returns, so only one is ever pending.
+static PENDING_SUB_PLUGIN: Mutex<Option<(String, Slapi_R_Plugin_FN)>> = Mutex::new(None);
+
+/// The nsslapd-pluginType of a plugin that does not set one.
+const DEFAULT_KIND: &str = "preoperation";
+
+// The state shared by the plugin and all of its sub-plugins.
+static PLUGIN_STATE: Mutex<Option<Arc<dyn Any + Send + Sync>>> = Mutex::new(None);
+
+/// The name the plugin registered with, if it has been registered.
+pub(crate) fn plugin_name() -> Option<String> {
+    PLUGIN_NAME.lock().unwrap().clone()
+}
 
 /// Defines the functions that *must* be implemented by a version 3 compatible
 /// plugin for directory server.
@@ -39,46 +87,202 @@ pub trait Slapi_Plugin_V3 {
     fn close<T: Slapi_PBlock_V3>( pb: &T ) -> Result<(), PluginOperationError>;
 }
 
+/// Describes a plugin at compile time. This is generated by the
+/// slapi_plugin attribute of slapi_r_plugin_derive as PLUGIN_METADATA on the
+/// plugin type, so that tools can build the plugin configuration entry.
+#[allow(non_camel_case_types)]
+#[derive(Debug)]
+pub struct Slapi_R_Plugin_Metadata {
+    /// The name of the plugin, IE the cn of its configuration entry.
+    pub name: &'static str,
+    /// The nsslapd-pluginType of the plugin, IE preoperation.
+    pub kind: &'static str,
+    /// The version of the plugin crate.
+    pub version: &'static str,
+    /// The description of the plugin crate.
+    pub description: &'static str,
+    /// The callbacks the plugin registers, IE pre_search.
+    pub hooks: &'static [&'static str],
+}
+
+// The type of every callback of Slapi_R_Plugin_FN.
+type Callback = fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>;
+
+// The type of the wrappers that Directory Server calls.
+type Wrapper = extern "C" fn(*const libc::c_void) -> libc::c_int;
+
+/// The callbacks of Slapi_R_Plugin_FN, by the names that callback() accepts.
+pub(crate) const CALLBACKS: &[&str] = &[
+    "start", "close", "post_search", "pre_bind", "pre_unbind", "pre_search",
+    "pre_compare", "pre_modify", "pre_modrdn", "pre_add", "pre_delete",
+    "pre_abandon", "pre_entry", "pre_referal", "pre_result", "pre_extop",
+    "post_bind", "post_unbind", "post_compare", "post_modify", "post_modrdn",
+    "post_add", "post_delete", "post_abandon", "post_entry", "post_referal",
+    "post_result",
+];
+
 ///
 /// Type that represents the possible call backs from a plugin.
 /// This is passed into slapi_pblock-...->plg_private, so that when our
 /// Regisitered proxy functions are called, we are able to look up and access
 /// the correct rust function call backs.
 ///
+/// The slot each callback is registered in depends on the plugin type. The
+/// pre_ callbacks of a betxnpreoperation sub-plugin are registered as
+/// SLAPI_PLUGIN_BE_TXN_PRE_*_FN, the post_ callbacks of an
+/// internalpostoperation sub-plugin as SLAPI_PLUGIN_INTERNAL_POST_*_FN, and so
+/// on. A callback the plugin type has no slot for is a registration error.
+///
 #[allow(non_camel_case_types)]
 pub struct Slapi_R_Plugin_FN {
     /// An option type for a function callback that handles plugin start up.
-    pub start: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub start: Option<Callback>,
     /// An option type for a function callback that handles plugin close down.
-    pub close: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub close: Option<Callback>,
     /// An option type for a function callback that handles post search.
-    pub post_search: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub post_search: Option<Callback>,
     /// An option type for a function callback that handles pre bind
-    pub pre_bind: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_bind: Option<Callback>,
     /// An option type for a function callback that handles pre unbind
-    pub pre_unbind: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_unbind: Option<Callback>,
     /// An option type for a function callback that handles pre search.
-    pub pre_search: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_search: Option<Callback>,
     /// An option type for a function callback that handles pre compare.
-    pub pre_compare: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_compare: Option<Callback>,
     /// An option type for a function callback that handles pre modify.
-    pub pre_modify: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_modify: Option<Callback>,
     /// An option type for a function callback that handles pre modrdn.
-    pub pre_modrdn: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_modrdn: Option<Callback>,
     /// An option type for a function callback that handles pre add.
-    pub pre_add: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_add: Option<Callback>,
     /// An option type for a function callback that handles pre delete.
-    pub pre_delete: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_delete: Option<Callback>,
     /// An option type for a function callback that handles pre abandon.
-    pub pre_abandon: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_abandon: Option<Callback>,
     /// An option type for a function callback that handles pre entry.
-    pub pre_entry: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_entry: Option<Callback>,
     /// An option type for a function callback that handles pre referal.
-    pub pre_referal: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_referal: Option<Callback>,
     /// An option type for a function callback that handles pre result.
-    pub pre_result: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_result: Option<Callback>,
     /// An option type for a function callback that handles pre extop.
-    pub pre_extop: Option<fn(&Slapi_R_PBlock) -> Result<(), PluginOperationError>>,
+    pub pre_extop: Option<Callback>,
+    /// An option type for a function callback that handles post bind.
+    pub post_bind: Option<Callback>,
+    /// An option type for a function callback that handles post unbind.
+    pub post_unbind: Option<Callback>,
+    /// An option type for a function callback that handles post compare.
+    pub post_compare: Option<Callback>,
+    /// An option type for a function callback that handles post modify.
+    pub post_modify: Option<Callback>,
+    /// An option type for a function callback that handles post modrdn.
+    pub post_modrdn: Option<Callback>,
+    /// An option type for a function callback that handles post add.
+    pub post_add: Option<Callback>,
+    /// An option type for a function callback that handles post delete.
+    pub post_delete: Option<Callback>,
+    /// An option type for a function callback that handles post abandon.
+    pub post_abandon: Option<Callback>,
+    /// An option type for a function callback that handles post entry.
+    pub post_entry: Option<Callback>,
+    /// An option type for a function callback that handles post referal.
+    pub post_referal: Option<Callback>,
+    /// An option type for a function callback that handles post result.
+    pub post_result: Option<Callback>,
+}
+
+impl Slapi_R_Plugin_FN {
+    /// Create a set of callbacks where none are registered.
+    pub fn new() -> Slapi_R_Plugin_FN {
+        Slapi_R_Plugin_FN {
+            start: None,
+            close: None,
+            post_search: None,
+            pre_bind: None,
+            pre_unbind: None,
+            pre_search: None,
+            pre_compare: None,
+            pre_modify: None,
+            pre_modrdn: None,
+            pre_add: None,
+            pre_delete: None,
+            pre_abandon: None,
+            pre_entry: None,
+            pre_referal: None,
+            pre_result: None,
+            pre_extop: None,
+            post_bind: None,
+            post_unbind: None,
+            post_compare: None,
+            post_modify: None,
+            post_modrdn: None,
+            post_add: None,
+            post_delete: None,
+            post_abandon: None,
+            post_entry: None,
+            post_referal: None,
+            post_result: None,
+        }
+    }
+
+    /// Returns the callback registered under its field name, IE pre_add, if
+    /// there is one.
+    pub fn callback(&self, name: &str) -> Option<Callback> {
+        match name {
+            "start" => self.start,
+            "close" => self.close,
+            "post_search" => self.post_search,
+            "pre_bind" => self.pre_bind,
+            "pre_unbind" => self.pre_unbind,
+            "pre_search" => self.pre_search,
+            "pre_compare" => self.pre_compare,
+            "pre_modify" => self.pre_modify,
+            "pre_modrdn" => self.pre_modrdn,
+            "pre_add" => self.pre_add,
+            "pre_delete" => self.pre_delete,
+            "pre_abandon" => self.pre_abandon,
+            "pre_entry" => self.pre_entry,
+            "pre_referal" => self.pre_referal,
+            "pre_result" => self.pre_result,
+            "pre_extop" => self.pre_extop,
+            "post_bind" => self.post_bind,
+            "post_unbind" => self.post_unbind,
+            "post_compare" => self.post_compare,
+            "post_modify" => self.post_modify,
+            "post_modrdn" => self.post_modrdn,
+            "post_add" => self.post_add,
+            "post_delete" => self.post_delete,
+            "post_abandon" => self.post_abandon,
+            "post_entry" => self.post_entry,
+            "post_referal" => self.post_referal,
+            "post_result" => self.post_result,
+            _ => None,
+        }
+    }
+}
+
+impl Default for Slapi_R_Plugin_FN {
+    fn default() -> Slapi_R_Plugin_FN {
+        Slapi_R_Plugin_FN::new()
+    }
+}
+
+///
+/// A further plugin registered from the same shared object, such as the
+/// betxnpostoperation half of a plugin that is also a preoperation plugin.
+/// Each sub-plugin has its own type and callbacks, and the callbacks are
+/// registered in the slots of its type. The plugin start and close callbacks
+/// are only called for the main plugin, so the start and close members of
+/// the functions are ignored.
+///
+#[allow(non_camel_case_types)]
+pub struct Slapi_R_Sub_Plugin<'a> {
+    /// The name of the sub-plugin.
+    pub name: &'a str,
+    /// The nsslapd-pluginType of the sub-plugin, IE betxnpostoperation.
+    pub kind: &'a str,
+    /// The set of optional callbacks that the sub-plugin registers.
+    pub functions: Slapi_R_Plugin_FN,
 }
 
 ///
@@ -89,10 +293,26 @@ pub struct Slapi_R_Plugin_FN {
 pub struct Slapi_R_Plugin_Manager<'a> {
     /// The name of the plugin.
     pub name: &'a str,
+    /// The nsslapd-pluginType of the plugin, IE preoperation. This selects
+    /// the slots the callbacks are registered in, and must match the
+    /// configuration entry. Defaults to preoperation.
+    pub kind: &'a str,
     /// The slapi plugin version api that is provided by the plugin. Defaults to 3
     pub version: constants::PluginVersion,
     /// The set of optional callbacks that the plugin registers.
     pub functions: Slapi_R_Plugin_FN,
+    /// Further plugins to register from this shared object.
+    pub sub_plugins: Vec<Slapi_R_Sub_Plugin<'a>>,
+    /// The state shared by the plugin and its sub-plugins. See
+    /// slapi_r_plugin_state.
+    pub state: Option<Arc<dyn Any + Send + Sync>>,
+    /// The nsslapd-pluginprecedence the plugin expects, from 1 to 99. Lower
+    /// values are called first. None does not check the precedence.
+    pub precedence: Option<u32>,
+    /// The plugin types that must be started before this plugin, IE database.
+    pub depends_on_type: Vec<&'a str>,
+    /// The names of the plugins that must be started before this plugin.
+    pub depends_on_named: Vec<&'a str>,
 }
 
 // These are wrappers that we register on a case by case for plugins
@@ -110,28 +330,62 @@ pub struct Slapi_R_Plugin_Manager<'a> {
 /// This allows us to mask complexities of Directory Server interaction from
 /// pure rust plugins, and gives us an avenue of abstraction to create changes
 /// and rewrites in the future.
+///
+/// Every call and every error is counted in the metrics registry as
+/// <cb_name>_calls and <cb_name>_errors, and the time the plugin function
+/// takes is recorded in the <cb_name>_latency histogram. While recording, the pblock and the
+/// result of each call are written to the record file, see the replay module.
+///
+/// This is called for every operation, so it must stay cheap: the plugin
+/// private data is looked up once, and the metrics are looked up on the
+/// first call only, so that no lock is taken and nothing is allocated unless
+/// recording. See benches/dispatch.rs.
 macro_rules! plugin_execute_fn_cb {
     ($cb_name:ident, $slapi_pblock:ident) => {{
+        static CALLS: OnceLock<Arc<metrics::Counter>> = OnceLock::new();
+        static ERRORS: OnceLock<Arc<metrics::Counter>> = OnceLock::new();
+        static LATENCY: OnceLock<Arc<metrics::Histogram>> = OnceLock::new();
+
         let pb: Slapi_R_PBlock = Slapi_R_PBlock::build($slapi_pblock);
+        CALLS.get_or_init(|| metrics::registry().counter(concat!(stringify!($cb_name), "_calls"))).inc();
 
         // Get the plugin private data we have registered to us.
-        if pb.get_plugin_private::<Slapi_R_Plugin_FN>().is_none() {
-            return PluginOperationError::Unknown.as_ds_isize()
-        }
-
-        let fn_ptrs: &Slapi_R_Plugin_FN = pb.get_plugin_private().unwrap();
+        let fn_ptrs: &Slapi_R_Plugin_FN = match pb.get_plugin_private() {
+            Ok(Some(f)) => f,
+            _ => return PluginOperationError::Unknown.as_ds_isize() as libc::c_int,
+        };
 
         let func = match fn_ptrs.$cb_name {
             Some(f) => f,
-            None => return PluginOperationError::Unknown.as_ds_isize(),
+            None => return PluginOperationError::Unknown.as_ds_isize() as libc::c_int,
         };
 
-        let result: Result<(), PluginOperationError> = func(&pb);
+        // Record the pblock before the plugin can change it.
+        let record = if replay::slapi_r_record_is_enabled() {
+            Some(replay::CallbackRecord::from_pblock(stringify!($cb_name), &pb))
+        } else {
+            None
+        };
+
+        let result: Result<(), PluginOperationError> = LATENCY
+            .get_or_init(|| metrics::registry().histogram(concat!(stringify!($cb_name), "_latency")))
+            .time(|| func(&pb));
         // Unwrap the result, and give it to DS in a way it can understand.
-        match result {
-            Ok(_) => constants::LDAP_SUCCESS,
-            Err(err) => err.as_ds_isize(),
+        let rc = match result {
+            Ok(_) => constants::LDAP_SUCCESS as libc::c_int,
+            Err(err) => {
+                ERRORS.get_or_init(|| metrics::registry().counter(concat!(stringify!($cb_name), "_errors"))).inc();
+                err.as_ds_isize() as libc::c_int
+            }
+        };
+
+        if let Some(mut record) = record {
+            record.result = rc as isize;
+            if let Err(e) = replay::slapi_r_record_write(&record) {
+                let _ = slapi_r_log_error(LogLevel::WARNING, SUBSYSTEM, format!("Stopped recording callbacks: {:?}\n", e));
+            }
         }
+        rc
     }};
 }
 
@@ -139,112 +393,191 @@ macro_rules! plugin_execute_fn_cb {
 /// A callback wrapper for starting the plugin. This allows the
 /// slapi_r_plugin_manager to start it's own internals, as well
 /// as allowing the plugin itself to start up and setup any data
-/// structures that it may require.
-extern fn slapi_r_plugin_start_cb(slapi_pblock: *const libc::c_void) -> isize {
+/// structures that it may require. The metrics registry is served as the
+/// plugin monitor entry from this point, and the plugin does not start if
+/// the monitor entry can not be created.
+extern "C" fn slapi_r_plugin_start_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    let pb: Slapi_R_PBlock = Slapi_R_PBlock::build(slapi_pblock);
+    let name = match *PLUGIN_NAME.lock().unwrap() {
+        Some(ref n) => n.clone(),
+        None => SUBSYSTEM.to_string(),
+    };
+    let monitor = match pb.get_plugin_identity() {
+        Some(identity) => metrics::slapi_r_monitor_start(&name, identity),
+        None => Err(PluginOperationError::Unknown),
+    };
+    if let Err(e) = monitor {
+        let _ = slapi_r_log_error(LogLevel::ERR, SUBSYSTEM, format!("Unable to create monitor entry for {}\n", name));
+        return e.as_ds_isize() as libc::c_int;
+    }
     plugin_execute_fn_cb!(start, slapi_pblock)
 }
 
 /// A callback wrapper for stopping the plugin. This allows the
 /// slapi_r_plugin_manager to free structures, such as the plugin
-/// private data, and allows the plugin itself to close down.
+/// private data, and allows the plugin itself to close down. Any work the
+/// plugin scheduled on the event queue is cancelled first, and new tasks
+/// are rejected.
 /// This does *not* use the plugin_execute_fn_cb as it requires access to
 /// destroy the plugin private data which the macro obscures.
-extern fn slapi_r_plugin_close_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_close_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     let pb: Slapi_R_PBlock = Slapi_R_PBlock::build(slapi_pblock);
 
-    // First check if the plugin actually has any call backs to call on close
-    let result_f = if !pb.get_plugin_private::<Slapi_R_Plugin_FN>().is_none() {
-        let fn_ptrs: &Slapi_R_Plugin_FN = pb.get_plugin_private().unwrap();
+    // Stop any scheduled work, so that nothing can run while we tear down.
+    eventq::cancel_all();
+    task::unregister_all();
+    metrics::slapi_r_monitor_stop();
+    replay::slapi_r_record_stop();
 
-        // TODO: Rewrite the other function callers in post search to use this
-        match fn_ptrs.close {
-            Some(f) => f(&pb),
-            None => Err(PluginOperationError::Unknown),
+    // First check if the plugin actually has any call backs to call on close
+    let result_f = match pb.get_plugin_private::<Slapi_R_Plugin_FN>() {
+        Ok(Some(fn_ptrs)) => {
+            match fn_ptrs.close {
+                Some(f) => f(&pb),
+                None => Err(PluginOperationError::Unknown),
+            }
         }
-    } else {
-        Ok(())
+        Ok(None) => Ok(()),
+        Err(_) => Err(PluginOperationError::Unknown),
     };
 
 
     let result_p = pb.destroy_plugin_private();
+    // Drop the shared state once no callback can use it.
+    *PLUGIN_STATE.lock().unwrap() = None;
 
     match (result_f, result_p) {
-        (Ok(_), Ok(_)) => constants::LDAP_SUCCESS,
-        (Ok(_), Err(e)) => e.as_ds_isize(),
-        (Err(e), _) => e.as_ds_isize(),
+        (Ok(_), Ok(_)) => constants::LDAP_SUCCESS as libc::c_int,
+        (Ok(_), Err(e)) => e.as_ds_isize() as libc::c_int,
+        (Err(e), _) => e.as_ds_isize() as libc::c_int,
     }
 }
 
 /// The callback wrapper for post_search
-extern fn slapi_r_plugin_post_search_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_post_search_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(post_search, slapi_pblock)
 }
 
 /// The callback wrapper for pre_bind.
-extern fn slapi_r_plugin_pre_bind_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_bind_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_bind, slapi_pblock)
 }
 
 /// The callback wrapper for pre_unbind
-extern fn slapi_r_plugin_pre_unbind_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_unbind_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_unbind, slapi_pblock)
 }
 
 /// The callback wrapper for pre_search_fn
-extern fn slapi_r_plugin_pre_search_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_search_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_search, slapi_pblock)
 }
 
 /// The callback wrapper for pre_compare
-extern fn slapi_r_plugin_pre_compare_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_compare_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_compare, slapi_pblock)
 }
 
 /// The callback wrapper for pre_modify
-extern fn slapi_r_plugin_pre_modify_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_modify_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_modify, slapi_pblock)
 }
 
 /// The callback wrapper for pre_modrdn
-extern fn slapi_r_plugin_pre_modrdn_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_modrdn_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_modrdn, slapi_pblock)
 }
 
 /// The callback wrapper for pre_add
-extern fn slapi_r_plugin_pre_add_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_add_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_add, slapi_pblock)
 }
 
 /// The callback wrapper for pre_delete
-extern fn slapi_r_plugin_pre_delete_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_delete_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_delete, slapi_pblock)
 }
 
 /// The callback wrapper for pre_abandon
-extern fn slapi_r_plugin_pre_abandon_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_abandon_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_abandon, slapi_pblock)
 }
 
 /// The callback wrapper for pre_entry
-extern fn slapi_r_plugin_pre_entry_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_entry_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_entry, slapi_pblock)
 }
 
 /// The callback wrapper for pre_referal
-extern fn slapi_r_plugin_pre_referal_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_referal_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_referal, slapi_pblock)
 }
 
 /// The callback wrapper for pre_result
-extern fn slapi_r_plugin_pre_result_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_result_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_result, slapi_pblock)
 }
 
 /// The callback wrapper for pre_extop
-extern fn slapi_r_plugin_pre_extop_cb(slapi_pblock: *const libc::c_void) -> isize {
+extern "C" fn slapi_r_plugin_pre_extop_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
     plugin_execute_fn_cb!(pre_extop, slapi_pblock)
 }
 
+/// The callback wrapper for post_bind
+extern "C" fn slapi_r_plugin_post_bind_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_bind, slapi_pblock)
+}
+
+/// The callback wrapper for post_unbind
+extern "C" fn slapi_r_plugin_post_unbind_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_unbind, slapi_pblock)
+}
+
+/// The callback wrapper for post_compare
+extern "C" fn slapi_r_plugin_post_compare_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_compare, slapi_pblock)
+}
+
+/// The callback wrapper for post_modify
+extern "C" fn slapi_r_plugin_post_modify_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_modify, slapi_pblock)
+}
+
+/// The callback wrapper for post_modrdn
+extern "C" fn slapi_r_plugin_post_modrdn_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_modrdn, slapi_pblock)
+}
+
+/// The callback wrapper for post_add
+extern "C" fn slapi_r_plugin_post_add_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_add, slapi_pblock)
+}
+
+/// The callback wrapper for post_delete
+extern "C" fn slapi_r_plugin_post_delete_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_delete, slapi_pblock)
+}
+
+/// The callback wrapper for post_abandon
+extern "C" fn slapi_r_plugin_post_abandon_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_abandon, slapi_pblock)
+}
+
+/// The callback wrapper for post_entry
+extern "C" fn slapi_r_plugin_post_entry_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_entry, slapi_pblock)
+}
+
+/// The callback wrapper for post_referal
+extern "C" fn slapi_r_plugin_post_referal_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_referal, slapi_pblock)
+}
+
+/// The callback wrapper for post_result
+extern "C" fn slapi_r_plugin_post_result_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    plugin_execute_fn_cb!(post_result, slapi_pblock)
+}
+
 
 impl<'a> Slapi_R_Plugin_Manager<'a> {
     /// Builds a new Slapi_R_Plugin_Manager. The Rust plugin can then set values
@@ -252,106 +585,142 @@ impl<'a> Slapi_R_Plugin_Manager<'a> {
     /// plugins initialisation.
     pub fn new() -> Slapi_R_Plugin_Manager<'a> {
 
-        let srpf: Slapi_R_Plugin_FN = Slapi_R_Plugin_FN{
-            start: None,
-            close: None,
-            post_search: None,
-            pre_bind: None,
-            pre_unbind: None,
-            pre_search: None,
-            pre_compare: None,
-            pre_modify: None,
-            pre_modrdn: None,
-            pre_add: None,
-            pre_delete: None,
-            pre_abandon: None,
-            pre_entry: None,
-            pre_referal: None,
-            pre_result: None,
-            pre_extop: None,
-        };
-
         Slapi_R_Plugin_Manager {
             name: SUBSYSTEM,
+            kind: DEFAULT_KIND,
             version: constants::PluginVersion::_03,
-            functions: srpf,
+            functions: Slapi_R_Plugin_FN::new(),
+            sub_plugins: Vec::new(),
+            state: None,
+            precedence: None,
+            depends_on_type: Vec::new(),
+            depends_on_named: Vec::new(),
         }
     }
 
-    /// Completes the registration to Directory Server of the plugin. This is
-    /// the *last* function you call when building a plugin in a plugin init.
-    pub fn register<T: Slapi_PBlock_Init_V3>(self, pb: T) -> Result<(), PluginRegistrationError> {
-
-        match slapi_r_log_error(
-            LogLevel::FATAL,
-            SUBSYSTEM,
-            format!("Registering a rust plugin wrapper\n")
-        ) {
-            Ok(_) => {},
-            Err(_) => return Err(PluginRegistrationError::LoggingError),
-        };
-
-        // Set the plugin api version
-        pb.set_plugin_version(self.version as isize);
-
-        // Set description:
-        // I think this is optional ...
-
-        if self.functions.post_search.is_some() {
-            pb.set_plugin_post_search_fn(slapi_r_plugin_post_search_cb)
-        }
-
-        if self.functions.pre_bind.is_some() {
-            pb.set_plugin_pre_bind_fn(slapi_r_plugin_pre_bind_cb)
+    /// Compare the precedence and dependencies the plugin declared with those
+    /// of its configuration entry, returning a message for each difference.
+    /// Directory Server orders plugins from the configuration, so a
+    /// difference means the plugin may run before a plugin it relies on.
+    pub fn config_mismatches(&self, config: &Entry) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if let Some(precedence) = self.precedence {
+            let configured = match config.get_attribute_value(PRECEDENCE_ATTR) {
+                Some(v) => v.trim().parse::<u32>().ok(),
+                None => Some(DEFAULT_PRECEDENCE),
+            };
+            if configured != Some(precedence) {
+                mismatches.push(format!("{} is {} but {} is declared",
+                                        PRECEDENCE_ATTR,
+                                        config.get_attribute_value(PRECEDENCE_ATTR).unwrap_or(DEFAULT_PRECEDENCE.to_string()),
+                                        precedence));
+            }
         }
+        compare_values(config, DEPENDS_ON_TYPE_ATTR, &self.depends_on_type, &mut mismatches);
+        compare_values(config, DEPENDS_ON_NAMED_ATTR, &self.depends_on_named, &mut mismatches);
+        mismatches
+    }
 
-        if self.functions.pre_unbind.is_some() {
-            pb.set_plugin_pre_unbind_fn(slapi_r_plugin_pre_unbind_cb)
-        }
+    /// Register a further plugin of another type from this shared object.
+    /// It is registered with Directory Server after the main plugin.
+    pub fn sub_plugin(&mut self, name: &'a str, kind: &'a str, functions: Slapi_R_Plugin_FN) {
+        self.sub_plugins.push(Slapi_R_Sub_Plugin {
+            name,
+            kind,
+            functions,
+        });
+    }
 
-        if self.functions.pre_search.is_some() {
-            pb.set_plugin_pre_search_fn(slapi_r_plugin_pre_search_cb)
-        }
+    /// Set the state that the plugin and its sub-plugins share. It is
+    /// available from slapi_r_plugin_state until the plugin closes.
+    pub fn set_state<S: Any + Send + Sync>(&mut self, state: S) {
+        self.state = Some(Arc::new(state));
+    }
 
-        if self.functions.pre_compare.is_some() {
-            pb.set_plugin_pre_compare_fn(slapi_r_plugin_pre_compare_cb)
-        }
+    /// Completes the registration to Directory Server of the plugin. This is
+    /// the *last* function you call when building a plugin in a plugin init.
+    ///
+    /// Every callback of the plugin and its sub-plugins is checked before
+    /// anything is registered. Directory Server may still refuse a
+    /// sub-plugin, and it has no way to remove a plugin once registered, so
+    /// the sub-plugins before it stay registered while this returns an error.
+    /// Their callbacks still run, but slapi_r_plugin_state returns None as the
+    /// shared state is freed. The sub-plugins that remain are logged.
+    pub fn register<T: Slapi_PBlock_Init_V3>(self, pb: T) -> Result<(), PluginRegistrationError> {
 
-        if self.functions.pre_modify.is_some() {
-            pb.set_plugin_pre_modify_fn(slapi_r_plugin_pre_modify_cb)
+        slapi_r_log_init();
+
+        if slapi_r_log_is_enabled(LogLevel::PLUGIN) {
+            match slapi_r_log_error(
+                LogLevel::PLUGIN,
+                SUBSYSTEM,
+                "Registering a rust plugin wrapper\n".to_string()
+            ) {
+                Ok(_) => {},
+                Err(_) => return Err(PluginRegistrationError::LoggingError),
+            };
         }
 
-        if self.functions.pre_modrdn.is_some() {
-            pb.set_plugin_pre_modrdn_fn(slapi_r_plugin_pre_modrdn_cb)
-        }
+        *PLUGIN_NAME.lock().unwrap() = Some(self.name.to_string());
 
-        if self.functions.pre_add.is_some() {
-            pb.set_plugin_pre_add_fn(slapi_r_plugin_pre_add_cb)
+        if slapi_r_log_is_enabled(LogLevel::PLUGIN) {
+            let capabilities: Vec<&str> = slapi_r_capabilities().iter().map(|c| c.as_str()).collect();
+            let _ = slapi_r_log_error(LogLevel::PLUGIN, SUBSYSTEM, format!("Server version {} provides {}\n",
+                                      slapi_r_server_version().unwrap_or("unknown".to_string()),
+                                      capabilities.join(", ")));
         }
 
-        if self.functions.pre_delete.is_some() {
-            pb.set_plugin_pre_delete_fn(slapi_r_plugin_pre_delete_cb)
+        // Select the structured log format and the record file from the
+        // plugin configuration, and warn if the configuration orders the plugin differently to
+        // what the plugin declared.
+        if let Some(config) = pb.get_plugin_config_entry() {
+            for m in self.config_mismatches(&config.to_entry()) {
+                let _ = slapi_r_log_error(LogLevel::WARNING, SUBSYSTEM, format!("{}: {}\n", self.name, m));
+            }
+            if let Some(value) = config.get_attribute_value(LOG_FORMAT_ATTR) {
+                match LogFormat::from_config(&value) {
+                    Some(format) => slapi_r_log_set_format(format),
+                    None => {
+                        let _ = slapi_r_log_error(LogLevel::WARNING, SUBSYSTEM, format!("Unknown {} {}, using keyvalue\n", LOG_FORMAT_ATTR, value));
+                    }
+                }
+            }
+            if let Some(path) = config.get_attribute_value(RECORD_FILE_ATTR) {
+                match replay::slapi_r_record_start(Path::new(path.trim())) {
+                    Ok(_) => {
+                        let _ = slapi_r_log_error(LogLevel::WARNING, SUBSYSTEM, format!("Recording callbacks to {}\n", path.trim()));
+                    }
+                    Err(e) => {
+                        let _ = slapi_r_log_error(LogLevel::WARNING, SUBSYSTEM, format!("Unable to record callbacks: {:?}\n", e));
+                    }
+                }
+            }
         }
 
-        if self.functions.pre_abandon.is_some() {
-            pb.set_plugin_pre_abandon_fn(slapi_r_plugin_pre_abandon_cb)
+        if !self.sub_plugins.is_empty() && !Capability::SubPlugins.is_available() {
+            let _ = slapi_r_log_error(LogLevel::ERR, SUBSYSTEM, format!("{}: this server can not register sub-plugins\n", self.name));
+            return Err(PluginRegistrationError::SubPlugin(self.sub_plugins[0].name.to_string()));
         }
 
-        if self.functions.pre_entry.is_some() {
-            pb.set_plugin_pre_entry_fn(slapi_r_plugin_pre_entry_cb)
+        // Check every callback has a slot before anything is registered.
+        callback_slots(self.kind, &self.functions)?;
+        for sub in self.sub_plugins.iter() {
+            if sub.name.contains('\0') {
+                return Err(PluginRegistrationError::SubPlugin(sub.name.to_string()));
+            }
+            if let Err(e) = callback_slots(sub.kind, &sub.functions) {
+                let _ = slapi_r_log_error(LogLevel::ERR, SUBSYSTEM, format!("{}: {:?}\n", sub.name, e));
+                return Err(e);
+            }
         }
 
-        if self.functions.pre_referal.is_some() {
-            pb.set_plugin_pre_referal_fn(slapi_r_plugin_pre_referal_cb)
-        }
+        // Set the plugin api version
+        pb.set_plugin_version(self.version);
 
-        if self.functions.pre_result.is_some() {
-            pb.set_plugin_pre_result_fn(slapi_r_plugin_pre_result_cb)
-        }
+        // Set description:
+        // I think this is optional ...
 
-        if self.functions.pre_extop.is_some() {
-            pb.set_plugin_pre_extop_fn(slapi_r_plugin_pre_extop_cb)
-        }
+        set_callbacks(&pb, self.kind, &self.functions)?;
 
         // We always register the start and close functions: We have some
         // checks in place to see if the rust plugin actually needs to use them
@@ -362,35 +731,464 @@ impl<'a> Slapi_R_Plugin_Manager<'a> {
         // Finally,we set a private structure of the functions we had registered
         pb.set_plugin_private(self.functions);
 
-        // Only if the plugin implements lots of be_types do we
-        // need slapi_plugin_register.
-
-        // For now, a single plugin can register standalone.
+        *PLUGIN_STATE.lock().unwrap() = self.state;
+
+        // Sub-plugins are grouped under our identity, so that they share
+        // our configuration.
+        let identity = pb.get_plugin_identity().unwrap_or(ptr::null()) as *mut libc::c_void;
+        let initsymbol = CString::new("slapi_r_sub_plugin_init_cb").unwrap();
+        let mut registered: Vec<&str> = Vec::new();
+        for sub in self.sub_plugins {
+            let c_kind = CString::new(sub.kind).unwrap();
+            let c_name = CString::new(sub.name).unwrap();
+            *PENDING_SUB_PLUGIN.lock().unwrap() = Some((sub.kind.to_string(), sub.functions));
+            let rc = unsafe {
+                sys::slapi_register_plugin(c_kind.as_ptr(), 1, initsymbol.as_ptr(), Some(slapi_r_sub_plugin_init_cb),
+                                           c_name.as_ptr(), ptr::null_mut(), identity)
+            };
+            // If Directory Server never called the init function, the
+            // functions are still pending.
+            let pending = PENDING_SUB_PLUGIN.lock().unwrap().take();
+            if rc != 0 || pending.is_some() {
+                // Directory Server will not start or close the main plugin,
+                // so free what it would have freed on close.
+                let _ = pb.destroy_plugin_private();
+                *PLUGIN_STATE.lock().unwrap() = None;
+                let _ = slapi_r_log_error(LogLevel::ERR, SUBSYSTEM, format!("{}: sub-plugin {} was refused\n", self.name, sub.name));
+                if !registered.is_empty() {
+                    let _ = slapi_r_log_error(LogLevel::ERR, SUBSYSTEM,
+                                              format!("{}: sub-plugins {} remain registered without the plugin\n",
+                                                      self.name, registered.join(", ")));
+                }
+                return Err(PluginRegistrationError::SubPlugin(sub.name.to_string()));
+            }
+            registered.push(sub.name);
+        }
 
-        // If something went wrong, return this:
-        //Err(error::PluginRegistrationError::Unknown)
         Ok(())
     }
 
 }
 
+impl<'a> Default for Slapi_R_Plugin_Manager<'a> {
+    fn default() -> Slapi_R_Plugin_Manager<'a> {
+        Slapi_R_Plugin_Manager::new()
+    }
+}
+
+/// Add a mismatch for each value of a multi-valued attribute that is only
+/// declared or only configured. Values are compared without case.
+fn compare_values(config: &Entry, attr: &str, declared: &[&str], mismatches: &mut Vec<String>) {
+    let configured: Vec<String> = config
+        .get_values(attr)
+        .unwrap_or(&[])
+        .iter()
+        .map(|v| String::from_utf8_lossy(v).to_lowercase())
+        .collect();
+    for d in declared.iter() {
+        if !configured.contains(&d.to_lowercase()) {
+            mismatches.push(format!("{} {} is declared but not configured", attr, d));
+        }
+    }
+    for c in configured.iter() {
+        if !declared.iter().any(|d| d.to_lowercase() == *c) {
+            mismatches.push(format!("{} {} is configured but not declared", attr, c));
+        }
+    }
+}
+
+/// The callback wrapper that dispatches to the named callback.
+fn callback_wrapper(callback: &str) -> Option<Wrapper> {
+    let wrapper: Wrapper = match callback {
+        "pre_bind" => slapi_r_plugin_pre_bind_cb,
+        "pre_unbind" => slapi_r_plugin_pre_unbind_cb,
+        "pre_search" => slapi_r_plugin_pre_search_cb,
+        "pre_compare" => slapi_r_plugin_pre_compare_cb,
+        "pre_modify" => slapi_r_plugin_pre_modify_cb,
+        "pre_modrdn" => slapi_r_plugin_pre_modrdn_cb,
+        "pre_add" => slapi_r_plugin_pre_add_cb,
+        "pre_delete" => slapi_r_plugin_pre_delete_cb,
+        "pre_abandon" => slapi_r_plugin_pre_abandon_cb,
+        "pre_entry" => slapi_r_plugin_pre_entry_cb,
+        "pre_referal" => slapi_r_plugin_pre_referal_cb,
+        "pre_result" => slapi_r_plugin_pre_result_cb,
+        "pre_extop" => slapi_r_plugin_pre_extop_cb,
+        "post_search" => slapi_r_plugin_post_search_cb,
+        "post_bind" => slapi_r_plugin_post_bind_cb,
+        "post_unbind" => slapi_r_plugin_post_unbind_cb,
+        "post_compare" => slapi_r_plugin_post_compare_cb,
+        "post_modify" => slapi_r_plugin_post_modify_cb,
+        "post_modrdn" => slapi_r_plugin_post_modrdn_cb,
+        "post_add" => slapi_r_plugin_post_add_cb,
+        "post_delete" => slapi_r_plugin_post_delete_cb,
+        "post_abandon" => slapi_r_plugin_post_abandon_cb,
+        "post_entry" => slapi_r_plugin_post_entry_cb,
+        "post_referal" => slapi_r_plugin_post_referal_cb,
+        "post_result" => slapi_r_plugin_post_result_cb,
+        _ => return None,
+    };
+    Some(wrapper)
+}
+
+/// The slot a callback is registered in for a plugin of the given
+/// nsslapd-pluginType. None is returned if that type of plugin can not
+/// register the callback. Directory Server only calls the backend and
+/// internal operation plugins for add, modify, modrdn and delete.
+fn callback_slot(kind: &str, callback: &str) -> Option<isize> {
+    let (phase, op) = match callback.find('_') {
+        Some(i) => (&callback[..i], &callback[i + 1..]),
+        None => return None,
+    };
+    let slot = match (kind.to_lowercase().as_str(), phase) {
+        ("preoperation", "pre") => match op {
+            "bind" => constants::SLAPI_PLUGIN_PRE_BIND_FN,
+            "unbind" => constants::SLAPI_PLUGIN_PRE_UNBIND_FN,
+            "search" => constants::SLAPI_PLUGIN_PRE_SEARCH_FN,
+            "compare" => constants::SLAPI_PLUGIN_PRE_COMPARE_FN,
+            "modify" => constants::SLAPI_PLUGIN_PRE_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_PRE_MODRDN_FN,
+            "add" => constants::SLAPI_PLUGIN_PRE_ADD_FN,
+            "delete" => constants::SLAPI_PLUGIN_PRE_DELETE_FN,
+            "abandon" => constants::SLAPI_PLUGIN_PRE_ABANDON_FN,
+            "entry" => constants::SLAPI_PLUGIN_PRE_ENTRY_FN,
+            "referal" => constants::SLAPI_PLUGIN_PRE_REFERAL_FN,
+            "result" => constants::SLAPI_PLUGIN_PRE_RESULT_FN,
+            "extop" => constants::SLAPI_PLUGIN_PRE_EXTOP_FN,
+            _ => return None,
+        },
+        // Plugins have always registered post_search alongside their
+        // preoperation callbacks.
+        ("preoperation", "post") if op == "search" => constants::SLAPI_PLUGIN_POST_SEARCH_FN,
+        ("postoperation", "post") => match op {
+            "bind" => constants::SLAPI_PLUGIN_POST_BIND_FN,
+            "unbind" => constants::SLAPI_PLUGIN_POST_UNBIND_FN,
+            "search" => constants::SLAPI_PLUGIN_POST_SEARCH_FN,
+            "compare" => constants::SLAPI_PLUGIN_POST_COMPARE_FN,
+            "modify" => constants::SLAPI_PLUGIN_POST_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_POST_MODRDN_FN,
+            "add" => constants::SLAPI_PLUGIN_POST_ADD_FN,
+            "delete" => constants::SLAPI_PLUGIN_POST_DELETE_FN,
+            "abandon" => constants::SLAPI_PLUGIN_POST_ABANDON_FN,
+            "entry" => constants::SLAPI_PLUGIN_POST_ENTRY_FN,
+            "referal" => constants::SLAPI_PLUGIN_POST_REFERAL_FN,
+            "result" => constants::SLAPI_PLUGIN_POST_RESULT_FN,
+            _ => return None,
+        },
+        ("bepreoperation", "pre") => match op {
+            "add" => constants::SLAPI_PLUGIN_BE_PRE_ADD_FN,
+            "modify" => constants::SLAPI_PLUGIN_BE_PRE_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_BE_PRE_MODRDN_FN,
+            "delete" => constants::SLAPI_PLUGIN_BE_PRE_DELETE_FN,
+            _ => return None,
+        },
+        ("bepostoperation", "post") => match op {
+            "add" => constants::SLAPI_PLUGIN_BE_POST_ADD_FN,
+            "modify" => constants::SLAPI_PLUGIN_BE_POST_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_BE_POST_MODRDN_FN,
+            "delete" => constants::SLAPI_PLUGIN_BE_POST_DELETE_FN,
+            _ => return None,
+        },
+        ("betxnpreoperation", "pre") => match op {
+            "add" => constants::SLAPI_PLUGIN_BE_TXN_PRE_ADD_FN,
+            "modify" => constants::SLAPI_PLUGIN_BE_TXN_PRE_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_BE_TXN_PRE_MODRDN_FN,
+            "delete" => constants::SLAPI_PLUGIN_BE_TXN_PRE_DELETE_FN,
+            _ => return None,
+        },
+        ("betxnpostoperation", "post") => match op {
+            "add" => constants::SLAPI_PLUGIN_BE_TXN_POST_ADD_FN,
+            "modify" => constants::SLAPI_PLUGIN_BE_TXN_POST_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_BE_TXN_POST_MODRDN_FN,
+            "delete" => constants::SLAPI_PLUGIN_BE_TXN_POST_DELETE_FN,
+            _ => return None,
+        },
+        ("internalpreoperation", "pre") => match op {
+            "add" => constants::SLAPI_PLUGIN_INTERNAL_PRE_ADD_FN,
+            "modify" => constants::SLAPI_PLUGIN_INTERNAL_PRE_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_INTERNAL_PRE_MODRDN_FN,
+            "delete" => constants::SLAPI_PLUGIN_INTERNAL_PRE_DELETE_FN,
+            _ => return None,
+        },
+        ("internalpostoperation", "post") => match op {
+            "add" => constants::SLAPI_PLUGIN_INTERNAL_POST_ADD_FN,
+            "modify" => constants::SLAPI_PLUGIN_INTERNAL_POST_MODIFY_FN,
+            "modrdn" => constants::SLAPI_PLUGIN_INTERNAL_POST_MODRDN_FN,
+            "delete" => constants::SLAPI_PLUGIN_INTERNAL_POST_DELETE_FN,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    // The betxn slots are only known to servers with backend transactions.
+    if !slapi_r_slot_available(slot) {
+        return None;
+    }
+    Some(slot)
+}
+
+/// Returns the slot and wrapper of each callback the plugin provides, or an
+/// error naming the first callback that a plugin of this type can not
+/// register. start and close are registered separately.
+fn callback_slots(kind: &str, functions: &Slapi_R_Plugin_FN) -> Result<Vec<(isize, Wrapper)>, PluginRegistrationError> {
+    let mut slots = Vec::new();
+    for name in CALLBACKS.iter().filter(|&&n| n != "start" && n != "close") {
+        if functions.callback(name).is_none() {
+            continue;
+        }
+        match (callback_slot(kind, name), callback_wrapper(name)) {
+            (Some(slot), Some(wrapper)) => slots.push((slot, wrapper)),
+            _ => return Err(PluginRegistrationError::UnsupportedCallback(kind.to_string(), name.to_string())),
+        }
+    }
+    Ok(slots)
+}
+
+/// Set the callback wrappers into the pblock for each callback the plugin
+/// provides, in the slots of the plugin type.
+fn set_callbacks<T: Slapi_PBlock_Init_V3>(pb: &T, kind: &str, functions: &Slapi_R_Plugin_FN) -> Result<(), PluginRegistrationError> {
+    for (slot, wrapper) in callback_slots(kind, functions)? {
+        pb.set_plugin_fn(slot, wrapper);
+    }
+    Ok(())
+}
+
+/// The init function of every sub-plugin. Directory Server calls this from
+/// within slapi_register_plugin, so the callbacks to register are those that
+/// are pending.
+extern "C" fn slapi_r_sub_plugin_init_cb(slapi_pblock: *mut sys::Slapi_PBlock) -> libc::c_int {
+    let pb: Slapi_R_PBlock = Slapi_R_PBlock::build(slapi_pblock as *const libc::c_void);
+    let (kind, functions) = match PENDING_SUB_PLUGIN.lock().unwrap().take() {
+        Some(f) => f,
+        None => return PluginRegistrationError::Unknown.as_ds_isize() as libc::c_int,
+    };
+    pb.set_plugin_version(constants::PluginVersion::_03);
+    if let Err(e) = set_callbacks(&pb, &kind, &functions) {
+        return e.as_ds_isize() as libc::c_int;
+    }
+    pb.set_plugin_close_fn(slapi_r_sub_plugin_close_cb);
+    pb.set_plugin_private(functions);
+    constants::LDAP_SUCCESS as libc::c_int
+}
+
+/// The close function of every sub-plugin. This only frees the callbacks of
+/// the sub-plugin, as the plugin close callback is called by the main plugin.
+extern "C" fn slapi_r_sub_plugin_close_cb(slapi_pblock: *const libc::c_void) -> libc::c_int {
+    let pb: Slapi_R_PBlock = Slapi_R_PBlock::build(slapi_pblock);
+    match pb.destroy_plugin_private() {
+        Ok(_) => constants::LDAP_SUCCESS as libc::c_int,
+        Err(e) => e.as_ds_isize() as libc::c_int,
+    }
+}
+
+/// Returns the state that the plugin set with
+/// Slapi_R_Plugin_Manager::set_state, so that the callbacks of the plugin and
+/// all of its sub-plugins can share it. None is returned if no state was set,
+/// if it is not of type S, or once the plugin has closed.
+pub fn slapi_r_plugin_state<S: Any + Send + Sync>() -> Option<Arc<S>> {
+    match *PLUGIN_STATE.lock().unwrap() {
+        Some(ref state) => state.clone().downcast::<S>().ok(),
+        None => None,
+    }
+}
+
 /// This macro is imported by plugins to wrap their rust init functions.
 /// This allows complete transparency to C types to the rust plugin.
 #[macro_export]
 macro_rules! slapi_r_plugin_init {
     ( $plugin_type:ident ) => (
-        extern crate libc;
-        use slapi_r_plugin::pblock::Slapi_R_PBlock;
         /// A static C function exported from the .so that Directory Server can
         /// find to complete plugin registration.
         #[no_mangle]
-        pub extern fn slapi_r_plugin_init_fn(slapi_pblock: *mut libc::c_void) -> isize {
-            let pb: Slapi_R_PBlock = Slapi_R_PBlock::build(slapi_pblock);
-            match <$plugin_type as Slapi_Plugin_V3>::init(pb) {
-                Ok(_) => constants::LDAP_SUCCESS,
-                Err(e) => return e.as_ds_isize(),
+        pub extern "C" fn slapi_r_plugin_init_fn(slapi_pblock: *mut $crate::libc::c_void) -> $crate::libc::c_int {
+            let pb: $crate::pblock::Slapi_R_PBlock = $crate::pblock::Slapi_R_PBlock::build(slapi_pblock);
+            match <$plugin_type as $crate::plugin::Slapi_Plugin_V3>::init(pb) {
+                Ok(_) => $crate::constants::LDAP_SUCCESS as $crate::libc::c_int,
+                Err(e) => return e.as_ds_isize() as $crate::libc::c_int,
             }
         }
     );
 }
 
+#[test]
+fn test_plugin_manager_config_mismatches() {
+    let mut manager = Slapi_R_Plugin_Manager::new();
+    manager.precedence = Some(40);
+    manager.depends_on_type = vec!["database"];
+    manager.depends_on_named = vec!["attribute uniqueness"];
+
+    let config = Entry::new("cn=test,cn=plugins,cn=config")
+        .with_value(PRECEDENCE_ATTR, "40")
+        .with_value(DEPENDS_ON_TYPE_ATTR, "Database")
+        .with_value(DEPENDS_ON_NAMED_ATTR, "attribute uniqueness");
+    assert!(manager.config_mismatches(&config).is_empty());
+
+    let config = Entry::new("cn=test,cn=plugins,cn=config")
+        .with_value(DEPENDS_ON_NAMED_ATTR, "referential integrity postoperation");
+    let mismatches = manager.config_mismatches(&config);
+    assert_eq!(mismatches.len(), 4);
+    assert_eq!(mismatches[0], "nsslapd-pluginprecedence is 50 but 40 is declared");
+}
+
+// Counts the allocations of each thread, so that test_plugin_dispatch can
+// check that calling a callback does not allocate.
+#[cfg(all(test, slapi_r_mock))]
+mod alloc_count {
+    use std::alloc::GlobalAlloc;
+    use std::alloc::Layout;
+    use std::alloc::System;
+    use std::cell::Cell;
+
+    thread_local!(static ALLOCATIONS: Cell<usize> = const { Cell::new(0) });
+
+    struct CountingAlloc;
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    /// The number of allocations this thread has made.
+    pub fn allocations() -> usize {
+        ALLOCATIONS.with(|a| a.get())
+    }
+}
+
+// Registration sets the plugin name and state, so tests that register a
+// plugin must not run at the same time.
+#[cfg(all(test, slapi_r_mock))]
+static REGISTER_LOCK: Mutex<()> = Mutex::new(());
+
+#[cfg(slapi_r_mock)]
+#[test]
+fn test_plugin_dispatch() {
+    use super::mock;
+    let _lock = REGISTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    fn allow(_: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        Ok(())
+    }
+
+    fn reject(_: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        Err(PluginOperationError::UnwillingToPerform)
+    }
+
+    let mut manager = Slapi_R_Plugin_Manager::new();
+    manager.functions.pre_search = Some(allow);
+    manager.functions.pre_add = Some(reject);
+    let pb = mock::register(manager).unwrap();
+
+    assert_eq!(mock::call_plugin_fn(&pb, constants::SLAPI_PLUGIN_PRE_SEARCH_FN), Some(constants::LDAP_SUCCESS));
+    assert_eq!(mock::call_plugin_fn(&pb, constants::SLAPI_PLUGIN_PRE_ADD_FN), Some(constants::LDAP_UNWILLING_TO_PERFORM));
+    assert_eq!(mock::call_plugin_fn(&pb, constants::SLAPI_PLUGIN_PRE_DELETE_FN), None);
+
+    // The metrics are looked up by the first calls, after which dispatch
+    // must not allocate.
+    let calls = metrics::registry().counter("pre_search_calls").get();
+    let errors = metrics::registry().counter("pre_add_errors").get();
+    let timed = metrics::registry().histogram("pre_add_latency").count();
+    let before = alloc_count::allocations();
+    for _ in 0..10 {
+        mock::call_plugin_fn(&pb, constants::SLAPI_PLUGIN_PRE_SEARCH_FN);
+        mock::call_plugin_fn(&pb, constants::SLAPI_PLUGIN_PRE_ADD_FN);
+    }
+    assert_eq!(alloc_count::allocations(), before);
+    assert_eq!(metrics::registry().counter("pre_search_calls").get(), calls + 10);
+    assert_eq!(metrics::registry().counter("pre_add_errors").get(), errors + 10);
+    assert_eq!(metrics::registry().histogram("pre_add_latency").count(), timed + 10);
+    pb.destroy();
+}
+
+#[cfg(slapi_r_mock)]
+#[test]
+fn test_plugin_sub_plugins() {
+    use super::mock;
+    use super::pblock::Slapi_PBlock_Init_V3;
+    let _lock = REGISTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    fn allow(_: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        Ok(())
+    }
+
+    fn reject(_: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        Err(PluginOperationError::UnwillingToPerform)
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct State(u32);
+
+    fn manager<'a>() -> Slapi_R_Plugin_Manager<'a> {
+        let mut manager = Slapi_R_Plugin_Manager::new();
+        manager.functions.pre_add = Some(allow);
+        let mut betxn = Slapi_R_Plugin_FN::new();
+        betxn.post_add = Some(reject);
+        manager.sub_plugin("test betxn", "betxnpostoperation", betxn);
+        let mut internal = Slapi_R_Plugin_FN::new();
+        internal.post_modify = Some(allow);
+        internal.pre_delete = Some(reject);
+        manager.sub_plugin("test internal", "internalpostoperation", internal);
+        manager.set_state(State(7));
+        manager
+    }
+
+    // Each sub-plugin has the slots of its own type.
+    let mut m = manager();
+    m.sub_plugins[1].functions.pre_delete = None;
+    let pb = mock::register(m).unwrap();
+    assert_eq!(slapi_r_plugin_state::<State>().as_deref(), Some(&State(7)));
+    assert_eq!(mock::call_plugin_fn(&pb, constants::SLAPI_PLUGIN_PRE_ADD_FN), Some(constants::LDAP_SUCCESS));
+    let subs = mock::take_sub_plugins();
+    assert_eq!(subs.len(), 2);
+    assert_eq!((subs[0].0.as_str(), subs[0].1.as_str()), ("betxnpostoperation", "test betxn"));
+    assert_eq!(mock::call_plugin_fn(&subs[0].2, constants::SLAPI_PLUGIN_BE_TXN_POST_ADD_FN),
+               Some(constants::LDAP_UNWILLING_TO_PERFORM));
+    assert_eq!(mock::call_plugin_fn(&subs[0].2, constants::SLAPI_PLUGIN_PRE_ADD_FN), None);
+    assert_eq!(mock::call_plugin_fn(&subs[1].2, constants::SLAPI_PLUGIN_INTERNAL_POST_MODIFY_FN),
+               Some(constants::LDAP_SUCCESS));
+    assert_eq!(mock::call_plugin_fn(&subs[1].2, constants::SLAPI_PLUGIN_POST_MODIFY_FN), None);
+    for (_, _, sub) in subs {
+        sub.destroy();
+    }
+    pb.destroy();
+
+    // A callback the type has no slot for is refused before anything is
+    // registered.
+    match mock::register(manager()) {
+        Err(PluginRegistrationError::UnsupportedCallback(kind, callback)) => {
+            assert_eq!(kind, "internalpostoperation");
+            assert_eq!(callback, "pre_delete");
+        }
+        _ => panic!("pre_delete registered for an internalpostoperation plugin"),
+    }
+    assert!(mock::take_sub_plugins().is_empty());
+
+    // If a sub-plugin is refused, the main plugin is never started or
+    // closed, so its private data and the state are freed straight away.
+    let mut m = manager();
+    m.sub_plugins[1].functions.pre_delete = None;
+    mock::refuse_sub_plugin(Some("test internal"));
+    let pb = Slapi_R_PBlock::new();
+    let ptr = pb.as_ptr() as *const libc::c_void;
+    match m.register(pb) {
+        Err(PluginRegistrationError::SubPlugin(name)) => assert_eq!(name, "test internal"),
+        _ => panic!("a refused sub-plugin registered"),
+    }
+    mock::refuse_sub_plugin(None);
+    let pb = Slapi_R_PBlock::build(ptr);
+    assert!(pb.get_plugin_private::<Slapi_R_Plugin_FN>().unwrap().is_none());
+    assert!(slapi_r_plugin_state::<State>().is_none());
+    // The sub-plugin registered before the refusal can not be removed.
+    let subs = mock::take_sub_plugins();
+    assert_eq!(subs.iter().map(|s| s.1.as_str()).collect::<Vec<&str>>(), vec!["test betxn"]);
+    for (_, _, sub) in subs {
+        sub.destroy();
+    }
+    pb.destroy();
+}
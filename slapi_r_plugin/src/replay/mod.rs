@@ -0,0 +1,539 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+#[cfg(feature = "arbitrary")]
+use arbitrary::Arbitrary;
+#[cfg(feature = "arbitrary")]
+use arbitrary::Unstructured;
+use serde_json;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use super::constants::*;
+use super::entry::Entry;
+use super::error::LdifError;
+use super::error::RecordError;
+use super::ldif;
+use super::ldif::LdifRecord;
+#[cfg(feature = "arbitrary")]
+use super::mods::ModType;
+use super::mods::Modification;
+use super::pblock::Slapi_R_PBlock;
+use super::pblock::Slapi_PBlock_V3;
+#[cfg(slapi_r_mock)]
+use super::error::PluginOperationError;
+#[cfg(slapi_r_mock)]
+use super::mock;
+#[cfg(slapi_r_mock)]
+use super::operation::OperationType;
+#[cfg(slapi_r_mock)]
+use super::plugin::Slapi_R_Plugin_FN;
+#[cfg(feature = "arbitrary")]
+use super::plugin::CALLBACKS;
+
+/// The attribute of the plugin configuration entry that names the file that
+/// plugin callbacks are recorded to. Recording is off when it is not set.
+pub const RECORD_FILE_ATTR: &str = "rustRecordFile";
+
+// Checked on every callback, so that the file lock is only taken while
+// recording.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static RECORD_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// The contents of the pblock given to one plugin callback, and the result
+/// the callback returned. Each is written to the record file as one line of
+/// JSON. Only the callback is required when reading a record, so that a test
+/// can be written by hand.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CallbackRecord {
+    /// The callback that was called, IE pre_add.
+    pub callback: String,
+    /// The type of the operation, IE add.
+    pub op_type: Option<String>,
+    /// The DN that the operation targets.
+    pub target_dn: Option<String>,
+    /// The entry of the operation as LDIF. This is the entry being added for
+    /// pre_add, and the search result entry for pre_entry and post_search.
+    pub entry: Option<String>,
+    /// The modifications of a modify operation, as an LDIF modify record.
+    pub mods: Option<String>,
+    /// The filter of a search operation.
+    pub filter: Option<String>,
+    /// The id of the connection the operation arrived on.
+    pub conn_id: u64,
+    /// The id of the operation on its connection.
+    pub op_id: isize,
+    /// The DN the operation is authorised as, or None if anonymous.
+    pub requestor_dn: Option<String>,
+    /// The operation is from replication.
+    pub replicated: bool,
+    /// The operation is from an internal server event.
+    pub internal: bool,
+    /// The result code the callback returned to Directory Server.
+    pub result: isize,
+}
+
+// The pblock slot holding the entry of the callback, if it has one.
+fn entry_slot(callback: &str) -> Option<isize> {
+    match callback {
+        "pre_add" => Some(SLAPI_ADD_ENTRY),
+        "pre_entry" | "post_search" => Some(SLAPI_SEARCH_RESULT_ENTRY),
+        _ => None,
+    }
+}
+
+fn has_filter(callback: &str) -> bool {
+    matches!(callback, "pre_search" | "pre_entry" | "post_search")
+}
+
+impl CallbackRecord {
+    /// Record the contents of the pblock given to the named callback. The
+    /// result is 0 until it is set.
+    pub fn from_pblock(callback: &str, pb: &Slapi_R_PBlock) -> CallbackRecord {
+        let mut record = CallbackRecord {
+            callback: callback.to_string(),
+            ..Default::default()
+        };
+        if let Some(op) = pb.get_operation() {
+            record.op_type = op.op_type().map(|t| t.as_str().to_string());
+            record.target_dn = op.target_dn();
+            record.conn_id = op.conn_id();
+            record.op_id = op.op_id();
+            record.requestor_dn = op.authorised_dn();
+            record.replicated = op.is_replicated();
+            record.internal = op.is_internal();
+        }
+        // Only the slots that Directory Server sets for this callback are
+        // read, as the others may not be valid.
+        let entry = match entry_slot(callback) {
+            Some(SLAPI_ADD_ENTRY) => pb.get_add_entry(),
+            Some(_) => pb.get_search_result_entry(),
+            None => None,
+        };
+        record.entry = entry.map(|e| ldif::to_string(&[e.to_entry().to_ldif_record()]));
+        if callback == "pre_modify" {
+            record.mods = pb.get_modifications().map(|mods| ldif::to_string(&[LdifRecord::Modify {
+                dn: record.target_dn.clone().unwrap_or_default(),
+                mods,
+            }]));
+        }
+        if has_filter(callback) {
+            record.filter = pb.get_search_filter();
+        }
+        record
+    }
+
+    /// The entry of the record, parsed from its LDIF.
+    pub fn to_entry(&self) -> Result<Option<Entry>, LdifError> {
+        match self.entry {
+            Some(ref e) => Ok(ldif::parse_str(e)?.first().and_then(Entry::from_ldif_record)),
+            None => Ok(None),
+        }
+    }
+
+    /// The modifications of the record, parsed from its LDIF.
+    pub fn to_modifications(&self) -> Result<Option<Vec<Modification>>, LdifError> {
+        match self.mods {
+            Some(ref m) => Ok(ldif::parse_str(m)?.into_iter().next().and_then(LdifRecord::into_modifications)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Start appending a record of every plugin callback to the file. This is
+/// called by Slapi_R_Plugin_Manager::register when the plugin configuration
+/// sets rustRecordFile, so you should not need to call it yourself.
+///
+/// Records contain entries and modifications as they were sent by clients,
+/// which may include passwords, so the file is only readable by its owner.
+///
+/// # Failures
+/// If the file can not be opened, RecordError::Io is returned.
+pub fn slapi_r_record_start(path: &Path) -> Result<(), RecordError> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(path).map_err(|e| RecordError::Io(format!("{}: {}", path.display(), e)))?;
+    *RECORD_FILE.lock().unwrap() = Some(file);
+    RECORDING.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Stop recording plugin callbacks, and close the record file.
+pub fn slapi_r_record_stop() {
+    RECORDING.store(false, Ordering::SeqCst);
+    *RECORD_FILE.lock().unwrap() = None;
+}
+
+/// Returns true if plugin callbacks are being recorded.
+pub fn slapi_r_record_is_enabled() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Append the record to the record file as a line of JSON. The line is
+/// written in one call, so that the records of concurrent operations are not
+/// interleaved. If the write fails, recording is stopped.
+///
+/// # Failures
+/// If recording is not started, or the file can not be written,
+/// RecordError::Io is returned.
+pub fn slapi_r_record_write(record: &CallbackRecord) -> Result<(), RecordError> {
+    let mut line = serde_json::to_string(record).map_err(|e| RecordError::Io(e.to_string()))?;
+    line.push('\n');
+    let mut guard = RECORD_FILE.lock().unwrap();
+    let result = match *guard {
+        Some(ref mut f) => f.write_all(line.as_bytes()).map_err(|e| RecordError::Io(e.to_string())),
+        None => Err(RecordError::Io("recording is not started".to_string())),
+    };
+    if result.is_err() {
+        RECORDING.store(false, Ordering::SeqCst);
+        *guard = None;
+    }
+    result
+}
+
+/// Read the records of a record file, one per line. Blank lines are skipped.
+///
+/// # Failures
+/// If a line is not a valid record, or the entry or modifications of the
+/// record are not valid LDIF, RecordError::Syntax is returned with the line.
+pub fn read_records<R: BufRead>(reader: R) -> Result<Vec<CallbackRecord>, RecordError> {
+    let mut records = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| RecordError::Io(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: CallbackRecord = serde_json::from_str(&line).map_err(|e| RecordError::Syntax(i + 1, e.to_string()))?;
+        if let Err(e) = record.to_entry() {
+            return Err(RecordError::Syntax(i + 1, format!("entry: {:?}", e)));
+        }
+        if let Err(e) = record.to_modifications() {
+            return Err(RecordError::Syntax(i + 1, format!("mods: {:?}", e)));
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// The result of replaying one record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayOutcome {
+    /// The record that was replayed.
+    pub record: CallbackRecord,
+    /// The result code the callback returned when replayed.
+    pub result: isize,
+    /// The result code and message the callback sent to the client with
+    /// send_ldap_result, if it sent one.
+    pub sent_result: Option<(isize, Option<String>)>,
+}
+
+impl ReplayOutcome {
+    /// Returns true if the callback returned the same result as it did when
+    /// it was recorded.
+    pub fn matches(&self) -> bool {
+        self.result == self.record.result
+    }
+}
+
+/// Call the plugin callback of each record, with a mock pblock holding the
+/// contents of the record. A record for a callback that the plugin does not
+/// register returns the result Directory Server would get, as if it had been
+/// called.
+#[cfg(slapi_r_mock)]
+pub fn replay(functions: &Slapi_R_Plugin_FN, records: &[CallbackRecord]) -> Vec<ReplayOutcome> {
+    records.iter().map(|r| replay_record(functions, r)).collect()
+}
+
+#[cfg(slapi_r_mock)]
+fn replay_record(functions: &Slapi_R_Plugin_FN, record: &CallbackRecord) -> ReplayOutcome {
+    let pb = Slapi_R_PBlock::new();
+    if let Some(op_type) = record.op_type.as_ref().and_then(|t| OperationType::from_name(t)) {
+        let mut op = mock::MockOperation::new(op_type);
+        if let Some(ref dn) = record.target_dn {
+            op = op.with_target_dn(dn);
+        }
+        if record.replicated {
+            op = op.with_flag(OP_FLAG_REPLICATED);
+        }
+        if record.internal {
+            op = op.with_flag(OP_FLAG_INTERNAL);
+        }
+        mock::set_operation(&pb, op);
+    }
+    mock::set_conn_id(&pb, record.conn_id);
+    mock::set_int(&pb, SLAPI_OPERATION_ID, record.op_id);
+    if let Some(ref dn) = record.requestor_dn {
+        mock::set_string(&pb, SLAPI_REQUESTOR_DN, dn);
+    }
+    if let (Some(slot), Ok(Some(entry))) = (entry_slot(&record.callback), record.to_entry()) {
+        mock::set_entry(&pb, slot, &entry);
+    }
+    if let Ok(Some(mods)) = record.to_modifications() {
+        mock::set_mods(&pb, &mods);
+    }
+    if let Some(ref filter) = record.filter {
+        mock::set_string(&pb, SLAPI_SEARCH_STRFILTER, filter);
+    }
+
+    // As plugin_execute_fn_cb in the plugin module.
+    let result = match functions.callback(&record.callback) {
+        Some(f) => match f(&pb) {
+            Ok(_) => LDAP_SUCCESS,
+            Err(e) => e.as_ds_isize(),
+        },
+        None => PluginOperationError::Unknown.as_ds_isize(),
+    };
+    let sent_result = mock::last_result(&pb);
+    pb.destroy();
+    ReplayOutcome {
+        record: record.clone(),
+        result,
+        sent_result,
+    }
+}
+
+/// Replay the records of a record file, and panic with every record whose
+/// callback no longer returns the recorded result. This is intended for the
+/// tests of a plugin, so that recorded incidents become regression tests.
+///
+/// ```ignore
+/// let records = include_str!("../replay/external_writes.jsonl");
+/// assert_replay(&RoReplicaPlugin::plugin_functions(), records);
+/// ```
+#[cfg(slapi_r_mock)]
+pub fn assert_replay(functions: &Slapi_R_Plugin_FN, records: &str) {
+    let records = match read_records(records.as_bytes()) {
+        Ok(r) => r,
+        Err(e) => panic!("invalid records: {:?}", e),
+    };
+    let mismatches: Vec<String> = replay(functions, &records)
+        .iter()
+        .enumerate()
+        .filter(|&(_, o)| !o.matches())
+        .map(|(i, o)| format!("record {} ({} conn={} op={}) returned {}, recorded {}",
+                              i + 1, o.record.callback, o.record.conn_id, o.record.op_id, o.result, o.record.result))
+        .collect();
+    if !mismatches.is_empty() {
+        panic!("replay mismatches:\n{}", mismatches.join("\n"));
+    }
+}
+
+// The names of OperationType, and one that is not, as a record may hold any
+// string.
+#[cfg(feature = "arbitrary")]
+const OP_TYPES: &'static [&'static str] = &[
+    "add", "modify", "delete", "modrdn", "search", "compare", "bind", "unbind",
+    "abandon", "extended", "unknown",
+];
+
+// Directory Server gives plugins C strings, so they can not contain a nul.
+#[cfg(feature = "arbitrary")]
+fn arbitrary_c_string(u: &mut Unstructured) -> arbitrary::Result<String> {
+    let s: String = u.arbitrary()?;
+    Ok(s.replace('\0', ""))
+}
+
+#[cfg(feature = "arbitrary")]
+fn arbitrary_c_string_opt(u: &mut Unstructured) -> arbitrary::Result<Option<String>> {
+    Ok(if u.arbitrary()? { Some(arbitrary_c_string(u)?) } else { None })
+}
+
+// An attribute description, as Directory Server has checked its syntax
+// before the plugin is called. IE cn or userCertificate;binary.
+#[cfg(feature = "arbitrary")]
+fn arbitrary_attr(u: &mut Unstructured) -> arbitrary::Result<String> {
+    const FIRST: &'static [u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const REST: &'static [u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-;";
+    let mut attr = String::new();
+    attr.push(*u.choose(FIRST)? as char);
+    for _ in 0..u.int_in_range(0..=15u8)? {
+        attr.push(*u.choose(REST)? as char);
+    }
+    Ok(attr)
+}
+
+#[cfg(feature = "arbitrary")]
+fn arbitrary_modification(u: &mut Unstructured) -> arbitrary::Result<Modification> {
+    let mod_type = *u.choose(&[ModType::Add, ModType::Delete, ModType::Replace])?;
+    let values: Vec<Vec<u8>> = u.arbitrary()?;
+    Ok(values.into_iter().fold(Modification::new(mod_type, &arbitrary_attr(u)?), |m, v| m.value(v)))
+}
+
+/// A record of an arbitrary pblock, for fuzzing plugin callbacks. The entry
+/// and modifications are only set for the callbacks that Directory Server
+/// gives them to. Their values are arbitrary, but attribute names are valid,
+/// as Directory Server rejects the operation otherwise.
+#[cfg(feature = "arbitrary")]
+impl<'a> Arbitrary<'a> for CallbackRecord {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<CallbackRecord> {
+        let callback = u.choose(CALLBACKS)?.to_string();
+        let op_type = if u.arbitrary()? { Some(u.choose(OP_TYPES)?.to_string()) } else { None };
+        let target_dn = arbitrary_c_string_opt(u)?;
+        let dn = target_dn.clone().unwrap_or_default();
+        let entry = if entry_slot(&callback).is_some() && u.arbitrary()? {
+            let mut attrs = Vec::new();
+            for _ in 0..u.int_in_range(0..=16u8)? {
+                attrs.push((arbitrary_attr(u)?, u.arbitrary()?));
+            }
+            Some(ldif::to_string(&[LdifRecord::Entry { dn: dn.clone(), attrs: attrs }]))
+        } else {
+            None
+        };
+        let mods = if callback == "pre_modify" && u.arbitrary()? {
+            let mut mods = Vec::new();
+            for _ in 0..u.int_in_range(0..=16u8)? {
+                mods.push(arbitrary_modification(u)?);
+            }
+            Some(ldif::to_string(&[LdifRecord::Modify { dn: dn, mods: mods }]))
+        } else {
+            None
+        };
+        let filter = if has_filter(&callback) { arbitrary_c_string_opt(u)? } else { None };
+        Ok(CallbackRecord {
+            callback: callback,
+            op_type: op_type,
+            target_dn: target_dn,
+            entry: entry,
+            mods: mods,
+            filter: filter,
+            conn_id: u.arbitrary()?,
+            op_id: u.arbitrary()?,
+            requestor_dn: arbitrary_c_string_opt(u)?,
+            replicated: u.arbitrary()?,
+            internal: u.arbitrary()?,
+            result: 0,
+        })
+    }
+}
+
+/// Call the plugin callbacks with the arbitrary records built from the data.
+/// This is the harness of the callback fuzz targets, see fuzz/README. A
+/// callback that panics aborts Directory Server, so any panic is a failure.
+///
+/// ```ignore
+/// fuzz_target!(|data: &[u8]| {
+///     fuzz_callbacks(&RoReplicaPlugin::plugin_functions(), data);
+/// });
+/// ```
+#[cfg(all(slapi_r_mock, feature = "arbitrary"))]
+pub fn fuzz_callbacks(functions: &Slapi_R_Plugin_FN, data: &[u8]) {
+    let mut u = Unstructured::new(data);
+    let records: Vec<CallbackRecord> = match u.arbitrary() {
+        Ok(r) => r,
+        Err(_) => return,
+    };
+    for outcome in replay(functions, &records) {
+        // The entry and modifications are written by ldif::to_string, so
+        // must read back for the callback to have been given them.
+        assert!(outcome.record.to_entry().is_ok(), "entry: {:?}", outcome.record.entry);
+        assert!(outcome.record.to_modifications().is_ok(), "mods: {:?}", outcome.record.mods);
+    }
+}
+
+#[cfg(all(test, slapi_r_mock))]
+mod tests {
+    use super::*;
+    use super::super::error::PluginOperationError;
+    use super::super::mods::ModType;
+    use super::super::operation::OperationType;
+
+    fn reject_external(pb: &Slapi_R_PBlock) -> Result<(), PluginOperationError> {
+        match pb.get_operation() {
+            Some(ref op) if op.is_replicated() => Ok(()),
+            _ => {
+                pb.send_ldap_result(PluginOperationError::UnwillingToPerform, "read only");
+                Err(PluginOperationError::UnwillingToPerform)
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let pb = Slapi_R_PBlock::new();
+        mock::set_operation(&pb, mock::MockOperation::new(OperationType::Modify)
+                            .with_target_dn("uid=a,dc=example,dc=com")
+                            .with_flag(OP_FLAG_REPLICATED));
+        mock::set_conn_id(&pb, 12);
+        mock::set_int(&pb, SLAPI_OPERATION_ID, 3);
+        mock::set_string(&pb, SLAPI_REQUESTOR_DN, "cn=replication manager,cn=config");
+        let mods = vec![
+            Modification::new(ModType::Replace, "nsAccountLock").value("true"),
+            Modification::new(ModType::Add, "jpegPhoto").value(vec![0xff, 0xd8, 0x00]),
+        ];
+        mock::set_mods(&pb, &mods);
+        assert_eq!(pb.get_modifications(), Some(mods.clone()));
+
+        let mut record = CallbackRecord::from_pblock("pre_modify", &pb);
+        pb.destroy();
+        record.result = LDAP_SUCCESS;
+        assert_eq!(record.op_type, Some("modify".to_string()));
+        assert_eq!(record.conn_id, 12);
+        assert!(record.replicated);
+
+        let line = serde_json::to_string(&record).unwrap();
+        let records = read_records(format!("{}\n\n", line).as_bytes()).unwrap();
+        assert_eq!(records, vec![record]);
+        assert_eq!(records[0].to_modifications().unwrap(), Some(mods));
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut functions = Slapi_R_Plugin_FN::new();
+        functions.pre_add = Some(reject_external);
+        let records = read_records(&br#"
+{"callback":"pre_add","op_type":"add","target_dn":"uid=a,dc=example,dc=com","entry":"dn: uid=a,dc=example,dc=com\nuid: a\n","result":53}
+{"callback":"pre_add","op_type":"add","replicated":true,"result":0}
+{"callback":"pre_delete","op_type":"delete","result":0}
+"#[..]).unwrap();
+        let outcomes = replay(&functions, &records);
+        assert!(outcomes[0].matches());
+        assert_eq!(outcomes[0].sent_result, Some((LDAP_UNWILLING_TO_PERFORM, Some("read only".to_string()))));
+        assert!(outcomes[1].matches());
+        // pre_delete is not registered.
+        assert!(!outcomes[2].matches());
+
+        match read_records(&b"{\"callback\":\"pre_add\",\"entry\":\"uid: a\\n\"}\n"[..]) {
+            Err(RecordError::Syntax(1, _)) => {}
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_fuzz_callbacks() {
+        let mut functions = Slapi_R_Plugin_FN::new();
+        functions.pre_add = Some(reject_external);
+        functions.pre_modify = Some(reject_external);
+        // One input of each length, few enough to run under Miri.
+        let mut state: u32 = 1;
+        for len in 0..64 {
+            let data: Vec<u8> = (0..len).map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            }).collect();
+            fuzz_callbacks(&functions, &data);
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+use libc;
+use slapi_r_plugin_sys as sys;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use super::capability::Capability;
+use super::constants::*;
+use super::entry::Slapi_R_Entry;
+use super::error::TaskError;
+
+/// The function signature of a task handler. It is run on a worker thread,
+/// and the result becomes the nsTaskExitCode of the task entry.
+#[allow(non_camel_case_types)]
+pub type Slapi_R_Task_Handler = fn(&Slapi_R_Task) -> Result<(), TaskError>;
+
+// The handlers that plugins have registered, by lower case task name.
+static HANDLERS: Mutex<BTreeMap<String, Slapi_R_Task_Handler>> = Mutex::new(BTreeMap::new());
+
+/// The state of a task that Directory Server holds for us as the task data.
+struct TaskState {
+    cancelled: AtomicBool,
+}
+
+#[allow(non_camel_case_types)]
+/// Slapi_R_Task is the context of a running administrative task. It contains
+/// the attributes of the task entry, and allows the task to report progress
+/// and messages back to the task entry as nsTaskStatus and nsTaskLog.
+pub struct Slapi_R_Task {
+    slapi_task: *mut sys::Slapi_Task,
+    dn: String,
+    attrs: BTreeMap<String, Vec<String>>,
+    state: Arc<TaskState>,
+}
+
+// The Slapi_Task is reference counted and locked by Directory Server, so it
+// is safe to move to our worker thread.
+unsafe impl Send for Slapi_R_Task {}
+
+impl Slapi_R_Task {
+    /// The DN of the task entry, IE cn=rebuild,cn=my task,cn=tasks,cn=config
+    pub fn get_dn(&self) -> &str {
+        &self.dn
+    }
+
+    /// Returns the first value of an attribute from the task entry, if
+    /// present. Attribute names are not case sensitive.
+    pub fn get_attr(&self, name: &str) -> Option<&str> {
+        self.get_attr_values(name).first().map(|v| v.as_str())
+    }
+
+    /// Returns every value of an attribute from the task entry, which is
+    /// empty if the attribute is not present. Attribute names are not case
+    /// sensitive.
+    pub fn get_attr_values(&self, name: &str) -> &[String] {
+        match self.attrs.get(&name.to_lowercase()) {
+            Some(v) => v,
+            None => &[],
+        }
+    }
+
+    /// Returns the value of an attribute from the task entry, or a
+    /// TaskError::MissingAttribute that can be returned from the handler.
+    pub fn require_attr(&self, name: &str) -> Result<&str, TaskError> {
+        match self.get_attr(name) {
+            Some(v) => Ok(v),
+            None => Err(TaskError::MissingAttribute(name.to_string())),
+        }
+    }
+
+    /// Add a message to the nsTaskLog of the task entry.
+    pub fn log(&self, message: &str) {
+        let c_message = CString::new(message.replace('\0', "")).unwrap();
+        unsafe {
+            sys::slapi_task_log_notice(self.slapi_task, b"%s\0".as_ptr() as *mut c_char, c_message.as_ptr());
+        }
+    }
+
+    /// Set the nsTaskStatus of the task entry.
+    pub fn status(&self, message: &str) {
+        let c_message = CString::new(message.replace('\0', "")).unwrap();
+        unsafe {
+            sys::slapi_task_log_status(self.slapi_task, b"%s\0".as_ptr() as *mut c_char, c_message.as_ptr());
+        }
+    }
+
+    /// Declare the amount of work this task will do, so that progress can be
+    /// reported with inc_progress.
+    pub fn begin(&self, total_work: usize) {
+        unsafe {
+            sys::slapi_task_begin(self.slapi_task, total_work as libc::c_int);
+        }
+    }
+
+    /// Report that one unit of work has been completed.
+    pub fn inc_progress(&self) {
+        unsafe {
+            sys::slapi_task_inc_progress(self.slapi_task);
+        }
+    }
+
+    /// Has an administrator cancelled this task. Long running tasks should
+    /// check this regularly, and return TaskError::Cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Called by Directory Server when the task is aborted.
+extern "C" fn slapi_r_task_cancel_cb(slapi_task: *mut sys::Slapi_Task) {
+    unsafe {
+        let data = sys::slapi_task_get_data(slapi_task) as *const TaskState;
+        if !data.is_null() {
+            (*data).cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Called by Directory Server when the task is destroyed, to release our state.
+extern "C" fn slapi_r_task_destructor_cb(slapi_task: *mut sys::Slapi_Task) {
+    unsafe {
+        let data = sys::slapi_task_get_data(slapi_task) as *const TaskState;
+        if !data.is_null() {
+            sys::slapi_task_set_data(slapi_task, ::std::ptr::null_mut());
+            drop(Arc::from_raw(data));
+        }
+    }
+}
+
+/// Find the task name from the task entry DN. The task entry is created
+/// under the task container, IE cn=rebuild,cn=my task,cn=tasks,cn=config has
+/// the name "my task".
+fn task_name_from_dn(dn: &str) -> Option<String> {
+    let mut rdns = dn.split(',');
+    rdns.next();
+    match rdns.next() {
+        Some(rdn) => {
+            let mut parts = rdn.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(_), Some(name)) => Some(name.trim().to_lowercase()),
+                _ => None,
+            }
+        }
+        None => None,
+    }
+}
+
+/// The callback that Directory Server calls when a task entry is added. We
+/// copy the task entry, create the Slapi_Task, and start the Rust handler on
+/// a worker thread.
+extern "C" fn slapi_r_task_add_cb(_slapi_pblock: *mut sys::Slapi_PBlock,
+                                  slapi_entry: *mut sys::Slapi_Entry,
+                                  _slapi_entry_after: *mut sys::Slapi_Entry,
+                                  returncode: *mut libc::c_int,
+                                  _returntext: *mut c_char,
+                                  _arg: *mut libc::c_void) -> libc::c_int {
+    let entry = unsafe { Slapi_R_Entry::new(slapi_entry as *const libc::c_void) };
+    let dn = entry.get_dn();
+
+    let handler = match task_name_from_dn(&dn) {
+        Some(name) => HANDLERS.lock().unwrap().get(&name).cloned(),
+        None => None,
+    };
+    let handler = match handler {
+        Some(h) => h,
+        None => {
+            unsafe { *returncode = LDAP_OPERATIONS_ERROR as libc::c_int; }
+            return SLAPI_DSE_CALLBACK_ERROR as libc::c_int;
+        }
+    };
+
+    // The entry is freed when we return, so the worker gets a copy.
+    let copy = entry.to_entry();
+    let mut attrs: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for name in copy.get_attribute_names() {
+        let values = copy.get_values(&name).unwrap_or(&[]).iter()
+            .map(|v| String::from_utf8_lossy(v).into_owned())
+            .collect();
+        attrs.insert(name.to_lowercase(), values);
+    }
+
+    let c_dn = CString::new(dn.clone()).unwrap();
+    let slapi_task = unsafe { sys::slapi_new_task(c_dn.as_ptr()) };
+    if slapi_task.is_null() {
+        unsafe { *returncode = LDAP_OPERATIONS_ERROR as libc::c_int; }
+        return SLAPI_DSE_CALLBACK_ERROR as libc::c_int;
+    }
+
+    let state = Arc::new(TaskState { cancelled: AtomicBool::new(false) });
+    unsafe {
+        sys::slapi_task_set_data(slapi_task, Arc::into_raw(state.clone()) as *mut libc::c_void);
+        sys::slapi_task_set_cancel_fn(slapi_task, Some(slapi_r_task_cancel_cb));
+        sys::slapi_task_set_destructor_fn(slapi_task, Some(slapi_r_task_destructor_cb));
+        // Hold the task until the worker is done with it.
+        sys::slapi_task_inc_refcount(slapi_task);
+    }
+
+    let task = Slapi_R_Task {
+        slapi_task,
+        dn,
+        attrs,
+        state,
+    };
+
+    let spawned = thread::Builder::new()
+        .name("slapi_r_task".to_string())
+        .spawn(move || {
+            // The task must finish and be released even if the handler
+            // panics, or it would never be removed.
+            let rc = match panic::catch_unwind(AssertUnwindSafe(|| handler(&task))) {
+                Ok(Ok(_)) => LDAP_SUCCESS,
+                Ok(Err(e)) => {
+                    task.log(&format!("Task failed: {:?}", e));
+                    e.as_ds_isize()
+                }
+                Err(_) => {
+                    task.log("Task failed: the handler panicked");
+                    LDAP_OPERATIONS_ERROR
+                }
+            };
+            unsafe {
+                sys::slapi_task_finish(task.slapi_task, rc as libc::c_int);
+                sys::slapi_task_dec_refcount(task.slapi_task);
+            }
+        });
+
+    match spawned {
+        Ok(_) => {
+            unsafe { *returncode = LDAP_SUCCESS as libc::c_int; }
+            SLAPI_DSE_CALLBACK_OK as libc::c_int
+        }
+        Err(_) => {
+            unsafe {
+                sys::slapi_task_finish(slapi_task, LDAP_OPERATIONS_ERROR as libc::c_int);
+                sys::slapi_task_dec_refcount(slapi_task);
+                *returncode = LDAP_OPERATIONS_ERROR as libc::c_int;
+            }
+            SLAPI_DSE_CALLBACK_ERROR as libc::c_int
+        }
+    }
+}
+
+/// Register a task handler, so that adding an entry under
+/// cn=<name>,cn=tasks,cn=config runs the handler on a worker thread. This
+/// must be called from the plugin start callback, as the task container does
+/// not exist during init.
+///
+/// # Failures
+/// If Directory Server refuses to register the handler, TaskError::Unknown is
+/// returned. If the server does not support tasks, TaskError::Unsupported is
+/// returned.
+pub fn slapi_r_task_register(name: &str, handler: Slapi_R_Task_Handler) -> Result<(), TaskError> {
+    if !Capability::Tasks.is_available() {
+        return Err(TaskError::Unsupported);
+    }
+    HANDLERS.lock().unwrap().insert(name.to_lowercase(), handler);
+    let c_name = CString::new(name).unwrap();
+    let res = unsafe { sys::slapi_task_register_handler(c_name.as_ptr(), Some(slapi_r_task_add_cb)) };
+    match res as isize {
+        LDAP_SUCCESS => Ok(()),
+        _ => {
+            HANDLERS.lock().unwrap().remove(&name.to_lowercase());
+            Err(TaskError::Unknown)
+        }
+    }
+}
+
+/// Remove all task handlers registered by this plugin from Directory Server,
+/// so that no new tasks are started once the plugin is closed. This is called
+/// by the Slapi_R_Plugin_Manager, so you should not need to call it yourself.
+pub fn unregister_all() {
+    let mut handlers = HANDLERS.lock().unwrap();
+    for name in handlers.keys() {
+        let c_name = CString::new(name.as_str()).unwrap();
+        unsafe {
+            sys::slapi_plugin_task_unregister_handler(c_name.as_ptr(), Some(slapi_r_task_add_cb));
+        }
+    }
+    handlers.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::task_name_from_dn;
+
+    #[test]
+    fn test_task_name_from_dn() {
+        assert_eq!(task_name_from_dn("cn=rebuild,cn=my task,cn=tasks,cn=config"), Some("my task".to_string()));
+        assert_eq!(task_name_from_dn("cn=tasks"), None);
+    }
+}
+
+// Run the add callback for a new task entry, as Directory Server does, and
+// return the nsTaskExitCode of the task once it is destroyed.
+#[cfg(all(test, slapi_r_mock))]
+fn run_task(entry: &super::entry::Entry) -> Option<isize> {
+    let owned = entry.to_slapi_entry().unwrap();
+    let mut returncode: libc::c_int = -1;
+    let rc = slapi_r_task_add_cb(::std::ptr::null_mut(), owned.as_entry().as_ptr(), ::std::ptr::null_mut(),
+                                 &mut returncode, ::std::ptr::null_mut(), ::std::ptr::null_mut());
+    assert_eq!(rc as isize, SLAPI_DSE_CALLBACK_OK);
+    assert_eq!(returncode as isize, LDAP_SUCCESS);
+    super::mock::wait_task_destroyed(entry.get_dn())
+}
+
+#[cfg(slapi_r_mock)]
+#[test]
+fn test_task_lifecycle() {
+    use super::entry::Entry;
+    use super::mock;
+
+    fn members_handler(task: &Slapi_R_Task) -> Result<(), TaskError> {
+        match task.get_attr_values("member") {
+            [a, b] if a == "uid=a" && b == "uid=b" => Ok(()),
+            v => Err(TaskError::Failed(format!("unexpected members {:?}", v))),
+        }
+    }
+
+    fn panic_handler(_task: &Slapi_R_Task) -> Result<(), TaskError> {
+        panic!("the task handler panicked");
+    }
+
+    slapi_r_task_register("rust members", members_handler).unwrap();
+    slapi_r_task_register("rust panic", panic_handler).unwrap();
+    assert!(mock::task_handlers().contains(&"rust members".to_string()));
+
+    // Every value of a multi valued attribute is given to the handler.
+    let members = Entry::new("cn=1,cn=rust members,cn=tasks,cn=config")
+        .with_value("cn", "1")
+        .with_value("member", "uid=a")
+        .with_value("member", "uid=b");
+    assert_eq!(run_task(&members), Some(LDAP_SUCCESS));
+
+    // A panic fails the task, which is still finished and released.
+    let panics = Entry::new("cn=1,cn=rust panic,cn=tasks,cn=config").with_value("cn", "1");
+    assert_eq!(run_task(&panics), Some(LDAP_OPERATIONS_ERROR));
+
+    unregister_all();
+    let handlers = mock::task_handlers();
+    assert!(!handlers.contains(&"rust members".to_string()));
+    assert!(!handlers.contains(&"rust panic".to_string()));
+}
@@ -0,0 +1,463 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+//! slapi_r_plugin_build generates the files that enable and configure a Rust
+//! plugin from the plugin crate, so that they can not drift from the plugin
+//! itself. It is used from the build.rs of a plugin:
+//!
+//! ```ignore
+//! extern crate slapi_r_plugin_build;
+//!
+//! fn main() {
+//!     slapi_r_plugin_build::configure();
+//! }
+//! ```
+//!
+//! The plugin describes itself in its Cargo.toml, and the version and
+//! description of the package are used as the plugin version and description.
+//!
+//! ```toml
+//! [package.metadata.dirsrv]
+//! name = "ro_replica"
+//! id = "ro_replica"
+//! kind = "preoperation"
+//! vendor = "389 Project"
+//! depends-on-type = ["database"]
+//! python-class = "RoreplicaPlugin"
+//! python-dir = "../../rs389"
+//! ```
+//!
+//! The optional precedence (1 to 99) and depends-on-named list order the
+//! plugin relative to others, as nsslapd-pluginprecedence and
+//! nsslapd-plugin-depends-on-named. The optional config-attributes list names
+//! the attributes the plugin reads from its configuration entry, in addition
+//! to those every Rust plugin reads.
+//!
+//! configure gives the name and kind to the plugin as the SLAPI_R_PLUGIN_NAME
+//! and SLAPI_R_PLUGIN_KIND environment variables at compile time, which
+//! slapi_r_plugin_derive uses as the defaults of #[slapi_plugin]. The
+//! precedence and dependencies are given as SLAPI_R_PLUGIN_PRECEDENCE,
+//! SLAPI_R_PLUGIN_DEPENDS_ON_TYPE and SLAPI_R_PLUGIN_DEPENDS_ON_NAMED, with
+//! lists separated by commas, so that the plugin can check them against the
+//! running configuration. When slapi_r_plugin was built with the mock backend,
+//! the plugin is built with cfg(slapi_r_mock), so that its tests can use it.
+//!
+//! The build does not write into the source tree. cargo xtask generate writes
+//! enable.ldif next to the Cargo.toml, the lib389 class of the plugin to
+//! <python-dir>/<name>.py, and the config schema of every plugin to
+//! schema/60rust-plugins.ldif. The content only depends on Cargo.toml, so
+//! these files are checked in.
+
+extern crate toml;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The nsslapd-pluginType values that a plugin may declare.
+pub const KINDS: &[&str] = &[
+    "preoperation",
+    "postoperation",
+    "betxnpreoperation",
+    "betxnpostoperation",
+    "internalpreoperation",
+    "internalpostoperation",
+    "extendedop",
+    "object",
+];
+
+/// The attributes that slapi_r_plugin reads from the configuration entry of
+/// every plugin.
+pub const CONFIG_ATTRIBUTES: &[&str] = &[
+    "rustLogFormat",
+    "rustRecordFile",
+];
+
+/// The file name of the config schema, as it is installed in the schema
+/// directory of Directory Server.
+pub const SCHEMA_FILE: &str = "60rust-plugins.ldif";
+
+/// The description of a plugin, as read from the Cargo.toml of the plugin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginConfig {
+    /// The cn of the plugin configuration entry.
+    pub name: String,
+    /// The nsslapd-pluginId.
+    pub id: String,
+    /// The nsslapd-pluginType.
+    pub kind: String,
+    /// The nsslapd-pluginPath, IE libhellorust.
+    pub path: String,
+    /// The nsslapd-pluginVendor.
+    pub vendor: String,
+    /// The nsslapd-pluginVersion, from the package version.
+    pub version: String,
+    /// The nsslapd-pluginDescription, from the package description.
+    pub description: String,
+    /// The nsslapd-pluginprecedence, if the plugin declares one.
+    pub precedence: Option<u32>,
+    /// The nsslapd-plugin-depends-on-type values.
+    pub depends_on_type: Vec<String>,
+    /// The nsslapd-plugin-depends-on-named values.
+    pub depends_on_named: Vec<String>,
+    /// The attributes of the configuration entry that only this plugin reads.
+    pub config_attributes: Vec<String>,
+    /// The name of the lib389 class for the plugin.
+    pub python_class: String,
+    /// The directory the lib389 class is written to, relative to the Cargo.toml.
+    pub python_dir: String,
+}
+
+fn get_str(table: &toml::Table, key: &str) -> Option<String> {
+    table.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+fn require_str(table: &toml::Table, section: &str, key: &str) -> Result<String, String> {
+    match get_str(table, key) {
+        Some(s) => Ok(s),
+        None => Err(format!("{} requires the string {}", section, key)),
+    }
+}
+
+fn get_str_list(table: &toml::Table, section: &str, key: &str) -> Result<Vec<String>, String> {
+    let values = match table.get(key) {
+        Some(v) => match v.as_array() {
+            Some(a) => a,
+            None => return Err(format!("{} {} must be a list of strings", section, key)),
+        },
+        None => return Ok(Vec::new()),
+    };
+    let mut out = Vec::new();
+    for v in values {
+        match v.as_str() {
+            Some(s) => out.push(s.to_string()),
+            None => return Err(format!("{} {} must be a list of strings", section, key)),
+        }
+    }
+    Ok(out)
+}
+
+impl PluginConfig {
+    /// Read the plugin description from the text of a Cargo.toml.
+    ///
+    /// # Failures
+    /// A message describing the missing or invalid key is returned.
+    pub fn from_manifest(manifest: &str) -> Result<PluginConfig, String> {
+        let doc: toml::Table = match manifest.parse() {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Cargo.toml is invalid: {}", e)),
+        };
+        let package = match doc.get("package").and_then(|p| p.as_table()) {
+            Some(p) => p,
+            None => return Err("Cargo.toml has no [package]".to_string()),
+        };
+        let dirsrv = match package.get("metadata").and_then(|m| m.as_table()).and_then(|m| m.get("dirsrv")).and_then(|d| d.as_table()) {
+            Some(d) => d,
+            None => return Err("Cargo.toml has no [package.metadata.dirsrv]".to_string()),
+        };
+        let section = "[package.metadata.dirsrv]";
+
+        let package_name = require_str(package, "[package]", "name")?;
+        let lib_name = doc.get("lib")
+            .and_then(|l| l.as_table())
+            .and_then(|l| get_str(l, "name"))
+            .unwrap_or(package_name.replace('-', "_"));
+
+        let kind = require_str(dirsrv, section, "kind")?;
+        if !KINDS.contains(&kind.as_str()) {
+            return Err(format!("{} kind {} is unknown, expected one of {}", section, kind, KINDS.join(", ")));
+        }
+
+        let config_attributes = get_str_list(dirsrv, section, "config-attributes")?;
+        for attr in config_attributes.iter() {
+            let valid = attr.starts_with(|c: char| c.is_ascii_alphabetic())
+                && attr.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+            if !valid {
+                return Err(format!("{} config-attributes {} is not an attribute name", section, attr));
+            }
+        }
+
+        let precedence = match dirsrv.get("precedence") {
+            Some(v) => match v.as_integer() {
+                Some(p) if (1..=99).contains(&p) => Some(p as u32),
+                _ => return Err(format!("{} precedence must be an integer from 1 to 99", section)),
+            },
+            None => None,
+        };
+
+        Ok(PluginConfig {
+            name: require_str(dirsrv, section, "name")?,
+            id: require_str(dirsrv, section, "id")?,
+            kind,
+            path: format!("lib{}", lib_name),
+            vendor: get_str(dirsrv, "vendor").unwrap_or("389 Project".to_string()),
+            version: require_str(package, "[package]", "version")?,
+            description: require_str(package, "[package]", "description")?,
+            precedence,
+            depends_on_type: get_str_list(dirsrv, section, "depends-on-type")?,
+            depends_on_named: get_str_list(dirsrv, section, "depends-on-named")?,
+            config_attributes,
+            python_class: require_str(dirsrv, section, "python-class")?,
+            python_dir: get_str(dirsrv, "python-dir").unwrap_or("../../rs389".to_string()),
+        })
+    }
+
+    /// The LDIF that adds the plugin configuration entry to cn=config.
+    pub fn ldif(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# Generated from Cargo.toml by slapi_r_plugin_build. Do not edit.\n");
+        out.push_str(&format!("dn: cn={},cn=plugins,cn=config\n", self.name));
+        out.push_str("changetype: add\n");
+        out.push_str("objectClass: top\n");
+        out.push_str("objectClass: nsSlapdPlugin\n");
+        out.push_str("objectClass: extensibleObject\n");
+        out.push_str(&format!("cn: {}\n", self.name));
+        for &(k, ref v) in self.properties().iter() {
+            out.push_str(&format!("{}: {}\n", k, v));
+        }
+        out
+    }
+
+    /// The lib389 Plugin class that creates the plugin configuration entry.
+    pub fn python(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# --- BEGIN COPYRIGHT BLOCK ---\n");
+        out.push_str("# Copyright (C) 2016 Red Hat, Inc.\n");
+        out.push_str("# All rights reserved.\n");
+        out.push_str("#\n");
+        out.push_str("# License: GPL (version 3 or any later version).\n");
+        out.push_str("# See LICENSE for details.\n");
+        out.push_str("# --- END COPYRIGHT BLOCK ---\n");
+        out.push_str("#\n");
+        out.push_str("# Generated from Cargo.toml by slapi_r_plugin_build. Do not edit.\n");
+        out.push_str("#\n\n");
+        out.push_str("from lib389.plugins import Plugin\n\n");
+        out.push_str(&format!("class {}(Plugin):\n", self.python_class));
+        out.push_str(&format!("    def __init__(self, instance, dn=\"cn={},cn=plugins,cn=config\", batch=False):\n", self.name));
+        out.push_str(&format!("        super({}, self).__init__(instance, dn, batch)\n", self.python_class));
+        out.push_str("        self._default_properties = {\n");
+        let mut seen: Vec<&str> = Vec::new();
+        for &(k, ref v) in self.properties().iter() {
+            // lib389 properties are a dict, so only the first value of an
+            // attribute can be given.
+            if seen.contains(&k) {
+                continue;
+            }
+            seen.push(k);
+            out.push_str(&format!("            '{}': {},\n", k, python_str(v)));
+        }
+        out.push_str("        }\n\n");
+        out.push_str(&format!("    def create(self, rdn=\"{}\", properties={{}}, basedn=\"cn=plugins,cn=config\"):\n", self.name));
+        out.push_str("        self._default_properties.update(properties)\n");
+        out.push_str(&format!("        super({}, self).create(rdn, self._default_properties, basedn)\n", self.python_class));
+        out
+    }
+
+    /// The attributes of the configuration entry, in the order they are written.
+    fn properties(&self) -> Vec<(&'static str, String)> {
+        let mut props = vec![
+            ("nsslapd-pluginPath", self.path.clone()),
+            ("nsslapd-pluginInitfunc", "slapi_r_plugin_init_fn".to_string()),
+            ("nsslapd-pluginType", self.kind.clone()),
+            ("nsslapd-pluginEnabled", "on".to_string()),
+        ];
+        if let Some(p) = self.precedence {
+            props.push(("nsslapd-pluginprecedence", p.to_string()));
+        }
+        for t in self.depends_on_type.iter() {
+            props.push(("nsslapd-plugin-depends-on-type", t.clone()));
+        }
+        for n in self.depends_on_named.iter() {
+            props.push(("nsslapd-plugin-depends-on-named", n.clone()));
+        }
+        props.push(("nsslapd-pluginId", self.id.clone()));
+        props.push(("nsslapd-pluginVersion", self.version.clone()));
+        props.push(("nsslapd-pluginVendor", self.vendor.clone()));
+        props.push(("nsslapd-pluginDescription", self.description.clone()));
+        props
+    }
+
+    /// Write enable.ldif and the lib389 class of the plugin, relative to the
+    /// directory of its Cargo.toml.
+    ///
+    /// # Failures
+    /// A message describing the file that could not be written is returned.
+    pub fn write(&self, manifest_dir: &Path) -> Result<(), String> {
+        write_if_changed(&manifest_dir.join("enable.ldif"), &self.ldif())?;
+        let python_path = manifest_dir.join(&self.python_dir).join(format!("{}.py", self.name));
+        write_if_changed(&python_path, &self.python())
+    }
+}
+
+/// Quote a string as a python literal.
+fn python_str(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The config schema of the plugins, as an LDIF file for the schema directory
+/// of Directory Server. It defines the attributes every Rust plugin reads, and
+/// those each plugin reads, so that their values are checked as the
+/// extensibleObject configuration entries are changed.
+pub fn schema(plugins: &[PluginConfig]) -> String {
+    let mut out = String::new();
+    out.push_str("# Generated from Cargo.toml by slapi_r_plugin_build. Do not edit.\n");
+    out.push_str("dn: cn=schema\n");
+    let mut attrs: Vec<&str> = CONFIG_ATTRIBUTES.to_vec();
+    for plugin in plugins {
+        for attr in plugin.config_attributes.iter() {
+            if !attrs.iter().any(|a| a.eq_ignore_ascii_case(attr)) {
+                attrs.push(attr);
+            }
+        }
+    }
+    for attr in attrs.iter() {
+        out.push_str(&format!("attributeTypes: ( {}-oid NAME '{}' SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 SINGLE-VALUE X-ORIGIN 'slapi_r_plugin' )\n",
+                              attr, attr));
+    }
+    out
+}
+
+/// Write the file only if the content differs, so that unchanged files keep
+/// their timestamps.
+///
+/// # Failures
+/// A message describing the file that could not be written is returned.
+pub fn write_if_changed(path: &Path, content: &str) -> Result<(), String> {
+    if let Ok(existing) = fs::read_to_string(path) {
+        if existing == content {
+            return Ok(());
+        }
+    }
+    let mut f = match fs::File::create(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Unable to create {}: {}", path.display(), e)),
+    };
+    match f.write_all(content.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Unable to write {}: {}", path.display(), e)),
+    }
+}
+
+/// Pass the plugin name, kind and ordering of the plugin being built to the
+/// compiler. This is the whole of the build.rs of a plugin.
+///
+/// # Panics
+/// If the metadata is missing or invalid, this panics so that the build fails
+/// with the reason.
+pub fn configure() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is not set, is this run from build.rs?"));
+    let manifest_path = manifest_dir.join("Cargo.toml");
+    println!("cargo:rerun-if-changed={}", manifest_path.display());
+
+    let manifest = match fs::read_to_string(&manifest_path) {
+        Ok(m) => m,
+        Err(e) => panic!("Unable to read {}: {}", manifest_path.display(), e),
+    };
+    let config = match PluginConfig::from_manifest(&manifest) {
+        Ok(c) => c,
+        Err(e) => panic!("{}: {}", manifest_path.display(), e),
+    };
+
+    println!("cargo:rustc-env=SLAPI_R_PLUGIN_NAME={}", config.name);
+    println!("cargo:rustc-env=SLAPI_R_PLUGIN_KIND={}", config.kind);
+    if let Some(p) = config.precedence {
+        println!("cargo:rustc-env=SLAPI_R_PLUGIN_PRECEDENCE={}", p);
+    }
+    println!("cargo:rustc-env=SLAPI_R_PLUGIN_DEPENDS_ON_TYPE={}", config.depends_on_type.join(","));
+    println!("cargo:rustc-env=SLAPI_R_PLUGIN_DEPENDS_ON_NAMED={}", config.depends_on_named.join(","));
+
+    // slapi_r_plugin built the mock backend, so the tests of the plugin can
+    // replay recorded callbacks.
+    println!("cargo:rustc-check-cfg=cfg(slapi_r_mock)");
+    if env::var("DEP_SLAPD_MOCK").is_ok() {
+        println!("cargo:rustc-cfg=slapi_r_mock");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PluginConfig;
+    use super::schema;
+
+    const MANIFEST: &str = r#"
+[package]
+name = "ro_replica"
+version = "0.1.0"
+description = "Prevents writes to this instance from external sources."
+
+[package.metadata.dirsrv]
+name = "ro_replica"
+id = "ro_replica"
+kind = "preoperation"
+depends-on-type = ["database"]
+python-class = "RoreplicaPlugin"
+"#;
+
+    #[test]
+    fn test_plugin_config_ldif() {
+        let config = PluginConfig::from_manifest(MANIFEST).unwrap();
+        assert_eq!(config.ldif(), "# Generated from Cargo.toml by slapi_r_plugin_build. Do not edit.
+dn: cn=ro_replica,cn=plugins,cn=config
+changetype: add
+objectClass: top
+objectClass: nsSlapdPlugin
+objectClass: extensibleObject
+cn: ro_replica
+nsslapd-pluginPath: libro_replica
+nsslapd-pluginInitfunc: slapi_r_plugin_init_fn
+nsslapd-pluginType: preoperation
+nsslapd-pluginEnabled: on
+nsslapd-plugin-depends-on-type: database
+nsslapd-pluginId: ro_replica
+nsslapd-pluginVersion: 0.1.0
+nsslapd-pluginVendor: 389 Project
+nsslapd-pluginDescription: Prevents writes to this instance from external sources.
+");
+        assert!(config.python().contains("            'nsslapd-pluginVersion': '0.1.0',\n"));
+    }
+
+    #[test]
+    fn test_plugin_config_invalid() {
+        assert!(PluginConfig::from_manifest(&MANIFEST.replace("preoperation", "preop")).is_err());
+        assert!(PluginConfig::from_manifest("[package]\nname = \"x\"\n").is_err());
+        assert!(PluginConfig::from_manifest(&MANIFEST.replace("kind =", "precedence = 100\nkind =")).is_err());
+    }
+
+    #[test]
+    fn test_plugin_config_ordering() {
+        let manifest = MANIFEST.replace("kind =", "precedence = 40\ndepends-on-named = [\"attribute uniqueness\"]\nkind =");
+        let config = PluginConfig::from_manifest(&manifest).unwrap();
+        assert_eq!(config.precedence, Some(40));
+        assert!(config.ldif().contains("nsslapd-pluginEnabled: on
+nsslapd-pluginprecedence: 40
+nsslapd-plugin-depends-on-type: database
+nsslapd-plugin-depends-on-named: attribute uniqueness
+"));
+    }
+
+    #[test]
+    fn test_plugin_config_schema() {
+        let manifest = MANIFEST.replace("kind =", "config-attributes = [\"roReplicaAllowDN\", \"rustLogFormat\"]\nkind =");
+        let config = PluginConfig::from_manifest(&manifest).unwrap();
+        assert_eq!(schema(&[config]), "# Generated from Cargo.toml by slapi_r_plugin_build. Do not edit.
+dn: cn=schema
+attributeTypes: ( rustLogFormat-oid NAME 'rustLogFormat' SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 SINGLE-VALUE X-ORIGIN 'slapi_r_plugin' )
+attributeTypes: ( rustRecordFile-oid NAME 'rustRecordFile' SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 SINGLE-VALUE X-ORIGIN 'slapi_r_plugin' )
+attributeTypes: ( roReplicaAllowDN-oid NAME 'roReplicaAllowDN' SYNTAX 1.3.6.1.4.1.1466.115.121.1.15 SINGLE-VALUE X-ORIGIN 'slapi_r_plugin' )
+");
+        assert!(PluginConfig::from_manifest(&MANIFEST.replace("kind =", "config-attributes = [\"ro_replica\"]\nkind =")).is_err());
+    }
+}
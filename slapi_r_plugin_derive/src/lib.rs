@@ -0,0 +1,366 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+//! slapi_r_plugin_derive generates the registration of a Rust Directory
+//! Server plugin from attributes on the impl of the plugin type.
+//!
+//! ```ignore
+//! #[slapi_plugin(name = "ro_replica", kind = "preoperation")]
+//! impl RoReplicaPlugin {
+//!     #[pre_modify]
+//!     #[pre_add]
+//!     fn intercept_operation<T: Slapi_PBlock_V3>(pb: &T) -> Result<(), PluginOperationError> {
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! This generates the Slapi_Plugin_V3 impl that fills in Slapi_R_Plugin_FN,
+//! the slapi_r_plugin_init! entry point, a PLUGIN_METADATA const
+//! describing the plugin, and a plugin_functions fn returning its callbacks.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate slapi_r_plugin_build;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use proc_macro2::TokenStream as TokenStream2;
+use slapi_r_plugin_build::KINDS;
+use syn::spanned::Spanned;
+use syn::{FnArg, ImplItem, ImplItemFn, ItemImpl, LitStr, ReturnType, Type};
+
+/// The callbacks that may be registered, and the field of Slapi_R_Plugin_FN
+/// each one is stored in. init is not a Slapi_R_Plugin_FN, and is run before
+/// registration.
+const HOOKS: &[&str] = &[
+    "init",
+    "start",
+    "close",
+    "post_search",
+    "pre_bind",
+    "pre_unbind",
+    "pre_search",
+    "pre_compare",
+    "pre_modify",
+    "pre_modrdn",
+    "pre_add",
+    "pre_delete",
+    "pre_abandon",
+    "pre_entry",
+    "pre_referal",
+    "pre_result",
+    "pre_extop",
+    "post_bind",
+    "post_unbind",
+    "post_compare",
+    "post_modify",
+    "post_modrdn",
+    "post_add",
+    "post_delete",
+    "post_abandon",
+    "post_entry",
+    "post_referal",
+    "post_result",
+];
+
+/// The properties given to #[slapi_plugin(...)].
+#[derive(Default)]
+struct PluginArgs {
+    name: Option<LitStr>,
+    kind: Option<LitStr>,
+    description: Option<LitStr>,
+}
+
+/// Declare the impl of a type as a Directory Server plugin. The name and kind
+/// properties default to those in the [package.metadata.dirsrv] of the crate,
+/// when the plugin uses slapi_r_plugin_build from its build.rs, and
+/// description defaults to the description of the crate. Methods of the impl are registered as callbacks with the
+/// attributes #[init], #[start], #[close], #[post_search], #[pre_bind]
+/// through #[pre_extop] and #[post_bind] through #[post_result], which are
+/// registered in the slots of the plugin kind. A method may have several of
+/// these attributes. The
+/// precedence and dependencies from the same section are checked against the
+/// running configuration as the plugin registers.
+#[proc_macro_attribute]
+pub fn slapi_plugin(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut plugin_args = PluginArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            plugin_args.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("kind") {
+            plugin_args.kind = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("description") {
+            plugin_args.description = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown slapi_plugin property, expected name, kind or description"))
+        }
+    });
+    if let Err(e) = syn::parse::Parser::parse(parser, args) {
+        return e.to_compile_error().into();
+    }
+    let item: ItemImpl = match syn::parse(input) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    match expand(plugin_args, item) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Remove the hook attributes from a method, returning the hooks it had.
+fn take_hooks(method: &mut ImplItemFn) -> Vec<(String, Span)> {
+    let mut hooks = Vec::new();
+    method.attrs.retain(|attr| {
+        for hook in HOOKS {
+            if attr.path().is_ident(hook) {
+                hooks.push((hook.to_string(), attr.span()));
+                return false;
+            }
+        }
+        true
+    });
+    hooks
+}
+
+/// Check that a method can be used as the hook, so that mistakes are reported
+/// on the method rather than deep inside the generated registration.
+fn check_signature(method: &ImplItemFn, hook: &str) -> syn::Result<()> {
+    let sig = &method.sig;
+    let (arg, ret) = if hook == "init" {
+        ("&T, where T: Slapi_PBlock_Init_V3", "Result<(), PluginRegistrationError>")
+    } else {
+        ("&T, where T: Slapi_PBlock_V3", "Result<(), PluginOperationError>")
+    };
+    let msg = format!("#[{}] must be `fn {}({}) -> {}`", hook, sig.ident, arg, ret);
+
+    if sig.asyncness.is_some() || sig.unsafety.is_some() || sig.abi.is_some() {
+        return Err(syn::Error::new(sig.fn_token.span(), format!("{}, and can not be async, unsafe or extern", msg)));
+    }
+    if sig.inputs.len() != 1 {
+        return Err(syn::Error::new(sig.inputs.span(), format!("{}, with exactly one argument", msg)));
+    }
+    match sig.inputs[0] {
+        FnArg::Receiver(ref r) => {
+            return Err(syn::Error::new(r.span(), format!("{}, and can not take self", msg)));
+        }
+        FnArg::Typed(ref pat) => match *pat.ty {
+            Type::Reference(ref r) if r.mutability.is_none() => {}
+            ref ty => {
+                return Err(syn::Error::new(ty.span(), format!("{}, taking the pblock by shared reference", msg)));
+            }
+        },
+    }
+    if let ReturnType::Default = sig.output {
+        return Err(syn::Error::new(sig.ident.span(), format!("{}, returning a Result", msg)));
+    }
+    if sig.generics.type_params().count() > 1 || sig.generics.lifetimes().count() > 0 {
+        return Err(syn::Error::new(sig.generics.span(), format!("{}, with at most one type parameter", msg)));
+    }
+    Ok(())
+}
+
+fn expand(args: PluginArgs, mut item: ItemImpl) -> syn::Result<TokenStream2> {
+    // Without a name or kind, use those that slapi_r_plugin_build read from
+    // the [package.metadata.dirsrv] of Cargo.toml.
+    let name = match args.name {
+        Some(n) => quote!(#n),
+        None => quote!(env!("SLAPI_R_PLUGIN_NAME")),
+    };
+    let kind = match args.kind {
+        Some(k) => {
+            if !KINDS.contains(&k.value().as_str()) {
+                return Err(syn::Error::new(k.span(), format!("unknown plugin kind, expected one of {}", KINDS.join(", "))));
+            }
+            quote!(#k)
+        }
+        None => quote!(env!("SLAPI_R_PLUGIN_KIND")),
+    };
+    let description = match args.description {
+        Some(d) => quote!(#d),
+        None => quote!(env!("CARGO_PKG_DESCRIPTION")),
+    };
+
+    if let Some((_, ref t, _)) = item.trait_ {
+        return Err(syn::Error::new(t.span(), "slapi_plugin must be on the inherent impl of the plugin type"));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new(item.generics.span(), "slapi_plugin types can not be generic"));
+    }
+    let ident = match *item.self_ty {
+        Type::Path(ref p) if p.qself.is_none() && p.path.get_ident().is_some() => p.path.get_ident().unwrap().clone(),
+        ref ty => return Err(syn::Error::new(ty.span(), "slapi_plugin must be on the impl of a named type")),
+    };
+
+    // Find which method handles each hook.
+    let mut registered: Vec<(String, syn::Ident)> = Vec::new();
+    for impl_item in item.items.iter_mut() {
+        if let ImplItem::Fn(ref mut method) = *impl_item {
+            for (hook, span) in take_hooks(method) {
+                if registered.iter().any(|(h, _)| *h == hook) {
+                    return Err(syn::Error::new(span, format!("#[{}] is registered more than once", hook)));
+                }
+                check_signature(method, &hook)?;
+                registered.push((hook, method.sig.ident.clone()));
+            }
+        }
+    }
+    let method_for = |hook: &str| -> Option<syn::Ident> {
+        registered.iter().find(|(h, _)| h == hook).map(|(_, m)| m.clone())
+    };
+
+    let init_call = match method_for("init") {
+        Some(m) => quote!(#ident::#m(&pb)?;),
+        None => quote!(),
+    };
+    let start_body = match method_for("start") {
+        Some(m) => quote!(#ident::#m(pb)),
+        None => quote!({ let _ = pb; Ok(()) }),
+    };
+    let close_body = match method_for("close") {
+        Some(m) => quote!(#ident::#m(pb)),
+        None => quote!({ let _ = pb; Ok(()) }),
+    };
+    let assignments: Vec<TokenStream2> = registered
+        .iter()
+        .filter(|(h, _)| h != "init")
+        .map(|(h, m)| {
+            let field = syn::Ident::new(h, Span::call_site());
+            quote!(functions.#field = Some(#ident::#m);)
+        })
+        .collect();
+    let hook_names: Vec<&String> = registered
+        .iter()
+        .filter(|(h, _)| h != "init")
+        .map(|(h, _)| h)
+        .collect();
+
+    item.items.push(syn::parse_quote! {
+        /// The compile time description of this plugin.
+        pub const PLUGIN_METADATA: ::slapi_r_plugin::plugin::Slapi_R_Plugin_Metadata = ::slapi_r_plugin::plugin::Slapi_R_Plugin_Metadata {
+            name: #name,
+            kind: #kind,
+            version: env!("CARGO_PKG_VERSION"),
+            description: #description,
+            hooks: &[#(#hook_names),*],
+        };
+    });
+    item.items.push(syn::parse_quote! {
+        /// The callbacks of this plugin, as they are registered with
+        /// Directory Server. Tests can give these to
+        /// slapi_r_plugin::replay::replay.
+        pub fn plugin_functions() -> ::slapi_r_plugin::plugin::Slapi_R_Plugin_FN {
+            let mut functions = ::slapi_r_plugin::plugin::Slapi_R_Plugin_FN::new();
+            #(#assignments)*
+            functions
+        }
+    });
+
+    Ok(quote! {
+        #item
+
+        impl ::slapi_r_plugin::plugin::Slapi_Plugin_V3 for #ident {
+            fn init<T: ::slapi_r_plugin::pblock::Slapi_PBlock_Init_V3>(pb: T) -> Result<(), ::slapi_r_plugin::error::PluginRegistrationError> {
+                #init_call
+                let mut p_manager = ::slapi_r_plugin::plugin::Slapi_R_Plugin_Manager::new();
+                p_manager.name = #name;
+                p_manager.kind = #kind;
+                if let Some(p) = option_env!("SLAPI_R_PLUGIN_PRECEDENCE") {
+                    p_manager.precedence = p.parse().ok();
+                }
+                if let Some(t) = option_env!("SLAPI_R_PLUGIN_DEPENDS_ON_TYPE") {
+                    p_manager.depends_on_type = t.split(',').filter(|s| !s.is_empty()).collect();
+                }
+                if let Some(n) = option_env!("SLAPI_R_PLUGIN_DEPENDS_ON_NAMED") {
+                    p_manager.depends_on_named = n.split(',').filter(|s| !s.is_empty()).collect();
+                }
+                p_manager.functions = #ident::plugin_functions();
+                p_manager.register(pb)
+            }
+
+            fn start<T: ::slapi_r_plugin::pblock::Slapi_PBlock_V3>(pb: &T) -> Result<(), ::slapi_r_plugin::error::PluginOperationError> {
+                #start_body
+            }
+
+            fn close<T: ::slapi_r_plugin::pblock::Slapi_PBlock_V3>(pb: &T) -> Result<(), ::slapi_r_plugin::error::PluginOperationError> {
+                #close_body
+            }
+        }
+
+        ::slapi_r_plugin::slapi_r_plugin_init!(#ident);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand, PluginArgs};
+
+    fn args(kind: &str) -> PluginArgs {
+        PluginArgs {
+            name: Some(syn::parse_quote!("test")),
+            kind: Some(syn::LitStr::new(kind, proc_macro2::Span::call_site())),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_expand_registers_hooks() {
+        let item = syn::parse_quote! {
+            impl TestPlugin {
+                #[pre_add]
+                #[pre_modify]
+                fn intercept<T: Slapi_PBlock_V3>(pb: &T) -> Result<(), PluginOperationError> { Ok(()) }
+            }
+        };
+        let out = expand(args("preoperation"), item).unwrap().to_string();
+        assert!(out.contains("functions . pre_add = Some (TestPlugin :: intercept)"));
+        assert!(out.contains("functions . pre_modify = Some (TestPlugin :: intercept)"));
+        assert!(out.contains("p_manager . functions = TestPlugin :: plugin_functions ()"));
+        assert!(!out.contains("# [pre_add]"));
+    }
+
+    #[test]
+    fn test_expand_rejects_bad_signatures() {
+        let item = syn::parse_quote! {
+            impl TestPlugin {
+                #[pre_search]
+                fn search(&self) -> Result<(), PluginOperationError> { Ok(()) }
+            }
+        };
+        let err = expand(args("preoperation"), item).unwrap_err().to_string();
+        assert!(err.contains("can not take self"));
+
+        let item = syn::parse_quote! {
+            impl TestPlugin {
+                #[start]
+                fn start<T: Slapi_PBlock_V3>(pb: &T) { }
+            }
+        };
+        assert!(expand(args("preoperation"), item).is_err());
+
+        let item = syn::parse_quote! { impl TestPlugin {} };
+        assert!(expand(args("preop"), item).is_err());
+
+        let item = syn::parse_quote! { impl TestPlugin {} };
+        let out = expand(PluginArgs::default(), item).unwrap().to_string();
+        assert!(out.contains("env ! (\"SLAPI_R_PLUGIN_NAME\")"));
+    }
+}
@@ -0,0 +1,93 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// With the bindgen feature, generate the bindings from slapi-plugin.h. Without
+// it, or when the header can not be found, src/lib.rs uses the bindings that
+// are checked in as src/fallback.rs.
+
+#[cfg(feature = "bindgen")]
+extern crate bindgen;
+
+use std::env;
+use std::path::PathBuf;
+
+// Where slapi-plugin.h is installed, in the order they are searched.
+const INCLUDE_DIRS: [&str; 3] = [
+    "/usr/include/dirsrv",
+    "/usr/local/include/dirsrv",
+    "/opt/dirsrv/include/dirsrv",
+];
+
+// slapi-plugin.h includes nspr.h and ldap.h, so these must be searched too.
+#[cfg(feature = "bindgen")]
+const NSPR_INCLUDE_DIRS: [&str; 2] = [
+    "/usr/include/nspr4",
+    "/usr/include/nspr",
+];
+
+fn find_dir(var: &str, dirs: &[&str], file: &str) -> Option<PathBuf> {
+    if let Ok(dir) = env::var(var) {
+        return Some(PathBuf::from(dir));
+    }
+    dirs.iter().map(PathBuf::from).find(|d| d.join(file).exists())
+}
+
+#[cfg(feature = "bindgen")]
+fn generate(include_dir: PathBuf) -> bool {
+    let mut builder = bindgen::Builder::default()
+        .header("wrapper.h")
+        .clang_arg(format!("-I{}", include_dir.display()))
+        // C #defines are ints, so generate them as c_int rather than u32.
+        .default_macro_constant_type(bindgen::MacroTypeVariation::Signed)
+        .allowlist_function("slapi_.*")
+        .allowlist_type("Slapi_.*|berval|LDAPMod")
+        .allowlist_var("SLAPI_.*|LDAP_.*|DSE_FLAG_.*")
+        .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()));
+    if let Some(nspr) = find_dir("NSPR_INCLUDE_DIR", &NSPR_INCLUDE_DIRS, "nspr.h") {
+        builder = builder.clang_arg(format!("-I{}", nspr.display()));
+    }
+    let bindings = match builder.generate() {
+        Ok(b) => b,
+        Err(e) => {
+            println!("cargo:warning=bindgen failed, using the fallback bindings: {}", e);
+            return false;
+        }
+    };
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("bindings.rs");
+    bindings.write_to_file(out).expect("Unable to write bindings.rs");
+    true
+}
+
+#[cfg(not(feature = "bindgen"))]
+fn generate(_include_dir: PathBuf) -> bool {
+    false
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-env-changed=DIRSRV_INCLUDE_DIR");
+    println!("cargo:rerun-if-env-changed=NSPR_INCLUDE_DIR");
+    println!("cargo:rustc-check-cfg=cfg(slapi_r_bindgen)");
+
+    let generated = match find_dir("DIRSRV_INCLUDE_DIR", &INCLUDE_DIRS, "slapi-plugin.h") {
+        Some(include_dir) => generate(include_dir),
+        None => {
+            if cfg!(feature = "bindgen") {
+                println!("cargo:warning=slapi-plugin.h not found, set DIRSRV_INCLUDE_DIR. Using the fallback bindings.");
+            }
+            false
+        }
+    };
+    if generated {
+        println!("cargo:rustc-cfg=slapi_r_bindgen");
+    }
+}
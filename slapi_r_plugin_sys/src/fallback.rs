@@ -0,0 +1,362 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// The bindings used when bindgen is not run. These are written to match what
+// bindgen generates from slapi-plugin.h, for the part of the header that
+// slapi_r_plugin uses. When adding to them, copy the types from the header:
+// int is c_int, unsigned long is c_ulong, and char * is *mut c_char.
+
+// Opaque types. These are only ever handled by pointer.
+
+/// A parameter block, passed to every plugin callback.
+#[repr(C)]
+pub struct Slapi_PBlock {
+    _unused: [u8; 0],
+}
+/// An entry.
+#[repr(C)]
+pub struct Slapi_Entry {
+    _unused: [u8; 0],
+}
+/// An attribute of an entry.
+#[repr(C)]
+pub struct Slapi_Attr {
+    _unused: [u8; 0],
+}
+/// A value of an attribute.
+#[repr(C)]
+pub struct Slapi_Value {
+    _unused: [u8; 0],
+}
+/// A DN.
+#[repr(C)]
+pub struct Slapi_DN {
+    _unused: [u8; 0],
+}
+/// An operation in progress.
+#[repr(C)]
+pub struct Slapi_Operation {
+    _unused: [u8; 0],
+}
+/// An administrative task.
+#[repr(C)]
+pub struct Slapi_Task {
+    _unused: [u8; 0],
+}
+/// The identity of a plugin, used for internal operations.
+#[repr(C)]
+pub struct Slapi_ComponentId {
+    _unused: [u8; 0],
+}
+/// An LDAP control.
+#[repr(C)]
+pub struct LDAPControl {
+    _unused: [u8; 0],
+}
+/// A modification of an attribute.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ldapmod {
+    /// The LDAP_MOD_* type, with LDAP_MOD_BVALUES if the values are bervals.
+    pub mod_op: ::std::os::raw::c_int,
+    /// The attribute that is modified.
+    pub mod_type: *mut ::std::os::raw::c_char,
+    /// The null terminated array of values.
+    pub mod_vals: ldapmod_mod_vals_u,
+}
+/// The values of a modification, as strings or as bervals.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union ldapmod_mod_vals_u {
+    /// The values, without LDAP_MOD_BVALUES.
+    pub modv_strvals: *mut *mut ::std::os::raw::c_char,
+    /// The values, with LDAP_MOD_BVALUES.
+    pub modv_bvals: *mut *mut berval,
+}
+pub type LDAPMod = ldapmod;
+
+/// A length and a pointer to that many bytes.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct berval {
+    /// The length of bv_val.
+    pub bv_len: ::std::os::raw::c_ulong,
+    /// The bytes of the value, which need not end in a null.
+    pub bv_val: *mut ::std::os::raw::c_char,
+}
+
+/// A handle to a job on the event queue.
+pub type Slapi_Eq_Context = *mut ::std::os::raw::c_void;
+
+/// A function run by the event queue.
+pub type slapi_eq_fn_t = ::std::option::Option<unsafe extern "C" fn(when: ::libc::time_t, arg: *mut ::std::os::raw::c_void)>;
+
+/// A callback on a change to cn=config, or on the add of a task.
+pub type dseCallbackFn = ::std::option::Option<unsafe extern "C" fn(pb: *mut Slapi_PBlock,
+                                                                    e: *mut Slapi_Entry,
+                                                                    entryAfter: *mut Slapi_Entry,
+                                                                    returncode: *mut ::std::os::raw::c_int,
+                                                                    returntext: *mut ::std::os::raw::c_char,
+                                                                    arg: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int>;
+
+/// A callback on the cancel or destruction of a task.
+pub type TaskCallbackFn = ::std::option::Option<unsafe extern "C" fn(task: *mut Slapi_Task)>;
+
+/// The init function of a plugin.
+pub type slapi_plugin_init_fnptr = ::std::option::Option<unsafe extern "C" fn(pb: *mut Slapi_PBlock) -> ::std::os::raw::c_int>;
+
+pub const LDAP_SUCCESS: ::std::os::raw::c_int = 0;
+pub const LDAP_OPERATIONS_ERROR: ::std::os::raw::c_int = 0x01;
+pub const LDAP_NO_SUCH_OBJECT: ::std::os::raw::c_int = 0x20;
+pub const LDAP_INSUFFICIENT_ACCESS: ::std::os::raw::c_int = 0x32;
+pub const LDAP_UNWILLING_TO_PERFORM: ::std::os::raw::c_int = 0x35;
+pub const LDAP_OBJECT_CLASS_VIOLATION: ::std::os::raw::c_int = 0x41;
+pub const LDAP_ALREADY_EXISTS: ::std::os::raw::c_int = 0x44;
+
+pub const LDAP_SCOPE_BASE: ::std::os::raw::c_int = 0;
+
+pub const LDAP_MOD_ADD: ::std::os::raw::c_int = 0x00;
+pub const LDAP_MOD_DELETE: ::std::os::raw::c_int = 0x01;
+pub const LDAP_MOD_REPLACE: ::std::os::raw::c_int = 0x02;
+pub const LDAP_MOD_BVALUES: ::std::os::raw::c_int = 0x80;
+
+pub const SLAPI_PLUGIN_VERSION_01: &[u8; 3] = b"01\0";
+pub const SLAPI_PLUGIN_VERSION_02: &[u8; 3] = b"02\0";
+pub const SLAPI_PLUGIN_VERSION_03: &[u8; 3] = b"03\0";
+
+pub const SLAPI_PLUGIN_CONFIG_ENTRY: ::std::os::raw::c_int = 1;
+pub const SLAPI_PLUGIN_PRIVATE: ::std::os::raw::c_int = 4;
+pub const SLAPI_PLUGIN_TYPE: ::std::os::raw::c_int = 5;
+pub const SLAPI_PLUGIN_VERSION: ::std::os::raw::c_int = 8;
+pub const SLAPI_PLUGIN_OPRETURN: ::std::os::raw::c_int = 9;
+pub const SLAPI_PLUGIN_DESCRIPTION: ::std::os::raw::c_int = 12;
+pub const SLAPI_PLUGIN_IDENTITY: ::std::os::raw::c_int = 13;
+pub const SLAPI_PLUGIN_INTOP_RESULT: ::std::os::raw::c_int = 15;
+
+pub const SLAPI_ADD_ENTRY: ::std::os::raw::c_int = 60;
+pub const SLAPI_MODIFY_MODS: ::std::os::raw::c_int = 90;
+pub const SLAPI_SEARCH_STRFILTER: ::std::os::raw::c_int = 115;
+pub const SLAPI_OPERATION: ::std::os::raw::c_int = 132;
+pub const SLAPI_CONN_ID: ::std::os::raw::c_int = 139;
+pub const SLAPI_OPINITIATED_TIME: ::std::os::raw::c_int = 140;
+pub const SLAPI_REQUESTOR_DN: ::std::os::raw::c_int = 141;
+pub const SLAPI_IS_REPLICATED_OPERATION: ::std::os::raw::c_int = 142;
+pub const SLAPI_SEARCH_RESULT_ENTRY: ::std::os::raw::c_int = 194;
+pub const SLAPI_OPERATION_ID: ::std::os::raw::c_int = 744;
+pub const SLAPI_TXN: ::std::os::raw::c_int = 882;
+pub const SLAPI_SEARCH_ENTRY_COPY: ::std::os::raw::c_int = 3002;
+
+pub const SLAPI_PLUGIN_CLOSE_FN: ::std::os::raw::c_int = 210;
+pub const SLAPI_PLUGIN_START_FN: ::std::os::raw::c_int = 212;
+
+pub const SLAPI_PLUGIN_PRE_BIND_FN: ::std::os::raw::c_int = 401;
+pub const SLAPI_PLUGIN_PRE_UNBIND_FN: ::std::os::raw::c_int = 402;
+pub const SLAPI_PLUGIN_PRE_SEARCH_FN: ::std::os::raw::c_int = 403;
+pub const SLAPI_PLUGIN_PRE_COMPARE_FN: ::std::os::raw::c_int = 404;
+pub const SLAPI_PLUGIN_PRE_MODIFY_FN: ::std::os::raw::c_int = 405;
+pub const SLAPI_PLUGIN_PRE_MODRDN_FN: ::std::os::raw::c_int = 406;
+pub const SLAPI_PLUGIN_PRE_ADD_FN: ::std::os::raw::c_int = 407;
+pub const SLAPI_PLUGIN_PRE_DELETE_FN: ::std::os::raw::c_int = 408;
+pub const SLAPI_PLUGIN_PRE_ABANDON_FN: ::std::os::raw::c_int = 409;
+pub const SLAPI_PLUGIN_PRE_ENTRY_FN: ::std::os::raw::c_int = 410;
+pub const SLAPI_PLUGIN_PRE_REFERAL_FN: ::std::os::raw::c_int = 411;
+pub const SLAPI_PLUGIN_PRE_RESULT_FN: ::std::os::raw::c_int = 412;
+pub const SLAPI_PLUGIN_PRE_EXTOP_FN: ::std::os::raw::c_int = 413;
+pub const SLAPI_PLUGIN_INTERNAL_PRE_ADD_FN: ::std::os::raw::c_int = 420;
+pub const SLAPI_PLUGIN_INTERNAL_PRE_MODIFY_FN: ::std::os::raw::c_int = 421;
+pub const SLAPI_PLUGIN_INTERNAL_PRE_MODRDN_FN: ::std::os::raw::c_int = 422;
+pub const SLAPI_PLUGIN_INTERNAL_PRE_DELETE_FN: ::std::os::raw::c_int = 423;
+pub const SLAPI_PLUGIN_BE_PRE_ADD_FN: ::std::os::raw::c_int = 450;
+pub const SLAPI_PLUGIN_BE_PRE_MODIFY_FN: ::std::os::raw::c_int = 451;
+pub const SLAPI_PLUGIN_BE_PRE_MODRDN_FN: ::std::os::raw::c_int = 452;
+pub const SLAPI_PLUGIN_BE_PRE_DELETE_FN: ::std::os::raw::c_int = 453;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_ADD_FN: ::std::os::raw::c_int = 460;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_MODIFY_FN: ::std::os::raw::c_int = 461;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_MODRDN_FN: ::std::os::raw::c_int = 462;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_DELETE_FN: ::std::os::raw::c_int = 463;
+pub const SLAPI_PLUGIN_BE_TXN_PRE_DELETE_TOMBSTONE_FN: ::std::os::raw::c_int = 464;
+pub const SLAPI_PLUGIN_POST_BIND_FN: ::std::os::raw::c_int = 501;
+pub const SLAPI_PLUGIN_POST_UNBIND_FN: ::std::os::raw::c_int = 502;
+pub const SLAPI_PLUGIN_POST_SEARCH_FN: ::std::os::raw::c_int = 503;
+pub const SLAPI_PLUGIN_POST_COMPARE_FN: ::std::os::raw::c_int = 504;
+pub const SLAPI_PLUGIN_POST_MODIFY_FN: ::std::os::raw::c_int = 505;
+pub const SLAPI_PLUGIN_POST_MODRDN_FN: ::std::os::raw::c_int = 506;
+pub const SLAPI_PLUGIN_POST_ADD_FN: ::std::os::raw::c_int = 507;
+pub const SLAPI_PLUGIN_POST_DELETE_FN: ::std::os::raw::c_int = 508;
+pub const SLAPI_PLUGIN_POST_ABANDON_FN: ::std::os::raw::c_int = 509;
+pub const SLAPI_PLUGIN_POST_ENTRY_FN: ::std::os::raw::c_int = 510;
+pub const SLAPI_PLUGIN_POST_REFERAL_FN: ::std::os::raw::c_int = 511;
+pub const SLAPI_PLUGIN_POST_RESULT_FN: ::std::os::raw::c_int = 512;
+pub const SLAPI_PLUGIN_INTERNAL_POST_ADD_FN: ::std::os::raw::c_int = 520;
+pub const SLAPI_PLUGIN_INTERNAL_POST_MODIFY_FN: ::std::os::raw::c_int = 521;
+pub const SLAPI_PLUGIN_INTERNAL_POST_MODRDN_FN: ::std::os::raw::c_int = 522;
+pub const SLAPI_PLUGIN_INTERNAL_POST_DELETE_FN: ::std::os::raw::c_int = 523;
+pub const SLAPI_PLUGIN_BE_POST_ADD_FN: ::std::os::raw::c_int = 550;
+pub const SLAPI_PLUGIN_BE_POST_MODIFY_FN: ::std::os::raw::c_int = 551;
+pub const SLAPI_PLUGIN_BE_POST_MODRDN_FN: ::std::os::raw::c_int = 552;
+pub const SLAPI_PLUGIN_BE_POST_DELETE_FN: ::std::os::raw::c_int = 553;
+pub const SLAPI_PLUGIN_BE_TXN_POST_ADD_FN: ::std::os::raw::c_int = 560;
+pub const SLAPI_PLUGIN_BE_TXN_POST_MODIFY_FN: ::std::os::raw::c_int = 561;
+pub const SLAPI_PLUGIN_BE_TXN_POST_MODRDN_FN: ::std::os::raw::c_int = 562;
+pub const SLAPI_PLUGIN_BE_TXN_POST_DELETE_FN: ::std::os::raw::c_int = 563;
+
+pub const SLAPI_ACL_COMPARE: ::std::os::raw::c_int = 0x01;
+pub const SLAPI_ACL_SEARCH: ::std::os::raw::c_int = 0x02;
+pub const SLAPI_ACL_READ: ::std::os::raw::c_int = 0x04;
+pub const SLAPI_ACL_WRITE: ::std::os::raw::c_int = 0x08;
+pub const SLAPI_ACL_DELETE: ::std::os::raw::c_int = 0x10;
+pub const SLAPI_ACL_ADD: ::std::os::raw::c_int = 0x20;
+pub const SLAPI_ACL_SELF: ::std::os::raw::c_int = 0x40;
+pub const SLAPI_ACL_PROXY: ::std::os::raw::c_int = 0x80;
+
+pub const DSE_FLAG_PREOP: ::std::os::raw::c_int = 0x0002;
+pub const SLAPI_DSE_CALLBACK_OK: ::std::os::raw::c_int = 1;
+pub const SLAPI_DSE_CALLBACK_ERROR: ::std::os::raw::c_int = -1;
+pub const SLAPI_DSE_CALLBACK_DO_NOT_APPLY: ::std::os::raw::c_int = 0;
+
+pub const SLAPI_OPERATION_BIND: ::std::os::raw::c_int = 0x00000001;
+pub const SLAPI_OPERATION_UNBIND: ::std::os::raw::c_int = 0x00000002;
+pub const SLAPI_OPERATION_SEARCH: ::std::os::raw::c_int = 0x00000004;
+pub const SLAPI_OPERATION_MODIFY: ::std::os::raw::c_int = 0x00000008;
+pub const SLAPI_OPERATION_ADD: ::std::os::raw::c_int = 0x00000010;
+pub const SLAPI_OPERATION_DELETE: ::std::os::raw::c_int = 0x00000020;
+pub const SLAPI_OPERATION_MODRDN: ::std::os::raw::c_int = 0x00000040;
+pub const SLAPI_OPERATION_COMPARE: ::std::os::raw::c_int = 0x00000080;
+pub const SLAPI_OPERATION_ABANDON: ::std::os::raw::c_int = 0x00000100;
+pub const SLAPI_OPERATION_EXTENDED: ::std::os::raw::c_int = 0x00000200;
+
+pub const SLAPI_LOG_FATAL: ::std::os::raw::c_int = 0;
+pub const SLAPI_LOG_TRACE: ::std::os::raw::c_int = 1;
+pub const SLAPI_LOG_PACKETS: ::std::os::raw::c_int = 2;
+pub const SLAPI_LOG_ARGS: ::std::os::raw::c_int = 3;
+pub const SLAPI_LOG_CONNS: ::std::os::raw::c_int = 4;
+pub const SLAPI_LOG_BER: ::std::os::raw::c_int = 5;
+pub const SLAPI_LOG_FILTER: ::std::os::raw::c_int = 6;
+pub const SLAPI_LOG_CONFIG: ::std::os::raw::c_int = 7;
+pub const SLAPI_LOG_ACL: ::std::os::raw::c_int = 8;
+pub const SLAPI_LOG_SHELL: ::std::os::raw::c_int = 9;
+pub const SLAPI_LOG_PARSE: ::std::os::raw::c_int = 10;
+pub const SLAPI_LOG_HOUSE: ::std::os::raw::c_int = 11;
+pub const SLAPI_LOG_REPL: ::std::os::raw::c_int = 12;
+pub const SLAPI_LOG_CACHE: ::std::os::raw::c_int = 13;
+pub const SLAPI_LOG_PLUGIN: ::std::os::raw::c_int = 14;
+pub const SLAPI_LOG_TIMING: ::std::os::raw::c_int = 15;
+pub const SLAPI_LOG_BACKLDBM: ::std::os::raw::c_int = 16;
+pub const SLAPI_LOG_ACLSUMMARY: ::std::os::raw::c_int = 17;
+pub const SLAPI_LOG_NUNCSTANS: ::std::os::raw::c_int = 18;
+pub const SLAPI_LOG_EMERG: ::std::os::raw::c_int = 19;
+pub const SLAPI_LOG_ALERT: ::std::os::raw::c_int = 20;
+pub const SLAPI_LOG_CRIT: ::std::os::raw::c_int = 21;
+pub const SLAPI_LOG_ERR: ::std::os::raw::c_int = 22;
+pub const SLAPI_LOG_WARNING: ::std::os::raw::c_int = 23;
+pub const SLAPI_LOG_NOTICE: ::std::os::raw::c_int = 24;
+pub const SLAPI_LOG_INFO: ::std::os::raw::c_int = 25;
+pub const SLAPI_LOG_DEBUG: ::std::os::raw::c_int = 26;
+
+pub const SLAPI_OP_NOTE_UNINDEXED: ::std::os::raw::c_uint = 0x01;
+pub const SLAPI_OP_NOTE_SIMPLEPAGED: ::std::os::raw::c_uint = 0x02;
+pub const SLAPI_OP_NOTE_FULL_UNINDEXED: ::std::os::raw::c_uint = 0x04;
+pub const SLAPI_OP_NOTE_FILTER_INVALID: ::std::os::raw::c_uint = 0x08;
+
+extern "C" {
+    // pblock.c
+    pub fn slapi_pblock_new() -> *mut Slapi_PBlock;
+    pub fn slapi_pblock_init(pb: *mut Slapi_PBlock);
+    pub fn slapi_pblock_destroy(pb: *mut Slapi_PBlock);
+    pub fn slapi_pblock_get(pb: *mut Slapi_PBlock, arg: ::std::os::raw::c_int, value: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+    pub fn slapi_pblock_set(pb: *mut Slapi_PBlock, arg: ::std::os::raw::c_int, value: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+
+    // result.c
+    pub fn slapi_send_ldap_result(pb: *mut Slapi_PBlock, err: ::std::os::raw::c_int, matched: *mut ::std::os::raw::c_char,
+                                  text: *mut ::std::os::raw::c_char, nentries: ::std::os::raw::c_int, urls: *mut *mut berval);
+
+    // acl
+    pub fn slapi_access_allowed(pb: *mut Slapi_PBlock, e: *mut Slapi_Entry, attr: *mut ::std::os::raw::c_char,
+                                val: *mut berval, access: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+    pub fn slapi_acl_check_mods(pb: *mut Slapi_PBlock, e: *mut Slapi_Entry, mods: *mut *mut LDAPMod,
+                                errbuf: *mut *mut ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+
+    // ch_malloc.c
+    pub fn slapi_ch_free_string(s: *mut *mut ::std::os::raw::c_char);
+
+    // log.c
+    pub fn slapi_log_error(loglevel: ::std::os::raw::c_int, subsystem: *mut ::std::os::raw::c_char,
+                           fmt: *mut ::std::os::raw::c_char, ...) -> ::std::os::raw::c_int;
+    pub fn slapi_is_loglevel_set(loglevel: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+    pub fn slapi_log_access(level: ::std::os::raw::c_int, fmt: *const ::std::os::raw::c_char, ...) -> ::std::os::raw::c_int;
+
+    // entry.c
+    pub fn slapi_entry_first_attr(e: *const Slapi_Entry, attr: *mut *mut Slapi_Attr) -> ::std::os::raw::c_int;
+    pub fn slapi_entry_next_attr(e: *const Slapi_Entry, prevattr: *mut Slapi_Attr, attr: *mut *mut Slapi_Attr) -> ::std::os::raw::c_int;
+    pub fn slapi_entry_attr_delete(e: *mut Slapi_Entry, type_: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+    pub fn slapi_entry_attr_get_charptr(e: *const Slapi_Entry, type_: *const ::std::os::raw::c_char) -> *mut ::std::os::raw::c_char;
+    pub fn slapi_entry_attr_set_charptr(e: *mut Slapi_Entry, type_: *const ::std::os::raw::c_char, value: *const ::std::os::raw::c_char);
+    pub fn slapi_entry_get_dn_const(e: *const Slapi_Entry) -> *const ::std::os::raw::c_char;
+    pub fn slapi_entry_free(e: *mut Slapi_Entry);
+    pub fn slapi_entry_dup(e: *const Slapi_Entry) -> *mut Slapi_Entry;
+    pub fn slapi_str2entry(s: *mut ::std::os::raw::c_char, flags: ::std::os::raw::c_int) -> *mut Slapi_Entry;
+
+    // attr.c and value.c
+    pub fn slapi_attr_get_type(attr: *const Slapi_Attr, type_: *mut *mut ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+    pub fn slapi_attr_first_value(a: *mut Slapi_Attr, v: *mut *mut Slapi_Value) -> ::std::os::raw::c_int;
+    pub fn slapi_attr_next_value(a: *mut Slapi_Attr, hint: ::std::os::raw::c_int, v: *mut *mut Slapi_Value) -> ::std::os::raw::c_int;
+    pub fn slapi_value_get_berval(value: *const Slapi_Value) -> *const berval;
+
+    // operation.c and dn.c
+    pub fn slapi_op_get_type(op: *mut Slapi_Operation) -> ::std::os::raw::c_ulong;
+    pub fn slapi_sdn_get_dn(sdn: *const Slapi_DN) -> *const ::std::os::raw::c_char;
+
+    // dse.c
+    pub fn slapi_config_register_callback(operation: ::std::os::raw::c_int, flags: ::std::os::raw::c_int, base: *const ::std::os::raw::c_char,
+                                          scope: ::std::os::raw::c_int, filter: *const ::std::os::raw::c_char, fn_: dseCallbackFn,
+                                          fn_arg: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+    pub fn slapi_config_remove_callback(operation: ::std::os::raw::c_int, flags: ::std::os::raw::c_int, base: *const ::std::os::raw::c_char,
+                                        scope: ::std::os::raw::c_int, filter: *const ::std::os::raw::c_char, fn_: dseCallbackFn) -> ::std::os::raw::c_int;
+
+    // plugin_internal_op.c
+    pub fn slapi_add_entry_internal_set_pb(pb: *mut Slapi_PBlock, e: *mut Slapi_Entry, controls: *mut *mut LDAPControl,
+                                           plugin_identity: *mut Slapi_ComponentId, operation_flags: ::std::os::raw::c_int);
+    pub fn slapi_add_internal_pb(pb: *mut Slapi_PBlock) -> ::std::os::raw::c_int;
+    pub fn slapi_delete_internal_set_pb(pb: *mut Slapi_PBlock, dn: *const ::std::os::raw::c_char, controls: *mut *mut LDAPControl,
+                                        uniqueid: *const ::std::os::raw::c_char, plugin_identity: *mut Slapi_ComponentId,
+                                        operation_flags: ::std::os::raw::c_int);
+    pub fn slapi_delete_internal_pb(pb: *mut Slapi_PBlock) -> ::std::os::raw::c_int;
+
+    // eventq.c
+    pub fn slapi_eq_once(fn_: slapi_eq_fn_t, arg: *mut ::std::os::raw::c_void, when: ::libc::time_t) -> Slapi_Eq_Context;
+    pub fn slapi_eq_repeat(fn_: slapi_eq_fn_t, arg: *mut ::std::os::raw::c_void, when: ::libc::time_t,
+                           interval: ::std::os::raw::c_ulong) -> Slapi_Eq_Context;
+    pub fn slapi_eq_cancel(ctx: Slapi_Eq_Context) -> ::std::os::raw::c_int;
+    pub fn slapi_current_time() -> ::libc::time_t;
+
+    // task.c
+    pub fn slapi_task_register_handler(name: *const ::std::os::raw::c_char, func: dseCallbackFn) -> ::std::os::raw::c_int;
+    pub fn slapi_plugin_task_unregister_handler(name: *const ::std::os::raw::c_char, func: dseCallbackFn) -> ::std::os::raw::c_int;
+    pub fn slapi_new_task(dn: *const ::std::os::raw::c_char) -> *mut Slapi_Task;
+    pub fn slapi_task_set_data(task: *mut Slapi_Task, data: *mut ::std::os::raw::c_void);
+    pub fn slapi_task_get_data(task: *mut Slapi_Task) -> *mut ::std::os::raw::c_void;
+    pub fn slapi_task_set_cancel_fn(task: *mut Slapi_Task, func: TaskCallbackFn);
+    pub fn slapi_task_set_destructor_fn(task: *mut Slapi_Task, func: TaskCallbackFn);
+    pub fn slapi_task_inc_refcount(task: *mut Slapi_Task);
+    pub fn slapi_task_dec_refcount(task: *mut Slapi_Task);
+    pub fn slapi_task_begin(task: *mut Slapi_Task, total_work: ::std::os::raw::c_int);
+    pub fn slapi_task_inc_progress(task: *mut Slapi_Task);
+    pub fn slapi_task_finish(task: *mut Slapi_Task, rc: ::std::os::raw::c_int);
+    pub fn slapi_task_log_status(task: *mut Slapi_Task, format: *mut ::std::os::raw::c_char, ...);
+    pub fn slapi_task_log_notice(task: *mut Slapi_Task, format: *mut ::std::os::raw::c_char, ...);
+
+    // plugin.c
+    pub fn slapi_register_plugin(plugintype: *const ::std::os::raw::c_char, enabled: ::std::os::raw::c_int,
+                                 initsymbol: *const ::std::os::raw::c_char, initfunc: slapi_plugin_init_fnptr,
+                                 name: *const ::std::os::raw::c_char, argv: *mut *mut ::std::os::raw::c_char,
+                                 group_identity: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int;
+}
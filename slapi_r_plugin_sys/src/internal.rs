@@ -0,0 +1,51 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+// Constants and functions of libslapd that plugins rely on, but that are
+// declared in slapi-private.h and proto-slap.h rather than slapi-plugin.h.
+// These are not installed, so bindgen can not generate them.
+
+use super::{Slapi_DN, Slapi_Operation};
+
+pub const LDAP_DEBUG_STATS: ::std::os::raw::c_int = 0x100;
+
+pub const OP_FLAG_PS: ::std::os::raw::c_int = 0x000001;
+pub const OP_FLAG_PS_CHANGESONLY: ::std::os::raw::c_int = 0x000002;
+pub const OP_FLAG_GET_EFFECTIVE_RIGHTS: ::std::os::raw::c_int = 0x000004;
+pub const OP_FLAG_REPLICATED: ::std::os::raw::c_int = 0x000008;
+pub const OP_FLAG_REPL_FIXUP: ::std::os::raw::c_int = 0x000010;
+pub const OP_FLAG_INTERNAL: ::std::os::raw::c_int = 0x000020;
+pub const OP_FLAG_ACTION_LOG_ACCESS: ::std::os::raw::c_int = 0x000040;
+pub const OP_FLAG_ACTION_LOG_AUDIT: ::std::os::raw::c_int = 0x000080;
+pub const OP_FLAG_ACTION_SCHEMA_CHECK: ::std::os::raw::c_int = 0x000100;
+pub const OP_FLAG_ACTION_LOG_CHANGES: ::std::os::raw::c_int = 0x000200;
+pub const OP_FLAG_ACTION_INVOKE_FOR_REPLOP: ::std::os::raw::c_int = 0x000400;
+pub const OP_FLAG_NEVER_CHAIN: ::std::os::raw::c_int = 0x000800;
+pub const OP_FLAG_TOMBSTONE_ENTRY: ::std::os::raw::c_int = 0x001000;
+pub const OP_FLAG_RESURECT_ENTRY: ::std::os::raw::c_int = 0x002000;
+pub const OP_FLAG_LEGACY_REPLICATION_DN: ::std::os::raw::c_int = 0x004000;
+pub const OP_FLAG_ACTION_NOLOG: ::std::os::raw::c_int = 0x008000;
+pub const OP_FLAG_SKIP_MODIFIED_ATTRS: ::std::os::raw::c_int = 0x010000;
+pub const OP_FLAG_REPL_RUV: ::std::os::raw::c_int = 0x020000;
+pub const OP_FLAG_PAGED_RESULTS: ::std::os::raw::c_int = 0x040000;
+pub const OP_FLAG_SERVER_SIDE_SORTING: ::std::os::raw::c_int = 0x080000;
+pub const OP_FLAG_REVERSE_CANDIDATE_ORDER: ::std::os::raw::c_int = 0x100000;
+pub const OP_FLAG_NEVER_CACHE: ::std::os::raw::c_int = 0x200000;
+pub const OP_FLAG_TOMBSTONE_FIXUP: ::std::os::raw::c_int = 0x400000;
+pub const OP_FLAG_BULK_IMPORT: ::std::os::raw::c_int = 0x800000;
+pub const OP_FLAG_NOOP: ::std::os::raw::c_int = 0x01000000;
+
+extern "C" {
+    pub fn operation_is_flag_set(op: *mut Slapi_Operation, flag: ::std::os::raw::c_int) -> ::std::os::raw::c_int;
+    pub fn operation_get_target_spec(op: *mut Slapi_Operation) -> *mut Slapi_DN;
+    pub fn slapd_get_version_value() -> *mut ::std::os::raw::c_char;
+}
@@ -0,0 +1,124 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+//! Raw bindings to the Directory Server plugin API, slapi-plugin.h.
+//!
+//! Built with the bindgen feature, the bindings are generated from the
+//! installed header, found in DIRSRV_INCLUDE_DIR or /usr/include/dirsrv, so
+//! that every SLAPI_* constant and function is available with the types of
+//! the header. Otherwise the checked in bindings in src/fallback.rs are used.
+//!
+//! These are unsafe and untyped. Plugins should use slapi_r_plugin, which is
+//! built on top of this crate.
+
+#![allow(non_camel_case_types)]
+#![allow(non_upper_case_globals)]
+#![allow(non_snake_case)]
+#![allow(missing_docs)]
+
+extern crate libc;
+
+#[cfg(slapi_r_bindgen)]
+include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+#[cfg(not(slapi_r_bindgen))]
+include!("fallback.rs");
+
+mod internal;
+pub use internal::*;
+
+// These check the layout of the bindings that slapi_r_plugin relies on. They
+// run against the fallback bindings, or with the bindgen feature against the
+// installed headers, so that a wrong C type fails here rather than in a
+// running server.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+    use std::os::raw::c_char;
+    use std::os::raw::c_int;
+    use std::os::raw::c_ulong;
+    use std::os::raw::c_void;
+
+    // The signatures are checked as the tests are compiled.
+    const _PBLOCK_GET: unsafe extern "C" fn(*mut Slapi_PBlock, c_int, *mut c_void) -> c_int = slapi_pblock_get;
+    const _PBLOCK_SET: unsafe extern "C" fn(*mut Slapi_PBlock, c_int, *mut c_void) -> c_int = slapi_pblock_set;
+    const _SEND_LDAP_RESULT: unsafe extern "C" fn(*mut Slapi_PBlock, c_int, *mut c_char, *mut c_char, c_int, *mut *mut berval) = slapi_send_ldap_result;
+    const _ACCESS_ALLOWED: unsafe extern "C" fn(*mut Slapi_PBlock, *mut Slapi_Entry, *mut c_char, *mut berval, c_int) -> c_int = slapi_access_allowed;
+    const _LOG_ERROR: unsafe extern "C" fn(c_int, *mut c_char, *mut c_char, ...) -> c_int = slapi_log_error;
+    const _IS_LOGLEVEL_SET: unsafe extern "C" fn(c_int) -> c_int = slapi_is_loglevel_set;
+    const _OP_GET_TYPE: unsafe extern "C" fn(*mut Slapi_Operation) -> c_ulong = slapi_op_get_type;
+    const _OPERATION_IS_FLAG_SET: unsafe extern "C" fn(*mut Slapi_Operation, c_int) -> c_int = operation_is_flag_set;
+    const _ENTRY_FIRST_ATTR: unsafe extern "C" fn(*const Slapi_Entry, *mut *mut Slapi_Attr) -> c_int = slapi_entry_first_attr;
+    const _ATTR_NEXT_VALUE: unsafe extern "C" fn(*mut Slapi_Attr, c_int, *mut *mut Slapi_Value) -> c_int = slapi_attr_next_value;
+    const _TASK_FINISH: unsafe extern "C" fn(*mut Slapi_Task, c_int) = slapi_task_finish;
+    const _EQ_CANCEL: unsafe extern "C" fn(Slapi_Eq_Context) -> c_int = slapi_eq_cancel;
+
+    #[test]
+    fn test_layout_berval() {
+        // bv_len is a ber_len_t, an unsigned long, followed by the pointer.
+        assert_eq!(mem::size_of::<berval>(), mem::size_of::<c_ulong>() + mem::size_of::<*mut c_char>());
+        assert_eq!(mem::align_of::<berval>(), mem::align_of::<*mut c_char>());
+        let bv = berval { bv_len: 0, bv_val: ::std::ptr::null_mut() };
+        let base = &bv as *const berval as usize;
+        assert_eq!(&bv.bv_len as *const c_ulong as usize - base, 0);
+        assert_eq!(&bv.bv_val as *const *mut c_char as usize - base, mem::size_of::<c_ulong>());
+    }
+
+    #[test]
+    fn test_layout_ldapmod() {
+        // mod_op is an int, followed by the attribute and the union of
+        // value arrays, which are both pointers.
+        let ptr = mem::size_of::<*mut c_char>();
+        assert_eq!(mem::size_of::<ldapmod_mod_vals_u>(), ptr);
+        assert_eq!(mem::size_of::<LDAPMod>(), 3 * ptr);
+        let m: LDAPMod = unsafe { mem::zeroed() };
+        let base = &m as *const LDAPMod as usize;
+        assert_eq!(&m.mod_type as *const *mut c_char as usize - base, ptr);
+        assert_eq!(&m.mod_vals as *const ldapmod_mod_vals_u as usize - base, 2 * ptr);
+    }
+
+    #[test]
+    fn test_layout_callbacks() {
+        // Callbacks are nullable function pointers, so None must be NULL.
+        assert_eq!(mem::size_of::<dseCallbackFn>(), mem::size_of::<*mut c_void>());
+        assert_eq!(mem::size_of::<TaskCallbackFn>(), mem::size_of::<*mut c_void>());
+        assert_eq!(mem::size_of::<slapi_eq_fn_t>(), mem::size_of::<*mut c_void>());
+        assert_eq!(mem::size_of::<slapi_plugin_init_fnptr>(), mem::size_of::<*mut c_void>());
+    }
+
+    #[test]
+    fn test_layout_constants() {
+        // The constants are ints, and these are the values that are compiled
+        // into every plugin, so they must never change.
+        let values: [(c_int, c_int); 14] = [
+            (SLAPI_PLUGIN_PRIVATE, 4),
+            (SLAPI_PLUGIN_OPRETURN, 9),
+            (SLAPI_PLUGIN_INTOP_RESULT, 15),
+            (SLAPI_ADD_ENTRY, 60),
+            (SLAPI_SEARCH_STRFILTER, 115),
+            (SLAPI_OPERATION, 132),
+            (SLAPI_SEARCH_RESULT_ENTRY, 194),
+            (SLAPI_PLUGIN_PRE_SEARCH_FN, 403),
+            (SLAPI_PLUGIN_BE_TXN_POST_DELETE_FN, 563),
+            (SLAPI_TXN, 882),
+            (SLAPI_SEARCH_ENTRY_COPY, 3002),
+            (SLAPI_LOG_DEBUG, 26),
+            (LDAP_MOD_BVALUES, 0x80),
+            (SLAPI_OP_NOTE_FILTER_INVALID as c_int, 0x08),
+        ];
+        for &(value, expected) in values.iter() {
+            assert_eq!(value, expected);
+        }
+        assert_eq!(SLAPI_PLUGIN_VERSION_03, b"03\0");
+    }
+}
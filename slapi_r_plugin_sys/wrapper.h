@@ -0,0 +1,17 @@
+/*
+ * BEGIN COPYRIGHT BLOCK
+ * Copyright (C) 2016 Red Hat, Inc.
+ * All rights reserved.
+ *
+ * License: GPL (version 3 or any later version).
+ * See LICENSE for details.
+ * END COPYRIGHT BLOCK
+ *
+ * Author: William Brown <wibrown@redhat.com>
+ */
+
+/*
+ * The headers that bindgen generates slapi_r_plugin-sys from. Functions of
+ * libslapd that are not in slapi-plugin.h are declared in src/internal.rs.
+ */
+#include <slapi-plugin.h>
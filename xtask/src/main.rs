@@ -0,0 +1,256 @@
+//
+// BEGIN COPYRIGHT BLOCK
+// Copyright (C) 2016 Red Hat, Inc.
+// All rights reserved.
+//
+// License: GPL (version 3 or any later version).
+// See LICENSE for details. 
+// END COPYRIGHT BLOCK
+//
+// Author: William Brown <wibrown@redhat.com>
+//
+
+#![warn(missing_docs)]
+
+//! xtask builds every plugin of the workspace, and stages them for
+//! installation, so that autotools and the rpm spec only need one command.
+//!
+//! ```text
+//! cargo xtask generate
+//! cargo xtask build [--release]
+//! cargo xtask stage --destdir <dir> [--release] [--libdir <dir>] [--datadir <dir>]
+//! ```
+//!
+//! A plugin is any crate in plugins/ with a [package.metadata.dirsrv]
+//! section. generate writes the enable.ldif and rs389 class of each plugin,
+//! and the config schema of all of them to schema/, from their Cargo.toml.
+//! These are checked in, so run it after changing the metadata of a plugin.
+//! stage builds the plugins, then copies each plugin to
+//! <destdir>/<libdir>/dirsrv/plugins, its enable.ldif to
+//! <destdir>/<datadir>/dirsrv/examples/<name>, and the config schema to
+//! <destdir>/<datadir>/dirsrv/schema. The libdir and datadir default to lib
+//! and share, and may be absolute, IE /usr/lib64, as autotools and rpm give
+//! them.
+
+extern crate slapi_r_plugin_build;
+
+use slapi_r_plugin_build::PluginConfig;
+use slapi_r_plugin_build::SCHEMA_FILE;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+
+const USAGE: &str = "usage: cargo xtask generate
+       cargo xtask build [--release]
+       cargo xtask stage --destdir <dir> [--release] [--libdir <dir>] [--datadir <dir>]";
+
+/// A plugin crate of the workspace.
+struct Plugin {
+    dir: PathBuf,
+    config: PluginConfig,
+}
+
+/// The command line of the xtask.
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: String,
+    release: bool,
+    destdir: Option<PathBuf>,
+    libdir: String,
+    datadir: String,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let command = match args.next() {
+            Some(ref c) if c == "generate" || c == "build" || c == "stage" => c.clone(),
+            Some(c) => return Err(format!("unknown command {}", c)),
+            None => return Err("no command given".to_string()),
+        };
+        let mut options = Options {
+            command,
+            release: false,
+            destdir: None,
+            libdir: "lib".to_string(),
+            datadir: "share".to_string(),
+        };
+        while let Some(arg) = args.next() {
+            if arg == "--release" {
+                options.release = true;
+                continue;
+            }
+            let value = match args.next() {
+                Some(v) => v,
+                None => return Err(format!("{} requires a value", arg)),
+            };
+            match arg.as_str() {
+                "--destdir" => options.destdir = Some(PathBuf::from(value)),
+                "--libdir" => options.libdir = value,
+                "--datadir" => options.datadir = value,
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        if options.command == "stage" && options.destdir.is_none() {
+            return Err("stage requires --destdir".to_string());
+        }
+        Ok(options)
+    }
+
+    fn profile(&self) -> &'static str {
+        if self.release { "release" } else { "debug" }
+    }
+}
+
+/// The root of the workspace, which xtask is a member of.
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().to_path_buf()
+}
+
+/// Join a directory that may be absolute, as autotools gives $(libdir), under
+/// the destdir.
+fn under(destdir: &Path, dir: &str) -> PathBuf {
+    destdir.join(dir.trim_start_matches('/'))
+}
+
+fn find_plugins(root: &Path) -> Result<Vec<Plugin>, String> {
+    let dir = root.join("plugins");
+    let entries = fs::read_dir(&dir).map_err(|e| format!("unable to read {}: {}", dir.display(), e))?;
+    let mut dirs: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path())
+        .filter(|p| p.join("Cargo.toml").exists())
+        .collect();
+    dirs.sort();
+
+    let mut plugins = Vec::new();
+    for dir in dirs {
+        let manifest_path = dir.join("Cargo.toml");
+        let manifest = fs::read_to_string(&manifest_path)
+            .map_err(|e| format!("unable to read {}: {}", manifest_path.display(), e))?;
+        let config = PluginConfig::from_manifest(&manifest)
+            .map_err(|e| format!("{}: {}", manifest_path.display(), e))?;
+        plugins.push(Plugin { dir, config });
+    }
+    Ok(plugins)
+}
+
+/// The config schema of the plugins.
+fn schema(plugins: &[Plugin]) -> String {
+    let configs: Vec<PluginConfig> = plugins.iter().map(|p| p.config.clone()).collect();
+    slapi_r_plugin_build::schema(&configs)
+}
+
+fn generate(root: &Path, plugins: &[Plugin]) -> Result<(), String> {
+    for plugin in plugins {
+        plugin.config.write(&plugin.dir)?;
+    }
+    slapi_r_plugin_build::write_if_changed(&root.join("schema").join(SCHEMA_FILE), &schema(plugins))
+}
+
+fn build(plugins: &[Plugin], options: &Options) -> Result<(), String> {
+    let cargo = env::var("CARGO").unwrap_or("cargo".to_string());
+    for plugin in plugins {
+        let mut cmd = Command::new(&cargo);
+        cmd.arg("build").arg("--manifest-path").arg(plugin.dir.join("Cargo.toml"));
+        if options.release {
+            cmd.arg("--release");
+        }
+        let status = cmd.status().map_err(|e| format!("unable to run {}: {}", cargo, e))?;
+        if !status.success() {
+            return Err(format!("building {} failed", plugin.config.name));
+        }
+    }
+    Ok(())
+}
+
+fn copy(from: &Path, to_dir: &Path, name: &str) -> Result<(), String> {
+    fs::create_dir_all(to_dir).map_err(|e| format!("unable to create {}: {}", to_dir.display(), e))?;
+    let to = to_dir.join(name);
+    fs::copy(from, &to).map_err(|e| format!("unable to copy {} to {}: {}", from.display(), to.display(), e))?;
+    println!("staged {}", to.display());
+    Ok(())
+}
+
+fn stage(root: &Path, plugins: &[Plugin], options: &Options) -> Result<(), String> {
+    build(plugins, options)?;
+    let destdir = options.destdir.as_ref().unwrap();
+    let target_dir = env::var("CARGO_TARGET_DIR").map(PathBuf::from).unwrap_or(root.join("target"));
+    let built_dir = target_dir.join(options.profile());
+    let plugin_dir = under(destdir, &options.libdir).join("dirsrv/plugins");
+    let example_dir = under(destdir, &options.datadir).join("dirsrv/examples");
+    let schema_dir = under(destdir, &options.datadir).join("dirsrv/schema");
+
+    for plugin in plugins {
+        let lib = format!("{}.so", plugin.config.path);
+        copy(&built_dir.join(&lib), &plugin_dir, &lib)?;
+        copy(&plugin.dir.join("enable.ldif"), &example_dir.join(&plugin.config.name), "enable.ldif")?;
+    }
+    copy(&root.join("schema").join(SCHEMA_FILE), &schema_dir, SCHEMA_FILE)
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    let root = workspace_root();
+    let result = find_plugins(&root).and_then(|plugins| match options.command.as_str() {
+        "generate" => generate(&root, &plugins),
+        "build" => build(&plugins, &options),
+        _ => stage(&root, &plugins, &options),
+    });
+    if let Err(e) = result {
+        eprintln!("xtask: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_options_parse() {
+        let o = parse(&["stage", "--release", "--destdir", "/tmp/root", "--libdir", "/usr/lib64"]).unwrap();
+        assert_eq!(o.profile(), "release");
+        assert_eq!(o.destdir, Some(PathBuf::from("/tmp/root")));
+        assert_eq!(under(o.destdir.as_ref().unwrap(), &o.libdir), PathBuf::from("/tmp/root/usr/lib64"));
+        assert_eq!(o.datadir, "share");
+
+        assert!(parse(&["build"]).is_ok());
+        assert!(parse(&["generate"]).is_ok());
+        assert!(parse(&["stage"]).is_err());
+        assert!(parse(&["build", "--libdir"]).is_err());
+        assert!(parse(&["install"]).is_err());
+    }
+
+    #[test]
+    fn test_find_plugins() {
+        let plugins = find_plugins(&workspace_root()).unwrap();
+        let names: Vec<&str> = plugins.iter().map(|p| p.config.name.as_str()).collect();
+        assert_eq!(names, vec!["hellorust", "ro_replica"]);
+    }
+
+    #[test]
+    fn test_generated_files_are_current() {
+        // The checked in files must be what generate writes, or the metadata
+        // of a plugin was changed without running cargo xtask generate.
+        let root = workspace_root();
+        let plugins = find_plugins(&root).unwrap();
+        for plugin in plugins.iter() {
+            let ldif = fs::read_to_string(plugin.dir.join("enable.ldif")).unwrap();
+            assert_eq!(ldif, plugin.config.ldif());
+            let python_path = plugin.dir.join(&plugin.config.python_dir).join(format!("{}.py", plugin.config.name));
+            assert_eq!(fs::read_to_string(python_path).unwrap(), plugin.config.python());
+        }
+        assert_eq!(fs::read_to_string(root.join("schema").join(SCHEMA_FILE)).unwrap(), schema(&plugins));
+    }
+}